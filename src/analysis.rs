@@ -0,0 +1,280 @@
+use crate::{
+    board::{Board, Stone},
+    drawing::{
+        draw_multiline_text, pressed, refresh, scaled, scaled_size, Button, Held, HoldTracker,
+        SCREEN_HEIGHT,
+    },
+    engine::Engine,
+    reset::{draw_back, handle_back, space_before_back},
+    routine::Routine,
+    settings::SETTINGS,
+    sgf::{save_sgf, setup_sgf},
+};
+use libremarkable::{
+    appctx,
+    cgmath::{Point2, Vector2},
+    framebuffer::core::Framebuffer,
+    input::MultitouchEvent,
+};
+use log::{info, warn};
+use std::time::Instant;
+
+#[derive(Clone, Copy)]
+enum Action {
+    Clear,
+    Save,
+    ToggleColour,
+}
+
+const BUTTON_SIZE: Vector2<u32> = Vector2 { x: 350, y: 95 };
+
+/// Free placement of stones, with no engine checking anything, for setting up positions
+pub struct Analysis {
+    board: Board,
+    /// Row-major, 0-based from the top-left
+    stones: Vec<Option<Stone>>,
+    /// What an empty point becomes when tapped
+    first_colour: Stone,
+    message: Option<String>,
+    hold: HoldTracker,
+}
+
+impl Analysis {
+    pub fn new() -> Analysis {
+        let board = Board::new(SETTINGS.local_board_size());
+        let points = board.board_size as usize * board.board_size as usize;
+        Analysis {
+            board,
+            stones: vec![None; points],
+            first_colour: Stone::Black,
+            message: None,
+            hold: HoldTracker::default(),
+        }
+    }
+
+    fn index(&self, point: Point2<u8>) -> usize {
+        point.y as usize * self.board.board_size as usize + point.x as usize
+    }
+
+    /// Empty -> first colour -> other colour -> empty
+    fn cycle(&mut self, point: Point2<u8>) -> Option<Stone> {
+        let index = self.index(point);
+        self.stones[index] = match self.stones[index] {
+            None => Some(self.first_colour),
+            Some(stone) if stone == self.first_colour => Some(stone.other()),
+            Some(_) => None,
+        };
+        self.stones[index]
+    }
+
+    /// 0-based points of one colour
+    fn stones_of(&self, colour: Stone) -> Vec<Point2<u8>> {
+        let size = self.board.board_size as usize;
+        self.stones
+            .iter()
+            .enumerate()
+            .filter(|(_, stone)| **stone == Some(colour))
+            .map(|(index, _)| Point2 {
+                x: (index % size) as u8,
+                y: (index / size) as u8,
+            })
+            .collect()
+    }
+
+    fn act(&mut self, action: Action) {
+        match action {
+            Action::Clear => {
+                self.stones.fill(None);
+                self.message = None;
+            }
+            Action::Save => self.save(),
+            Action::ToggleColour => self.first_colour = self.first_colour.other(),
+        }
+    }
+
+    fn buttons(&self) -> Vec<Button<Action>> {
+        let left = self.board.spare_width as i32;
+        let size = scaled_size(BUTTON_SIZE);
+        let second_row = 20 + scaled(120) as i32;
+        let save_x = left + scaled(400) as i32;
+        // Drop down a row if big buttons would run into the exit button
+        let save_y = if size.x > space_before_back(save_x) {
+            second_row
+        } else {
+            20
+        };
+        vec![
+            Button::new("Clear", Point2 { x: left, y: 20 }, size, Action::Clear).held(),
+            Button::new(
+                "Save",
+                Point2 {
+                    x: save_x,
+                    y: save_y,
+                },
+                size,
+                Action::Save,
+            ),
+            Button::new(
+                match self.first_colour {
+                    Stone::Black => "Black",
+                    Stone::White => "White",
+                },
+                Point2 {
+                    x: left,
+                    y: second_row,
+                },
+                size,
+                Action::ToggleColour,
+            ),
+        ]
+    }
+
+    fn redraw(&self, fb: &mut Framebuffer) {
+        // draw_board wants GTP-style 1-based points
+        let one_based = |points: Vec<Point2<u8>>| -> Vec<Point2<u8>> {
+            points
+                .iter()
+                .map(|p| Point2 {
+                    x: p.x + 1,
+                    y: p.y + 1,
+                })
+                .collect()
+        };
+        self.board.draw_board(
+            fb,
+            &one_based(self.stones_of(Stone::White)),
+            &one_based(self.stones_of(Stone::Black)),
+        );
+        for button in self.buttons() {
+            button.draw(fb);
+        }
+        draw_back(fb);
+        if let Some(ref message) = self.message {
+            draw_multiline_text(
+                fb,
+                Point2 {
+                    x: self.board.spare_width as f32,
+                    y: (SCREEN_HEIGHT - 222) as f32,
+                },
+                message,
+                40.0,
+                50,
+            );
+        }
+        refresh(fb);
+    }
+
+    fn save(&mut self) {
+        let sgf = setup_sgf(
+            self.board.board_size,
+            &self.stones_of(Stone::Black),
+            &self.stones_of(Stone::White),
+        );
+        self.message = Some(match save_sgf("setup", &sgf) {
+            Ok(path) => format!("Saved to {}", path.display()),
+            Err(err) => {
+                warn!("Can't save setup: {err}");
+                format!("Can't save: {err}")
+            }
+        });
+    }
+}
+
+impl Routine for Analysis {
+    fn init(&mut self, fb: &mut Framebuffer, _ctrl: &mut Engine) {
+        self.redraw(fb);
+    }
+
+    fn on_multitouch_event(
+        &mut self,
+        ctx: &mut appctx::ApplicationContext<'_>,
+        event: MultitouchEvent,
+        _ctrl: &mut Engine,
+    ) {
+        let buttons = self.buttons();
+        match self
+            .hold
+            .handle(ctx.get_framebuffer_ref(), &buttons, &event)
+        {
+            Held::Fired(action) => {
+                self.act(action);
+                self.redraw(ctx.get_framebuffer_ref());
+                return;
+            }
+            Held::Holding => return,
+            Held::Ignored => {}
+        }
+
+        if let MultitouchEvent::Press { finger } = event {
+            let start = Instant::now();
+            let fb = ctx.get_framebuffer_ref();
+
+            if handle_back(ctx, finger.pos) {
+                return;
+            }
+
+            if let Some(button) = pressed(&buttons, finger.pos) {
+                self.act(button.action);
+                self.redraw(fb);
+                return;
+            }
+
+            let Some(point) = self.board.pixel_to_point(finger.pos) else {
+                info!("Bad point {:?}", finger.pos);
+                return;
+            };
+            let previous = self.stones[self.index(point)];
+            match (previous, self.cycle(point)) {
+                // Only placing on an empty point can be drawn on its own; anything else needs the grid back
+                (None, Some(stone)) => {
+                    self.board.refresh_and_draw_one_piece(
+                        fb,
+                        point.x,
+                        point.y,
+                        stone == Stone::White,
+                    );
+                }
+                _ => self.redraw(fb),
+            }
+
+            let elapsed = start.elapsed();
+            info!("touch elapsed: {:.2?}", elapsed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use libremarkable::cgmath::Point2;
+    use pretty_assertions::assert_eq;
+
+    use crate::{analysis::Analysis, board::Stone};
+
+    #[test]
+    fn tap_cycles() {
+        let mut analysis = Analysis::new();
+        let point = Point2 { x: 2, y: 3 };
+        assert_eq!(Some(Stone::Black), analysis.cycle(point));
+        assert_eq!(Some(Stone::White), analysis.cycle(point));
+        assert_eq!(None, analysis.cycle(point));
+        analysis.first_colour = Stone::White;
+        assert_eq!(Some(Stone::White), analysis.cycle(point));
+        assert_eq!(Some(Stone::Black), analysis.cycle(point));
+    }
+
+    #[test]
+    fn stones_by_colour() {
+        let mut analysis = Analysis::new();
+        analysis.cycle(Point2 { x: 2, y: 3 });
+        analysis.cycle(Point2 { x: 4, y: 1 });
+        analysis.cycle(Point2 { x: 4, y: 1 });
+        assert_eq!(
+            vec![Point2 { x: 2, y: 3 }],
+            analysis.stones_of(Stone::Black)
+        );
+        assert_eq!(
+            vec![Point2 { x: 4, y: 1 }],
+            analysis.stones_of(Stone::White)
+        );
+    }
+}
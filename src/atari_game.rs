@@ -1,23 +1,33 @@
 use crate::{
-    board::{Board, AVAILABLE_WIDTH},
-    chooser::CURRENT_MODE,
-    drawing::{draw_button, refresh, refresh_with_options},
-    gtp::{clear_board, count_captures, do_human_move, list_stones, set_board_size, undo_move},
-    reset::{draw_reset, reset_button_top_left, RESET_BUTTON_SIZE},
+    board::{Board, Stone, AVAILABLE_WIDTH},
+    chooser::Mode,
+    drawing::{
+        centred_x, draw_status_note, pressed, refresh, scaled, scaled_size, submit_refresh,
+        wait_refresh, Button, Held, HoldTracker, StatusBar, SCREEN_HEIGHT,
+    },
+    engine::Engine,
+    game_state::{
+        clear_state, draw_resume_choice, load_state, resume_choice, save_state, GameState,
+        LocalGame, ResumeChoice,
+    },
+    gtp::{
+        board_size_fallback_text, clear_board, count_captures, do_human_move, legal_moves,
+        replay_moves, set_supported_board_size, suggest_move, undo_move, GtpError, StoneCache,
+    },
+    move_choice::DoubleTap,
+    reset::{draw_back, handle_back},
+    review::review_just_played,
     routine::Routine,
+    settings::SETTINGS,
+    sgf::game_sgf,
 };
-use gtp::controller::Engine;
 use libremarkable::{
     appctx,
     cgmath::{Point2, Vector2},
-    framebuffer::{
-        common::{color, mxcfb_rect, waveform_mode},
-        core::Framebuffer,
-        FramebufferDraw,
-    },
+    framebuffer::{common::waveform_mode, core::Framebuffer, FramebufferDraw},
     input::MultitouchEvent,
 };
-use log::info;
+use log::{error, info};
 use std::time::Instant;
 
 #[derive(PartialEq, Debug, Clone, Copy)]
@@ -26,27 +36,146 @@ enum Turn {
     BlackTurn = 2,
 }
 
+impl Turn {
+    fn stone(self) -> Stone {
+        match self {
+            Turn::WhiteTurn => Stone::White,
+            Turn::BlackTurn => Stone::Black,
+        }
+    }
+
+    fn of(stone: Stone) -> Turn {
+        match stone {
+            Stone::White => Turn::WhiteTurn,
+            Stone::Black => Turn::BlackTurn,
+        }
+    }
+}
+
 pub struct AtariGame {
     board: Board,
     current_turn: Turn,
+    first_turn: Option<Turn>,
     game_end: Option<Turn>,
-    undo_button_top_left: Point2<i32>,
+    undo_button: Button<()>,
+    hint_button: Button<()>,
+    /// GnuGo's suggestion for the current player, in GTP coordinates
+    hint: Option<Point2<u8>>,
+    stone_cache: StoneCache,
+    /// Times both players have moved, counting from whoever went first
+    turns: usize,
+    /// Stones each side has taken, from GTP `captures`
+    black_captures: usize,
+    white_captures: usize,
+    /// Every move so far, like the machine game's, though there aren't any passes here
+    history: Vec<(Stone, Option<Point2<u8>>)>,
+    /// A game saved before a crash, while asking whether to pick it back up
+    resume: Option<GameState>,
+    hold: HoldTracker,
+    double_tap: DoubleTap,
 }
 
 pub const UNDO_BUTTON_SIZE: Vector2<u32> = Vector2 { x: 350, y: 95 };
 
+const FIRST_TURN_BUTTON_SIZE: Vector2<u32> = Vector2 { x: 700, y: 95 };
+
+#[derive(Clone, Copy)]
+enum EndAction {
+    Review,
+    NewGame,
+}
+
+/// Under the board once someone's won, like the machine game's buttons
+fn end_buttons(board: &Board) -> Vec<Button<EndAction>> {
+    let size = scaled_size(UNDO_BUTTON_SIZE);
+    let y = SCREEN_HEIGHT as i32 - 152;
+    vec![
+        Button::new(
+            "Review",
+            Point2 {
+                x: board.spare_width as i32,
+                y,
+            },
+            size,
+            EndAction::Review,
+        ),
+        Button::new(
+            "New game",
+            Point2 {
+                x: (board.spare_width + board.grid_size()) as i32 - size.x as i32,
+                y,
+            },
+            size,
+            EndAction::NewGame,
+        )
+        // Throws the finished game away, like the machine game's
+        .held(),
+    ]
+}
+
+fn first_turn_buttons() -> Vec<Button<Turn>> {
+    let size = scaled_size(FIRST_TURN_BUTTON_SIZE);
+    [
+        ("Black first", Turn::BlackTurn, 300),
+        ("White first", Turn::WhiteTurn, 500),
+    ]
+    .iter()
+    .map(|(text, turn, y)| {
+        Button::new(
+            text,
+            Point2 {
+                x: centred_x(size.x),
+                y: *y,
+            },
+            size,
+            *turn,
+        )
+    })
+    .collect()
+}
+
 impl AtariGame {
     pub fn new() -> AtariGame {
-        let board: Board = Board::new(9);
-        let undo_button_top_left = Point2 {
-            x: (board.spare_width + AVAILABLE_WIDTH / 2 - 10) as i32,
-            y: 120,
-        };
+        AtariGame::with_size(SETTINGS.local_board_size())
+    }
+
+    /// For a board other than the settings' size, when the engine won't play on that
+    fn with_size(board_size: u8) -> AtariGame {
+        let board: Board = Board::new(board_size);
+        let undo_button = Button::new(
+            "Undo",
+            Point2 {
+                x: (board.spare_width + AVAILABLE_WIDTH / 2 - 10) as i32,
+                y: scaled(120) as i32,
+            },
+            scaled_size(UNDO_BUTTON_SIZE),
+            (),
+        );
+        let hint_button = Button::new(
+            "Hint",
+            Point2 {
+                x: board.spare_width as i32,
+                y: scaled(120) as i32,
+            },
+            scaled_size(UNDO_BUTTON_SIZE),
+            (),
+        );
         AtariGame {
             board,
             current_turn: Turn::BlackTurn,
+            first_turn: None,
             game_end: None,
-            undo_button_top_left,
+            undo_button,
+            hint_button,
+            hint: None,
+            stone_cache: StoneCache::default(),
+            turns: 0,
+            black_captures: 0,
+            white_captures: 0,
+            history: vec![],
+            resume: None,
+            hold: HoldTracker::default(),
+            double_tap: DoubleTap::default(),
         }
     }
 
@@ -67,48 +196,133 @@ impl AtariGame {
     }
 
     fn draw_status(&self, fb: &mut Framebuffer, text: &str, refresh: bool) {
-        let rect_width = 550;
-        fb.fill_rect(
-            Point2 {
-                x: self.board.spare_width as i32,
-                y: 0,
-            },
-            Vector2 {
-                x: rect_width,
-                y: 100,
-            },
-            color::WHITE,
+        let status = StatusBar::new(self.board.spare_width as i32);
+        status.draw(fb, text, refresh);
+        draw_status_note(
+            fb,
+            status.right(text),
+            status.height(),
+            &format!(
+                "Turn {}\nTaken: B {} W {}",
+                self.turns + 1,
+                self.black_captures,
+                self.white_captures
+            ),
+            refresh,
         );
-        fb.draw_text(
-            Point2 {
-                x: self.board.spare_width as f32,
-                y: 100.0,
-            },
-            text,
-            100.0,
-            color::BLACK,
-            false,
+
+        self.undo_button.draw(fb);
+        self.hint_button.draw(fb);
+    }
+
+    fn reset_game(&mut self, ctrl: &mut Engine, fb: &mut Framebuffer) -> Result<(), GtpError> {
+        clear_board(ctrl)?;
+        self.stone_cache.force_refresh(ctrl)?;
+        self.turns = 0;
+        self.black_captures = 0;
+        self.white_captures = 0;
+        self.history.clear();
+        self.double_tap.clear();
+        clear_state();
+        self.redraw_stones(ctrl, fb)
+    }
+
+    /// Picks a saved game back up, with everything worked out from its moves
+    fn resume_game(
+        &mut self,
+        ctrl: &mut Engine,
+        fb: &mut Framebuffer,
+        state: GameState,
+    ) -> Result<(), GtpError> {
+        let history = state.history();
+        info!("Resuming {} moves", history.len());
+        replay_moves(ctrl, self.board.board_size, &history)?;
+        self.stone_cache.force_refresh(ctrl)?;
+        self.update_captures(ctrl)?;
+        self.first_turn = Some(Turn::of(state.player));
+        // As count_turn does, a turn goes by with each move by whoever went second
+        self.turns = history
+            .iter()
+            .filter(|(stone, _)| *stone != state.player)
+            .count();
+        self.current_turn = Turn::of(
+            history
+                .last()
+                .map_or(state.player, |(stone, _)| stone.other()),
         );
+        self.history = history;
+        self.redraw_stones(ctrl, fb)
+    }
 
-        draw_button(fb, "Undo", self.undo_button_top_left, UNDO_BUTTON_SIZE);
+    fn save_state(&self) {
+        if let Some(first_turn) = self.first_turn {
+            save_state(&GameState::new(
+                LocalGame::Atari,
+                self.board.board_size,
+                first_turn.stone(),
+                &self.history,
+            ));
+        }
+    }
 
-        if refresh {
-            refresh_with_options(
-                fb,
-                &mxcfb_rect {
-                    top: 0,
-                    left: self.board.spare_width as u32,
-                    width: rect_width,
-                    height: 100,
-                },
-                waveform_mode::WAVEFORM_MODE_AUTO,
-            );
+    /// Records a move that's been played, and whether it won the game
+    fn record_move(&mut self, point: Point2<u8>) {
+        self.history.push((self.current_turn.stone(), Some(point)));
+        if self.game_end.is_some() {
+            clear_state();
+        } else {
+            self.save_state();
         }
     }
 
-    fn reset_game(&self, ctrl: &mut Engine, fb: &mut Framebuffer) {
-        clear_board(ctrl);
-        self.redraw_stones(ctrl, fb);
+    fn choose_resume(&mut self, ctrl: &mut Engine, fb: &mut Framebuffer, choice: ResumeChoice) {
+        let Some(state) = self.resume.take() else {
+            return;
+        };
+        match choice {
+            ResumeChoice::Resume => {
+                if let Err(err) = self.resume_game(ctrl, fb, state) {
+                    // Most likely GnuGo wouldn't take one of the saved moves, so don't offer it again
+                    clear_state();
+                    self.show_engine_error(fb, err);
+                }
+            }
+            ResumeChoice::NewGame => {
+                clear_state();
+                self.draw_first_turn_choice(fb);
+            }
+        }
+    }
+
+    /// The moves so far, with whoever took a stone first as the winner
+    fn game_sgf(&self) -> String {
+        let result = self.game_end.map(|winner| match winner {
+            Turn::BlackTurn => "B+",
+            Turn::WhiteTurn => "W+",
+        });
+        game_sgf(self.board.board_size, &self.history, result)
+    }
+
+    /// Back to choosing who goes first, as at the start
+    fn new_game(&mut self, fb: &mut Framebuffer) {
+        self.first_turn = None;
+        self.game_end = None;
+        self.hint = None;
+        clear_state();
+        self.draw_first_turn_choice(fb);
+    }
+
+    fn update_captures(&mut self, ctrl: &mut Engine) -> Result<(), GtpError> {
+        self.black_captures = count_captures(ctrl, "black")?;
+        self.white_captures = count_captures(ctrl, "white")?;
+        Ok(())
+    }
+
+    /// Call before changing turn, after a move's been played
+    fn count_turn(&mut self) {
+        if Some(self.current_turn) != self.first_turn {
+            self.turns += 1;
+        }
     }
 
     fn draw_game_state(&self, fb: &mut Framebuffer) {
@@ -119,24 +333,228 @@ impl AtariGame {
         }
     }
 
-    fn redraw_stones(&self, ctrl: &mut Engine, fb: &mut Framebuffer) {
+    fn draw_first_turn_choice(&self, fb: &mut Framebuffer) {
+        fb.clear();
+        for button in first_turn_buttons() {
+            button.draw(fb);
+        }
+        draw_back(fb);
+        refresh(fb);
+    }
+
+    fn redraw_stones(&mut self, ctrl: &mut Engine, fb: &mut Framebuffer) -> Result<(), GtpError> {
         let start = Instant::now();
-        let white_stones = list_stones(ctrl, "white");
-        let black_stones = list_stones(ctrl, "black");
+        let (white_stones, black_stones) = self.stone_cache.stones(ctrl)?;
         self.board.draw_board(fb, &white_stones, &black_stones);
+        if SETTINGS.show_legal_moves && self.game_end.is_none() {
+            if let Some(legal) = legal_moves(ctrl, self.current_turn.stone().gtp_name())? {
+                self.board.draw_legal_markers(fb, &legal);
+            }
+        }
         self.draw_game_state(fb);
-        draw_reset(&self.board, fb);
+        if self.game_end.is_some() {
+            for button in end_buttons(&self.board) {
+                button.draw(fb);
+            }
+        }
+        draw_back(fb);
         refresh(fb);
         let elapsed = start.elapsed();
         info!("redraw elapsed: {:.2?}", elapsed);
+        Ok(())
+    }
+
+    /// Just the new stone, unless the legal moves for the next player need showing
+    fn draw_move(
+        &mut self,
+        ctrl: &mut Engine,
+        fb: &mut Framebuffer,
+        point: Point2<u8>,
+        white: bool,
+    ) -> Result<(), GtpError> {
+        if SETTINGS.show_legal_moves {
+            return self.redraw_stones(ctrl, fb);
+        }
+        if SETTINGS.flash_captures {
+            // Kept up to date, so the capture that ends the game can be found from it
+            self.stone_cache.stones(ctrl)?;
+        }
+        self.board
+            .refresh_and_draw_one_piece(fb, point.x, point.y, white);
+        Ok(())
+    }
+
+    /// The winning move, with the stones it took flashed first if the settings want that
+    fn draw_win(&mut self, ctrl: &mut Engine, fb: &mut Framebuffer) -> Result<(), GtpError> {
+        if SETTINGS.flash_captures {
+            let captured = self.stone_cache.removed(ctrl)?;
+            self.board.flash_stones(fb, &captured);
+        }
+        self.redraw_stones(ctrl, fb)
+    }
+
+    fn show_engine_error(&self, fb: &mut Framebuffer, err: GtpError) {
+        error!("Engine error: {err}");
+        self.draw_status(fb, err.status_text(), true);
+    }
+
+    fn toggle_hint(&mut self, ctrl: &mut Engine, fb: &mut Framebuffer) -> Result<(), GtpError> {
+        if self.hint.take().is_some() {
+            return self.redraw_stones(ctrl, fb);
+        }
+        let colour = match self.current_turn {
+            Turn::WhiteTurn => "white",
+            Turn::BlackTurn => "black",
+        };
+        self.draw_status(fb, "Thinking...", true);
+        match suggest_move(ctrl, colour)? {
+            Some(point) => {
+                let rect = self.board.draw_hint_marker(fb, point.x - 1, point.y - 1);
+                // The marker and the status can both update at once
+                let marker = submit_refresh(fb, &rect, waveform_mode::WAVEFORM_MODE_DU);
+                self.hint = Some(point);
+                self.draw_turn(fb, true);
+                wait_refresh(fb, marker);
+            }
+            None => self.draw_status(fb, "Hint: pass", true),
+        }
+        Ok(())
+    }
+
+    fn handle_press(
+        &mut self,
+        ctrl: &mut Engine,
+        fb: &mut Framebuffer,
+        pos: Point2<u16>,
+    ) -> Result<(), GtpError> {
+        if self.first_turn.is_none() {
+            if let Some(button) = pressed(&first_turn_buttons(), pos) {
+                let turn = button.action;
+                info!("First turn: {turn:?}");
+                self.first_turn = Some(turn);
+                self.current_turn = turn;
+                self.reset_game(ctrl, fb)?;
+            }
+            return Ok(());
+        }
+
+        if self.hint_button.contains(pos) {
+            if self.game_end.is_none() {
+                self.toggle_hint(ctrl, fb)?;
+            }
+            return Ok(());
+        }
+
+        if self.undo_button.contains(pos) {
+            self.hint = None;
+            if undo_move(ctrl)? {
+                self.history.pop();
+                // A winning move leaves the turn with the winner, and otherwise the undone move
+                // was by whoever's turn it isn't. Either way, play carries on from before it
+                let undone = self
+                    .game_end
+                    .take()
+                    .unwrap_or_else(|| Turn::of(self.current_turn.stone().other()));
+                self.save_state();
+                self.stone_cache.board_changed();
+                self.update_captures(ctrl)?;
+                if Some(undone) != self.first_turn {
+                    self.turns = self.turns.saturating_sub(1);
+                }
+                self.set_turn(undone, fb);
+                self.redraw_stones(ctrl, fb)?;
+            }
+            return Ok(());
+        }
+
+        let Some(point) = self.board.pixel_to_point(pos) else {
+            info!("Bad point {pos:?}");
+            self.double_tap.clear();
+            return Ok(());
+        };
+        if !self
+            .double_tap
+            .confirm(point, Instant::now(), SETTINGS.double_tap_window())
+        {
+            self.draw_status(fb, "Tap again to play", true);
+            return Ok(());
+        }
+        info!("Drawing: {point:?} for {pos:?}");
+        if self.hint.take().is_some() {
+            // Clear the marker before the move gets drawn over it
+            self.redraw_stones(ctrl, fb)?;
+        }
+
+        match self.current_turn {
+            Turn::WhiteTurn => {
+                if !do_human_move(ctrl, point, "white")?.played() {
+                    info!("Bad white move");
+                    return Ok(());
+                }
+                self.stone_cache.board_changed();
+                self.count_turn();
+                self.update_captures(ctrl)?;
+                if self.white_captures > 0 {
+                    info!("White win");
+
+                    self.game_end = Some(Turn::WhiteTurn);
+                    self.record_move(point);
+                    self.draw_win(ctrl, fb)?;
+                } else {
+                    self.record_move(point);
+                    self.set_turn(Turn::BlackTurn, fb);
+                    self.draw_move(ctrl, fb, point, true)?;
+                }
+            }
+            Turn::BlackTurn => {
+                if !do_human_move(ctrl, point, "black")?.played() {
+                    info!("Bad black move");
+                    return Ok(());
+                }
+                self.stone_cache.board_changed();
+                self.count_turn();
+                self.update_captures(ctrl)?;
+                if self.black_captures > 0 {
+                    info!("Black win");
+                    self.game_end = Some(Turn::BlackTurn);
+                    self.record_move(point);
+                    self.draw_win(ctrl, fb)?;
+                } else {
+                    self.record_move(point);
+                    self.set_turn(Turn::WhiteTurn, fb);
+                    self.draw_move(ctrl, fb, point, false)?;
+                }
+            }
+        };
+        Ok(())
     }
 }
 
 impl Routine for AtariGame {
     fn init(&mut self, fb: &mut Framebuffer, ctrl: &mut Engine) {
-        set_board_size(ctrl, self.board.board_size);
-        self.set_turn(Turn::BlackTurn, fb);
-        self.reset_game(ctrl, fb);
+        let asked = self.board.board_size;
+        match set_supported_board_size(ctrl, asked) {
+            Ok(size) if size != asked => *self = AtariGame::with_size(size),
+            Ok(_) => {}
+            Err(err) => {
+                self.show_engine_error(fb, err);
+                return;
+            }
+        }
+        self.resume = load_state(LocalGame::Atari, self.board.board_size);
+        if self.resume.is_some() {
+            draw_resume_choice(fb);
+        } else {
+            self.draw_first_turn_choice(fb);
+        }
+        if self.board.board_size != asked {
+            // Just the status, as the choice screen doesn't have the turn note
+            StatusBar::new(self.board.spare_width as i32).draw(
+                fb,
+                &board_size_fallback_text(asked, self.board.board_size),
+                true,
+            );
+        }
     }
 
     fn on_multitouch_event(
@@ -145,85 +563,51 @@ impl Routine for AtariGame {
         event: MultitouchEvent,
         ctrl: &mut Engine,
     ) {
-        match event {
-            MultitouchEvent::Press { finger } => {
-                let start = Instant::now();
-                let fb = ctx.get_framebuffer_ref();
-
-                let rbtl = reset_button_top_left(&self.board);
-                if (finger.pos.x as i32) >= rbtl.x
-                    && (finger.pos.x as i32) < (rbtl.x + RESET_BUTTON_SIZE.x as i32)
-                    && (finger.pos.y as i32) >= rbtl.y
-                    && (finger.pos.y as i32) < (rbtl.y + RESET_BUTTON_SIZE.y as i32)
-                {
-                    *CURRENT_MODE.lock().unwrap() = crate::chooser::Mode::Chooser;
-                    ctx.stop();
-                    return;
-                }
+        if self.resume.is_some() {
+            if let Some(choice) = resume_choice(&mut self.hold, ctx, &event) {
+                self.choose_resume(ctrl, ctx.get_framebuffer_ref(), choice);
+            }
+            return;
+        }
 
-                if (finger.pos.x as i32) >= self.undo_button_top_left.x
-                    && (finger.pos.x as i32)
-                        < (self.undo_button_top_left.x + UNDO_BUTTON_SIZE.x as i32)
-                    && (finger.pos.y as i32) >= self.undo_button_top_left.y
-                    && (finger.pos.y as i32)
-                        < (self.undo_button_top_left.y + UNDO_BUTTON_SIZE.y as i32)
-                {
-                    if undo_move(ctrl) {
-                        match self.current_turn {
-                            Turn::WhiteTurn => self.set_turn(Turn::BlackTurn, fb),
-                            Turn::BlackTurn => self.set_turn(Turn::WhiteTurn, fb),
-                        }
-                        self.redraw_stones(ctrl, fb);
-                    }
+        if self.game_end.is_some() {
+            let fb = ctx.get_framebuffer_ref();
+            match self.hold.handle(fb, &end_buttons(&self.board), &event) {
+                Held::Fired(EndAction::NewGame) => {
+                    self.new_game(fb);
                     return;
                 }
+                Held::Fired(EndAction::Review) | Held::Holding => return,
+                Held::Ignored => {}
+            }
+        }
 
-                let point = self.board.nearest_spot(finger.pos.x, finger.pos.y);
-                let pos = finger.pos;
-                if point.x >= self.board.board_size || point.y >= self.board.board_size {
-                    info!("Bad point {point:?}");
+        if let MultitouchEvent::Press { finger } = event {
+            let start = Instant::now();
+            let fb = ctx.get_framebuffer_ref();
+
+            if handle_back(ctx, finger.pos) {
+                return;
+            }
+
+            if self.game_end.is_some() {
+                let buttons = end_buttons(&self.board);
+                if let Some(button) = pressed(&buttons, finger.pos) {
+                    match button.action {
+                        EndAction::Review => review_just_played(ctx, self.game_sgf(), Mode::Atari),
+                        // Held, so that's handled above
+                        EndAction::NewGame => {}
+                    }
                     return;
                 }
-                info!("Drawing: {point:?} for {pos:?}");
-
-                match self.current_turn {
-                    Turn::WhiteTurn => {
-                        if !do_human_move(ctrl, point, "white") {
-                            info!("Bad white move");
-                            return;
-                        }
-                        if count_captures(ctrl, "white") > 0 {
-                            info!("White win");
-
-                            self.game_end = Some(Turn::WhiteTurn);
-                            self.redraw_stones(ctrl, fb);
-                        } else {
-                            self.set_turn(Turn::BlackTurn, fb);
-                            self.board
-                                .refresh_and_draw_one_piece(fb, point.x, point.y, true);
-                        }
-                    }
-                    Turn::BlackTurn => {
-                        if !do_human_move(ctrl, point, "black") {
-                            info!("Bad black move");
-                            return;
-                        }
-                        if count_captures(ctrl, "black") > 0 {
-                            info!("Black win");
-                            self.game_end = Some(Turn::BlackTurn);
-                            self.redraw_stones(ctrl, fb);
-                        } else {
-                            self.set_turn(Turn::WhiteTurn, fb);
-                            self.board
-                                .refresh_and_draw_one_piece(fb, point.x, point.y, false);
-                        }
-                    }
-                };
+            }
 
-                let elapsed = start.elapsed();
-                info!("touch elapsed: {:.2?}", elapsed);
+            if let Err(err) = self.handle_press(ctrl, fb, finger.pos) {
+                self.show_engine_error(fb, err);
             }
-            _ => {}
+
+            let elapsed = start.elapsed();
+            info!("touch elapsed: {:.2?}", elapsed);
         }
     }
 }
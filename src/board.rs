@@ -6,8 +6,18 @@ use libremarkable::{
         FramebufferDraw,
     },
 };
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, thread::sleep, time::Duration};
 
-use crate::drawing::refresh_with_options;
+use crate::{
+    drawing::{refresh_fast, submit_refresh, wait_refresh, SCREEN_HEIGHT, SCREEN_WIDTH},
+    gtp::StoneLists,
+    settings::{touch_offset, Orientation, StoneStyle, DEFAULT_STONE_BORDER, SETTINGS},
+};
+
+/// How long `flash_stones` leaves them blacked out
+const CAPTURE_FLASH: Duration = Duration::from_millis(400);
 
 pub struct Board {
     pub board_size: u8,
@@ -16,50 +26,495 @@ pub struct Board {
     pub circle_radius: u16,
     pub spare_width: u16,
     pub spare_height: u16,
+    pub stone_style: StoneStyle,
+    pub stone_border: u16,
+    pub grid_colour: color,
+    pub grid_width: u32,
+    pub stone_colour: color,
+    pub orientation: Orientation,
+}
+
+/// How the board gets drawn, which the settings can change
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoardStyle {
+    pub stone_style: StoneStyle,
+    /// Width in pixels of the outline on white stones
+    pub stone_border: u16,
+    pub grid_colour: color,
+    pub grid_width: u32,
+    /// Black stones, and the outline on white ones
+    pub stone_colour: color,
+    pub orientation: Orientation,
 }
 
-pub const AVAILABLE_WIDTH: u16 = libremarkable::dimensions::DISPLAYWIDTH - 200;
-const CIRCLE_BORDER: u16 = 5;
-const BORDER_WIDTH: u32 = 10;
+impl Default for BoardStyle {
+    fn default() -> Self {
+        BoardStyle {
+            stone_style: StoneStyle::default(),
+            stone_border: DEFAULT_STONE_BORDER,
+            grid_colour: color::BLACK,
+            grid_width: DEFAULT_GRID_WIDTH,
+            stone_colour: color::BLACK,
+            orientation: Orientation::default(),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Stone {
+    Black,
+    White,
+}
+
+impl Stone {
+    pub fn other(self) -> Stone {
+        match self {
+            Stone::Black => Stone::White,
+            Stone::White => Stone::Black,
+        }
+    }
+
+    /// Colour argument for GTP commands like `play` and `genmove`
+    pub fn gtp_name(self) -> &'static str {
+        match self {
+            Stone::Black => "black",
+            Stone::White => "white",
+        }
+    }
+}
+
+pub const AVAILABLE_WIDTH: u16 = SCREEN_WIDTH - 200;
+// Leaves room for the status and buttons above the board, and the same again below it
+const AVAILABLE_HEIGHT: u16 = SCREEN_HEIGHT - 600;
+pub const DEFAULT_GRID_WIDTH: u32 = 10;
+// Influence marks are grey, so they don't get confused with stones
+const INFLUENCE_GREY: u8 = 120;
+const INFLUENCE_OUTLINE: u32 = 3;
+// Legal move dots are light, so they don't look like the last move marker
+const LEGAL_GREY: u8 = 90;
+// Anything smaller has no squares, and would divide by zero working out how big they are
+const MIN_BOARD_SIZE: u8 = 2;
+
+/// Letter for the 0-based column `x`. GTP skips 'I' to avoid confusion with 'J', so it runs out
+/// after 25 columns
+fn column_letter(x: u8) -> Option<char> {
+    ('A'..='Z').filter(|letter| *letter != 'I').nth(x as usize)
+}
+
+/// GTP vertex for a point as GTP itself counts them, 1-based with A1 at (1, 1), like
+/// `list_stones` and `parse_vertex` give. `None` for a 0, which isn't on any board
+pub fn gtp_vertex(point: Point2<u8>) -> Option<String> {
+    if point.y == 0 {
+        return None;
+    }
+    let column = column_letter(point.x.checked_sub(1)?)?;
+    Some(format!("{column}{}", point.y))
+}
+
+/// GTP vertex for a point the way up that SGF and Dragon Go Server have it: 0-based from the
+/// top-left, so the top row is the board size and the bottom row is 1, e.g. "A9" for (0, 0) on
+/// a 9x9. `None` off the board. Local games are the other way up, see `gtp::do_human_move`
+pub fn point_to_gtp(point: Point2<u8>, board_size: u8) -> Option<String> {
+    if point.x >= board_size || point.y >= board_size {
+        return None;
+    }
+    gtp_vertex(Point2 {
+        x: point.x + 1,
+        y: board_size - point.y,
+    })
+}
+
+/// Stones in `after` that weren't in `before`, 0-based and with whether they're white. `None` if
+/// any stones went, as drawing the grid back under them needs the whole board
+pub fn added_stones(before: &StoneLists, after: &StoneLists) -> Option<Vec<(Point2<u8>, bool)>> {
+    let (before_white, before_black) = before;
+    let (after_white, after_black) = after;
+    let kept = |before: &Vec<Point2<u8>>, after: &Vec<Point2<u8>>| {
+        before.iter().all(|point| after.contains(point))
+    };
+    if !kept(before_white, after_white) || !kept(before_black, after_black) {
+        return None;
+    }
+    let new = |before: &Vec<Point2<u8>>, after: &Vec<Point2<u8>>, white: bool| {
+        after
+            .iter()
+            .filter(|point| !before.contains(point))
+            .map(|point| {
+                (
+                    Point2 {
+                        x: point.x - 1,
+                        y: point.y - 1,
+                    },
+                    white,
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    let mut added = new(before_white, after_white, true);
+    added.extend(new(before_black, after_black, false));
+    Some(added)
+}
+
+/// Stones in `before` that aren't in `after`, e.g. captures, 0-based and with whether they're
+/// white
+pub fn removed_stones(before: &StoneLists, after: &StoneLists) -> Vec<(Point2<u8>, bool)> {
+    let (before_white, before_black) = before;
+    let (after_white, after_black) = after;
+    let gone = |before: &Vec<Point2<u8>>, after: &Vec<Point2<u8>>, white: bool| {
+        before
+            .iter()
+            .filter(|point| !after.contains(point))
+            .map(|point| {
+                (
+                    Point2 {
+                        x: point.x - 1,
+                        y: point.y - 1,
+                    },
+                    white,
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    let mut removed = gone(before_white, after_white, true);
+    removed.extend(gone(before_black, after_black, false));
+    removed
+}
+
+/// Smallest rectangle covering all of `rects`
+fn bounding_rect(rects: &[mxcfb_rect]) -> Option<mxcfb_rect> {
+    let left = rects.iter().map(|r| r.left).min()?;
+    let top = rects.iter().map(|r| r.top).min()?;
+    let right = rects.iter().map(|r| r.left + r.width).max()?;
+    let bottom = rects.iter().map(|r| r.top + r.height).max()?;
+    Some(mxcfb_rect {
+        top,
+        left,
+        width: right - left,
+        height: bottom - top,
+    })
+}
+
+/// Where a 0-based `point` goes on screen with the board turned to `orientation`, as a 0-based
+/// point from the top-left. `last` is the highest coordinate
+fn orient(orientation: Orientation, point: Point2<u8>, last: u8) -> Point2<u8> {
+    let Point2 { x, y } = point;
+    match orientation {
+        Orientation::Normal => point,
+        Orientation::FlipHorizontal => Point2 { x: last - x, y },
+        Orientation::FlipVertical => Point2 { x, y: last - y },
+        Orientation::Rotate90 => Point2 { x: last - y, y: x },
+        Orientation::Rotate180 => Point2 {
+            x: last - x,
+            y: last - y,
+        },
+        Orientation::Rotate270 => Point2 { x: y, y: last - x },
+    }
+}
+
+/// Inverse of `orient`, for which point a spot on screen shows
+fn unorient(orientation: Orientation, screen: Point2<u8>, last: u8) -> Point2<u8> {
+    let Point2 { x, y } = screen;
+    match orientation {
+        Orientation::Rotate90 => Point2 { x: y, y: last - x },
+        Orientation::Rotate270 => Point2 { x: last - y, y: x },
+        // The rest undo themselves
+        _ => orient(orientation, screen, last),
+    }
+}
+
+/// Gap in pixels between grid lines, for a board as big as both the width and height allow
+fn fitted_square_size(square_count: u8, available: cgmath::Vector2<u16>) -> u16 {
+    available.x.min(available.y) / square_count as u16
+}
 
 impl Board {
     pub fn new(board_size: u8) -> Board {
+        Board::with_style(board_size, SETTINGS.board_style())
+    }
+
+    pub fn with_style(board_size: u8, style: BoardStyle) -> Board {
+        if board_size < MIN_BOARD_SIZE {
+            warn!("Can't draw a board of size {board_size}, so using {MIN_BOARD_SIZE}");
+        }
+        let board_size = board_size.max(MIN_BOARD_SIZE);
         let square_count = board_size - 1;
-        let square_size = AVAILABLE_WIDTH / square_count as u16;
-        Board {
+        let square_size = fitted_square_size(
+            square_count,
+            cgmath::Vector2 {
+                x: AVAILABLE_WIDTH,
+                y: AVAILABLE_HEIGHT,
+            },
+        );
+        let mut board = Board {
             board_size,
             square_count,
             square_size,
             circle_radius: ((square_size as f64 / 2_f64) * 0.6) as u16,
-            spare_width: (libremarkable::dimensions::DISPLAYWIDTH
-                - (square_size * square_count as u16))
-                / 2,
-            spare_height: (libremarkable::dimensions::DISPLAYHEIGHT
-                - (square_size * square_count as u16))
-                / 2,
+            spare_width: 0,
+            spare_height: 0,
+            stone_style: style.stone_style,
+            stone_border: 0,
+            grid_colour: style.grid_colour,
+            grid_width: style.grid_width,
+            stone_colour: style.stone_colour,
+            orientation: style.orientation,
+        };
+        // Anything wider than the stone would make white stones look black
+        board.stone_border = style
+            .stone_border
+            .clamp(1, board.circle_radius.saturating_sub(1).max(1));
+        // Centre on what actually gets drawn, not AVAILABLE_WIDTH, as square_size is truncated
+        board.spare_width = (SCREEN_WIDTH - board.grid_size()) / 2;
+        board.spare_height = (SCREEN_HEIGHT - board.grid_size()) / 2;
+        board
+    }
+
+    /// Distance in pixels between the first and last lines of the grid
+    pub fn grid_size(&self) -> u16 {
+        self.square_size * self.square_count as u16
+    }
+
+    /// Top edge of the highest stones on screen
+    pub fn top(&self) -> u32 {
+        self.spare_height.saturating_sub(self.circle_radius) as u32
+    }
+
+    /// Centre of a 0-based point, in screen pixels
+    pub fn point_to_pixel(&self, point: Point2<u8>) -> Point2<i32> {
+        self.screen_pixel(orient(self.orientation, point, self.board_size - 1))
+    }
+
+    /// Centre of an intersection on screen, counting from the top-left whichever way the board's
+    /// turned
+    fn screen_pixel(&self, screen: Point2<u8>) -> Point2<i32> {
+        Point2 {
+            x: (self.spare_width + self.square_size * screen.x as u16) as i32,
+            y: (self.spare_height + self.square_size * screen.y as u16) as i32,
         }
     }
 
+    /// Nearest 0-based point to a touch, or `None` if it's off the board
+    pub fn pixel_to_point(&self, pos: Point2<u16>) -> Option<Point2<u8>> {
+        self.pixel_to_point_with_offset(pos, touch_offset())
+    }
+
+    /// `pixel_to_point` for a touch that's `offset` pixels away from where it was meant to be
+    fn pixel_to_point_with_offset(
+        &self,
+        pos: Point2<u16>,
+        offset: cgmath::Vector2<i32>,
+    ) -> Option<Point2<u8>> {
+        // Signed, as touches left of/above the board are further out than the spare space
+        let nearest = |pixel: u16, offset: i32, spare: u16| {
+            let lines = (pixel as i32 + offset - spare as i32) as f32 / self.square_size as f32;
+            let line = lines.round();
+            (line >= 0.0 && line < self.board_size as f32).then_some(line as u8)
+        };
+        let screen = Point2 {
+            x: nearest(pos.x, offset.x, self.spare_width)?,
+            y: nearest(pos.y, offset.y, self.spare_height)?,
+        };
+        Some(unorient(self.orientation, screen, self.board_size - 1))
+    }
+
     pub fn draw_piece(&self, fb: &mut Framebuffer, x: u8, y: u8, white: bool) -> mxcfb_rect {
         // info!("draw_piece: {x} {y} {white}");
-        let point = Point2 {
-            x: (self.spare_width + (self.square_size * x as u16)) as i32,
-            y: (self.spare_height + (self.square_size * y as u16)) as i32,
-        };
-        let rect = fb.fill_circle(point, self.circle_radius as u32, color::BLACK);
+        let point = self.point_to_pixel(Point2 { x, y });
+        let rect = fb.fill_circle(point, self.circle_radius as u32, self.stone_colour);
         if white {
             fb.fill_circle(
                 point,
-                (self.circle_radius - CIRCLE_BORDER) as u32,
-                color::WHITE,
+                self.circle_radius.saturating_sub(self.stone_border) as u32,
+                match self.stone_style {
+                    StoneStyle::Ring => color::WHITE,
+                    StoneStyle::Grey => color::GRAY(0x80),
+                },
             );
         }
         rect
     }
 
+    /// Small dot in the opposite colour on top of an existing stone
+    pub fn draw_last_move_marker(&self, fb: &mut Framebuffer, x: u8, y: u8, white: bool) {
+        let point = self.point_to_pixel(Point2 { x, y });
+        fb.fill_circle(
+            point,
+            (self.circle_radius / 3) as u32,
+            if white { color::BLACK } else { color::WHITE },
+        );
+    }
+
+    /// Cross in the opposite colour over a stone that the engine thinks is dead
+    pub fn draw_dead_marker(&self, fb: &mut Framebuffer, x: u8, y: u8, white: bool) {
+        let centre = self.point_to_pixel(Point2 { x, y });
+        let arm = (self.circle_radius / 2) as i32;
+        let colour = if white { color::BLACK } else { color::WHITE };
+        for (dx, dy) in [(arm, arm), (arm, -arm)] {
+            fb.draw_line(
+                Point2 {
+                    x: centre.x - dx,
+                    y: centre.y - dy,
+                },
+                Point2 {
+                    x: centre.x + dx,
+                    y: centre.y + dy,
+                },
+                self.stone_border as u32,
+                colour,
+            );
+        }
+    }
+
+    /// Number in the middle of a stone, in the opposite colour
+    pub fn draw_move_number(&self, fb: &mut Framebuffer, x: u8, y: u8, number: usize, white: bool) {
+        let centre = self.point_to_pixel(Point2 { x, y });
+        let text = number.to_string();
+        // Smaller once there are three digits, so they stay inside the stone
+        let size = self.circle_radius as f32 * if text.len() > 2 { 0.8 } else { 1.0 };
+        let colour = if white { color::BLACK } else { color::WHITE };
+        let area = fb.draw_text(Point2 { x: 0.0, y: 0.0 }, &text, size, colour, true);
+        fb.draw_text(
+            Point2 {
+                x: centre.x as f32 - area.width as f32 / 2.0,
+                y: centre.y as f32 + area.height as f32 / 2.0,
+            },
+            &text,
+            size,
+            colour,
+            false,
+        );
+    }
+
+    /// Ring just outside a stone, to draw the eye to it
+    pub fn draw_highlight(&self, fb: &mut Framebuffer, x: u8, y: u8) -> mxcfb_rect {
+        let centre = self.point_to_pixel(Point2 { x, y });
+        let inner = self.circle_radius + self.stone_border;
+        // Outermost last, so its area covers the lot
+        (inner..=inner + self.stone_border)
+            .map(|radius| fb.draw_circle(centre, radius as u32, self.stone_colour))
+            .last()
+            .expect("always at least one circle")
+    }
+
+    /// Hollow square, so it can't be mistaken for a stone, on a suggested point
+    pub fn draw_hint_marker(&self, fb: &mut Framebuffer, x: u8, y: u8) -> mxcfb_rect {
+        let side = self.circle_radius as u32;
+        let centre = self.point_to_pixel(Point2 { x, y });
+        let top_left = Point2 {
+            x: centre.x - (side / 2) as i32,
+            y: centre.y - (side / 2) as i32,
+        };
+        fb.draw_rect(
+            top_left,
+            cgmath::Vector2 { x: side, y: side },
+            self.stone_border as u32,
+            color::BLACK,
+        );
+        mxcfb_rect {
+            top: top_left.y as u32,
+            left: top_left.x as u32,
+            width: side,
+            height: side,
+        }
+    }
+
+    /// Marks from `influence_regions` on the empty points: grey squares for black, outlines for
+    /// white, bigger the surer it is. Light enough to leave the stones and grid readable
+    pub fn draw_influence(&self, fb: &mut Framebuffer, regions: &[i8]) {
+        for (index, region) in regions.iter().enumerate() {
+            let strength = region.unsigned_abs() as u32;
+            // 4 is a stone, which is already drawn
+            if strength == 0 || strength >= 4 {
+                continue;
+            }
+            let centre = self.point_to_pixel(Point2 {
+                x: (index % self.board_size as usize) as u8,
+                y: (index / self.board_size as usize) as u8,
+            });
+            let side = self.square_size as u32 * strength / 8;
+            let top_left = Point2 {
+                x: centre.x - (side / 2) as i32,
+                y: centre.y - (side / 2) as i32,
+            };
+            let size = cgmath::Vector2 { x: side, y: side };
+            if *region < 0 {
+                fb.fill_rect(top_left, size, color::GRAY(INFLUENCE_GREY));
+            } else {
+                fb.draw_rect(
+                    top_left,
+                    size,
+                    INFLUENCE_OUTLINE,
+                    color::GRAY(INFLUENCE_GREY),
+                );
+            }
+        }
+    }
+
+    /// Small light dot on each GTP-style 1-based point in `legal`, for `show_legal_moves`
+    pub fn draw_legal_markers(&self, fb: &mut Framebuffer, legal: &HashSet<Point2<u8>>) {
+        for point in legal {
+            let centre = self.point_to_pixel(Point2 {
+                x: point.x - 1,
+                y: point.y - 1,
+            });
+            fb.fill_circle(
+                centre,
+                (self.circle_radius / 4) as u32,
+                color::GRAY(LEGAL_GREY),
+            );
+        }
+    }
+
     pub fn refresh_and_draw_one_piece(&self, fb: &mut Framebuffer, x: u8, y: u8, white: bool) {
         let rect = self.draw_piece(fb, x, y, white);
-        refresh_with_options(fb, &rect, waveform_mode::WAVEFORM_MODE_AUTO);
+        refresh_fast(fb, &rect);
+    }
+
+    /// Draws 0-based `pieces` without refreshing, and returns the area covering them all so it
+    /// can be one refresh rather than one per stone
+    pub fn draw_pieces(
+        &self,
+        fb: &mut Framebuffer,
+        pieces: &[(Point2<u8>, bool)],
+    ) -> Option<mxcfb_rect> {
+        let rects: Vec<mxcfb_rect> = pieces
+            .iter()
+            .map(|(point, white)| self.draw_piece(fb, point.x, point.y, *white))
+            .collect();
+        bounding_rect(&rects)
+    }
+
+    /// Blacks out the squares round 0-based `stones` for a moment, so ones that are about to go
+    /// (e.g. captures) get noticed. Needs the board redrawing afterwards
+    pub fn flash_stones(&self, fb: &mut Framebuffer, stones: &[(Point2<u8>, bool)]) {
+        let radius = self.circle_radius as i32;
+        let rects: Vec<mxcfb_rect> = stones
+            .iter()
+            .map(|(point, _)| {
+                let centre = self.point_to_pixel(*point);
+                let top_left = Point2 {
+                    x: centre.x - radius,
+                    y: centre.y - radius,
+                };
+                let size = cgmath::Vector2 {
+                    x: 2 * radius as u32,
+                    y: 2 * radius as u32,
+                };
+                fb.fill_rect(top_left, size, color::BLACK);
+                mxcfb_rect {
+                    top: top_left.y as u32,
+                    left: top_left.x as u32,
+                    width: size.x,
+                    height: size.y,
+                }
+            })
+            .collect();
+        let Some(region) = bounding_rect(&rects) else {
+            return;
+        };
+        let marker = submit_refresh(fb, &region, waveform_mode::WAVEFORM_MODE_DU);
+        wait_refresh(fb, marker);
+        sleep(CAPTURE_FLASH);
     }
 
     fn draw_grid(&self, fb: &mut Framebuffer) {
@@ -67,17 +522,15 @@ impl Board {
 
         for y in 0..self.square_count {
             for x in 0..self.square_count {
+                // The grid looks the same whichever way it's turned
                 fb.draw_rect(
-                    cgmath::Point2 {
-                        x: (self.square_size * (x as u16) + self.spare_width) as i32,
-                        y: (self.square_size * (y as u16) + self.spare_height) as i32,
-                    },
+                    self.screen_pixel(Point2 { x, y }),
                     cgmath::Vector2 {
                         x: self.square_size as u32,
                         y: self.square_size as u32,
                     },
-                    BORDER_WIDTH,
-                    color::BLACK,
+                    self.grid_width,
+                    self.grid_colour,
                 );
             }
         }
@@ -89,24 +542,6 @@ impl Board {
         }
     }
 
-    pub fn nearest_spot(&self, x: u16, y: u16) -> Point2<u8> {
-        let raw_point = Point2::<f32> {
-            x: (((x - self.spare_width) as f32) / (self.square_size as f32)).round(),
-            y: (((y - self.spare_height) as f32) / (self.square_size as f32)).round(),
-        };
-        if raw_point.x < 0.0 || raw_point.y < 0.0 {
-            return Point2 {
-                x: self.board_size,
-                y: self.board_size,
-            };
-        } else {
-            Point2 {
-                x: raw_point.x as u8,
-                y: raw_point.y as u8,
-            }
-        }
-    }
-
     pub fn draw_board(
         &self,
         fb: &mut Framebuffer,
@@ -118,3 +553,463 @@ impl Board {
         self.draw_stones(fb, black_stones, false);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use libremarkable::{
+        cgmath::{Point2, Vector2},
+        framebuffer::common::mxcfb_rect,
+    };
+
+    use crate::{
+        board::{
+            added_stones, bounding_rect, fitted_square_size, gtp_vertex, point_to_gtp,
+            removed_stones, Board, BoardStyle, AVAILABLE_HEIGHT, AVAILABLE_WIDTH,
+        },
+        drawing::{SCREEN_HEIGHT, SCREEN_WIDTH},
+        settings::{Orientation, StoneStyle},
+    };
+
+    const ORIENTATIONS: [Orientation; 6] = [
+        Orientation::Normal,
+        Orientation::FlipHorizontal,
+        Orientation::FlipVertical,
+        Orientation::Rotate90,
+        Orientation::Rotate180,
+        Orientation::Rotate270,
+    ];
+
+    fn oriented(size: u8, orientation: Orientation) -> Board {
+        Board::with_style(
+            size,
+            BoardStyle {
+                orientation,
+                ..BoardStyle::default()
+            },
+        )
+    }
+
+    #[test]
+    fn gtp_corners() {
+        assert_eq!(
+            Some("A9".to_string()),
+            point_to_gtp(Point2 { x: 0, y: 0 }, 9)
+        );
+        assert_eq!(
+            Some("J1".to_string()),
+            point_to_gtp(Point2 { x: 8, y: 8 }, 9)
+        );
+        assert_eq!(
+            Some("T1".to_string()),
+            point_to_gtp(Point2 { x: 18, y: 18 }, 19)
+        );
+    }
+
+    #[test]
+    fn gtp_skips_i() {
+        assert_eq!(
+            Some("H5".to_string()),
+            point_to_gtp(Point2 { x: 7, y: 4 }, 9)
+        );
+        assert_eq!(
+            Some("J5".to_string()),
+            point_to_gtp(Point2 { x: 8, y: 4 }, 9)
+        );
+    }
+
+    #[test]
+    fn gtp_top_row() {
+        assert_eq!(
+            Some("D13".to_string()),
+            point_to_gtp(Point2 { x: 3, y: 0 }, 13)
+        );
+        assert_eq!(
+            Some("K19".to_string()),
+            point_to_gtp(Point2 { x: 9, y: 0 }, 19)
+        );
+    }
+
+    #[test]
+    fn gtp_vertices() {
+        assert_eq!(Some("A1".to_string()), gtp_vertex(Point2 { x: 1, y: 1 }));
+        assert_eq!(Some("H9".to_string()), gtp_vertex(Point2 { x: 8, y: 9 }));
+        assert_eq!(Some("J2".to_string()), gtp_vertex(Point2 { x: 9, y: 2 }));
+        assert_eq!(Some("T19".to_string()), gtp_vertex(Point2 { x: 19, y: 19 }));
+        assert_eq!(None, gtp_vertex(Point2 { x: 0, y: 1 }));
+        assert_eq!(None, gtp_vertex(Point2 { x: 1, y: 0 }));
+    }
+
+    #[test]
+    fn gtp_off_board() {
+        assert_eq!(None, point_to_gtp(Point2 { x: 9, y: 0 }, 9));
+        assert_eq!(None, point_to_gtp(Point2 { x: 0, y: 9 }, 9));
+        assert_eq!(None, point_to_gtp(Point2 { x: 25, y: 0 }, 30));
+    }
+
+    #[test]
+    fn too_small_board() {
+        for size in [0, 1] {
+            let board = Board::new(size);
+            assert_eq!(2, board.board_size);
+            assert_eq!(1, board.square_count);
+        }
+    }
+
+    #[test]
+    fn board_centred() {
+        for size in [5, 7, 9, 13, 19] {
+            let board = Board::new(size);
+            let right = SCREEN_WIDTH - board.spare_width - board.grid_size();
+            let bottom = SCREEN_HEIGHT - board.spare_height - board.grid_size();
+            assert!(board.spare_width.abs_diff(right) <= 1, "{size}");
+            assert!(board.spare_height.abs_diff(bottom) <= 1, "{size}");
+        }
+    }
+
+    #[test]
+    fn square_size_fits_tighter_side() {
+        let tall = Vector2 { x: 1200, y: 2000 };
+        let wide = Vector2 { x: 2000, y: 1200 };
+        for size in [5, 9, 19] {
+            let square_count = size - 1;
+            assert_eq!(
+                fitted_square_size(square_count, tall),
+                fitted_square_size(square_count, wide),
+                "{size}"
+            );
+            assert_eq!(
+                1200 / square_count as u16,
+                fitted_square_size(square_count, tall),
+                "{size}"
+            );
+        }
+    }
+
+    #[test]
+    fn board_fills_available_space() {
+        let tighter = AVAILABLE_WIDTH.min(AVAILABLE_HEIGHT);
+        for size in [5, 7, 9, 13, 19] {
+            let board = Board::new(size);
+            assert!(board.grid_size() <= tighter, "{size}");
+            // Only lost to rounding down the square size
+            assert!(
+                tighter - board.grid_size() < board.square_count as u16,
+                "{size}"
+            );
+        }
+    }
+
+    #[test]
+    fn board_stones_on_screen() {
+        for size in [5, 7, 9, 13, 19] {
+            let board = Board::new(size);
+            assert!(board.spare_width >= board.circle_radius, "{size}");
+            assert!(board.spare_height >= board.circle_radius, "{size}");
+            assert!(
+                board.spare_width + board.grid_size() + board.circle_radius < SCREEN_WIDTH,
+                "{size}"
+            );
+            assert!(
+                board.spare_height + board.grid_size() + board.circle_radius < SCREEN_HEIGHT,
+                "{size}"
+            );
+        }
+    }
+
+    #[test]
+    fn board_below_status_area() {
+        // Status text and the buttons under it (e.g. Undo at y=120) finish by here
+        let status_bottom = 215;
+        for size in [5, 7, 9, 13, 19] {
+            let board = Board::new(size);
+            assert!(
+                board.spare_height - board.circle_radius > status_bottom,
+                "{size}"
+            );
+        }
+    }
+
+    #[test]
+    fn point_to_pixel_corners() {
+        for size in [5, 7, 9, 13, 19] {
+            let board = Board::new(size);
+            let top_left = board.point_to_pixel(Point2 { x: 0, y: 0 });
+            assert_eq!(
+                Point2 {
+                    x: board.spare_width as i32,
+                    y: board.spare_height as i32
+                },
+                top_left,
+                "{size}"
+            );
+            let bottom_right = board.point_to_pixel(Point2 {
+                x: size - 1,
+                y: size - 1,
+            });
+            assert_eq!(
+                Point2 {
+                    x: (board.spare_width + board.grid_size()) as i32,
+                    y: (board.spare_height + board.grid_size()) as i32
+                },
+                bottom_right,
+                "{size}"
+            );
+        }
+    }
+
+    #[test]
+    fn pixel_to_point_round_trip() {
+        let none = Vector2 { x: 0, y: 0 };
+        for size in [5, 7, 9, 13, 19] {
+            let board = Board::new(size);
+            for x in 0..size {
+                for y in 0..size {
+                    let pixel = board.point_to_pixel(Point2 { x, y });
+                    // A bit off, like a real touch
+                    let pos = Point2 {
+                        x: pixel.x as u16 + 10,
+                        y: pixel.y as u16 - 10,
+                    };
+                    assert_eq!(
+                        Some(Point2 { x, y }),
+                        board.pixel_to_point_with_offset(pos, none),
+                        "{size}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn orientations_round_trip() {
+        let none = Vector2 { x: 0, y: 0 };
+        for orientation in ORIENTATIONS {
+            for size in [5, 9, 19] {
+                let board = oriented(size, orientation);
+                for x in 0..size {
+                    for y in 0..size {
+                        let pixel = board.point_to_pixel(Point2 { x, y });
+                        let pos = Point2 {
+                            x: pixel.x as u16 - 10,
+                            y: pixel.y as u16 + 10,
+                        };
+                        assert_eq!(
+                            Some(Point2 { x, y }),
+                            board.pixel_to_point_with_offset(pos, none),
+                            "{orientation:?} {size}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn orientation_corners() {
+        let last = 8;
+        let a1 = Point2 { x: 0, y: last };
+        let j9 = Point2 { x: last, y: 0 };
+        for (orientation, a1_at, j9_at) in [
+            (Orientation::Normal, a1, j9),
+            (
+                Orientation::FlipHorizontal,
+                Point2 { x: last, y: last },
+                Point2 { x: 0, y: 0 },
+            ),
+            (
+                Orientation::FlipVertical,
+                Point2 { x: 0, y: 0 },
+                Point2 { x: last, y: last },
+            ),
+            (
+                Orientation::Rotate90,
+                Point2 { x: 0, y: 0 },
+                Point2 { x: last, y: last },
+            ),
+            (Orientation::Rotate180, j9, a1),
+            (
+                Orientation::Rotate270,
+                Point2 { x: last, y: last },
+                Point2 { x: 0, y: 0 },
+            ),
+        ] {
+            let board = oriented(9, orientation);
+            assert_eq!(
+                board.screen_pixel(a1_at),
+                board.point_to_pixel(a1),
+                "{orientation:?}"
+            );
+            assert_eq!(
+                board.screen_pixel(j9_at),
+                board.point_to_pixel(j9),
+                "{orientation:?}"
+            );
+        }
+        // A quarter turn moves the rest of the first column along the top row
+        let board = oriented(9, Orientation::Rotate90);
+        assert_eq!(
+            board.screen_pixel(Point2 { x: 1, y: 0 }),
+            board.point_to_pixel(Point2 { x: 0, y: last - 1 })
+        );
+    }
+
+    #[test]
+    fn pixel_to_point_edges() {
+        let none = Vector2 { x: 0, y: 0 };
+        for size in [5, 9, 19] {
+            let board = Board::new(size);
+            let half = board.square_size / 2;
+            let last = size - 1;
+            let corner = board.point_to_pixel(Point2 { x: last, y: last });
+            let corner = Point2 {
+                x: corner.x as u16,
+                y: corner.y as u16,
+            };
+            // Just under half a square past the edge still counts
+            let near = Point2 {
+                x: corner.x + half - 1,
+                y: corner.y + half - 1,
+            };
+            assert_eq!(
+                Some(Point2 { x: last, y: last }),
+                board.pixel_to_point_with_offset(near, none),
+                "{size}"
+            );
+            let past = Point2 {
+                x: corner.x + half + 1,
+                y: corner.y,
+            };
+            assert_eq!(None, board.pixel_to_point_with_offset(past, none), "{size}");
+            // The spare width can be less than half a square, so above rather than left
+            let above = Point2 {
+                x: corner.x,
+                y: board.spare_height - half - 1,
+            };
+            assert_eq!(
+                None,
+                board.pixel_to_point_with_offset(above, none),
+                "{size}"
+            );
+        }
+    }
+
+    #[test]
+    fn pixel_to_point_offset() {
+        let board = Board::new(19);
+        // Lands on the next point along, unless the offset takes it back
+        let pos = Point2 {
+            x: board.spare_width + board.square_size * 3 + 40,
+            y: board.spare_height + board.square_size * 3,
+        };
+        let none = Vector2 { x: 0, y: 0 };
+        assert_eq!(
+            Some(Point2 { x: 4, y: 3 }),
+            board.pixel_to_point_with_offset(pos, none)
+        );
+        let back = Vector2 { x: -40, y: 0 };
+        assert_eq!(
+            Some(Point2 { x: 3, y: 3 }),
+            board.pixel_to_point_with_offset(pos, back)
+        );
+        // Pushed off the top of the board
+        let up = Vector2 { x: 0, y: -1000 };
+        assert_eq!(None, board.pixel_to_point_with_offset(pos, up));
+    }
+
+    #[test]
+    fn pixel_to_point_off_board() {
+        for size in [5, 7, 9, 13, 19] {
+            let board = Board::new(size);
+            assert_eq!(None, board.pixel_to_point(Point2 { x: 0, y: 0 }), "{size}");
+            let bottom_right = Point2 {
+                x: SCREEN_WIDTH - 1,
+                y: SCREEN_HEIGHT - 1,
+            };
+            assert_eq!(None, board.pixel_to_point(bottom_right), "{size}");
+        }
+    }
+
+    #[test]
+    fn stone_border_clamped() {
+        let style = BoardStyle {
+            stone_style: StoneStyle::Ring,
+            stone_border: 500,
+            ..BoardStyle::default()
+        };
+        let board = Board::with_style(19, style);
+        assert!(board.stone_border < board.circle_radius);
+        let style = BoardStyle {
+            stone_style: StoneStyle::Grey,
+            stone_border: 0,
+            ..BoardStyle::default()
+        };
+        let board = Board::with_style(9, style);
+        assert_eq!(1, board.stone_border);
+    }
+
+    #[test]
+    fn stone_border_huge_board() {
+        let board = Board::with_style(u8::MAX, BoardStyle::default());
+        assert_eq!(1, board.circle_radius);
+        assert_eq!(1, board.stone_border);
+    }
+
+    #[test]
+    fn added_stones_only() {
+        let before = (vec![Point2 { x: 1, y: 1 }], vec![Point2 { x: 2, y: 2 }]);
+        let after = (
+            vec![Point2 { x: 1, y: 1 }, Point2 { x: 3, y: 1 }],
+            vec![Point2 { x: 2, y: 2 }, Point2 { x: 5, y: 4 }],
+        );
+        assert_eq!(
+            Some(vec![
+                (Point2 { x: 2, y: 0 }, true),
+                (Point2 { x: 4, y: 3 }, false)
+            ]),
+            added_stones(&before, &after)
+        );
+        assert_eq!(Some(vec![]), added_stones(&after, &after));
+    }
+
+    #[test]
+    fn added_stones_with_capture() {
+        let before = (vec![Point2 { x: 1, y: 1 }], vec![]);
+        let after = (vec![], vec![Point2 { x: 1, y: 2 }]);
+        assert_eq!(None, added_stones(&before, &after));
+    }
+
+    #[test]
+    fn removed_stones_after_capture() {
+        let before = (
+            vec![Point2 { x: 1, y: 1 }, Point2 { x: 3, y: 3 }],
+            vec![Point2 { x: 2, y: 1 }],
+        );
+        let after = (
+            vec![Point2 { x: 3, y: 3 }],
+            vec![Point2 { x: 2, y: 1 }, Point2 { x: 1, y: 2 }],
+        );
+        assert_eq!(
+            vec![(Point2 { x: 0, y: 0 }, true)],
+            removed_stones(&before, &after)
+        );
+        assert_eq!(
+            Vec::<(Point2<u8>, bool)>::new(),
+            removed_stones(&after, &after)
+        );
+    }
+
+    #[test]
+    fn bounding_rects() {
+        assert_eq!(None, bounding_rect(&[]));
+        let rect = |left, top, width, height| mxcfb_rect {
+            top,
+            left,
+            width,
+            height,
+        };
+        assert_eq!(
+            Some(rect(10, 20, 290, 80)),
+            bounding_rect(&[rect(10, 50, 50, 50), rect(250, 20, 50, 50)])
+        );
+    }
+}
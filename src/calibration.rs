@@ -0,0 +1,219 @@
+use libremarkable::{
+    appctx,
+    cgmath::{Point2, Vector2},
+    framebuffer::{common::color, core::Framebuffer, FramebufferDraw},
+    input::MultitouchEvent,
+};
+use log::{info, warn};
+
+use crate::{
+    drawing::{
+        centred_x, draw_multiline_text, refresh, scaled_size, Button, SCREEN_HEIGHT, SCREEN_WIDTH,
+    },
+    engine::Engine,
+    reset::{draw_back, handle_back},
+    routine::Routine,
+    settings::{save_touch_offset, MAX_TOUCH_OFFSET},
+};
+
+/// Where the crosses go, as fractions of the screen. Spread over where the board gets drawn
+const TARGETS: [(f32, f32); 5] = [(0.2, 0.3), (0.8, 0.3), (0.5, 0.5), (0.2, 0.7), (0.8, 0.7)];
+const CROSS_SIZE: i32 = 60;
+const CROSS_WIDTH: u32 = 4;
+const AGAIN_BUTTON_SIZE: Vector2<u32> = Vector2 { x: 500, y: 95 };
+const TEXT_LEFT: f32 = 100.0;
+
+fn target(index: usize) -> Point2<i32> {
+    let (x, y) = TARGETS[index];
+    Point2 {
+        x: (SCREEN_WIDTH as f32 * x) as i32,
+        y: (SCREEN_HEIGHT as f32 * y) as i32,
+    }
+}
+
+fn again_button() -> Button<()> {
+    let size = scaled_size(AGAIN_BUTTON_SIZE);
+    Button::new(
+        "Calibrate again",
+        Point2 {
+            x: centred_x(size.x),
+            y: 600,
+        },
+        size,
+        (),
+    )
+}
+
+/// Mean of how far each tap was from its cross, rounded to the nearest pixel
+fn average_offset(misses: &[Vector2<i32>]) -> Vector2<i32> {
+    if misses.is_empty() {
+        return Vector2 { x: 0, y: 0 };
+    }
+    let count = misses.len() as f32;
+    let total = misses
+        .iter()
+        .fold(Vector2 { x: 0, y: 0 }, |total, miss| total + miss);
+    Vector2 {
+        x: (total.x as f32 / count).round() as i32,
+        y: (total.y as f32 / count).round() as i32,
+    }
+}
+
+fn draw_cross(fb: &mut Framebuffer, centre: Point2<i32>) {
+    fb.draw_line(
+        Point2 {
+            x: centre.x - CROSS_SIZE,
+            y: centre.y,
+        },
+        Point2 {
+            x: centre.x + CROSS_SIZE,
+            y: centre.y,
+        },
+        CROSS_WIDTH,
+        color::BLACK,
+    );
+    fb.draw_line(
+        Point2 {
+            x: centre.x,
+            y: centre.y - CROSS_SIZE,
+        },
+        Point2 {
+            x: centre.x,
+            y: centre.y + CROSS_SIZE,
+        },
+        CROSS_WIDTH,
+        color::BLACK,
+    );
+}
+
+/// Tap a few crosses, and the average miss becomes the offset for touches on the board
+pub struct Calibration {
+    /// How far each tap so far was from its cross, as what to add to a tap to hit it
+    misses: Vec<Vector2<i32>>,
+    message: Option<String>,
+}
+
+impl Calibration {
+    pub fn new() -> Calibration {
+        Calibration {
+            misses: vec![],
+            message: None,
+        }
+    }
+
+    fn done(&self) -> bool {
+        self.misses.len() == TARGETS.len()
+    }
+
+    fn draw(&self, fb: &mut Framebuffer) {
+        fb.clear();
+        draw_back(fb);
+        if self.done() {
+            again_button().draw(fb);
+        } else {
+            draw_multiline_text(
+                fb,
+                Point2 {
+                    x: TEXT_LEFT,
+                    y: 300.0,
+                },
+                &format!(
+                    "Tap the middle of the cross ({} of {})",
+                    self.misses.len() + 1,
+                    TARGETS.len()
+                ),
+                50.0,
+                45,
+            );
+            draw_cross(fb, target(self.misses.len()));
+        }
+        if let Some(ref message) = self.message {
+            draw_multiline_text(
+                fb,
+                Point2 {
+                    x: TEXT_LEFT,
+                    y: (SCREEN_HEIGHT - 222) as f32,
+                },
+                message,
+                50.0,
+                45,
+            );
+        }
+        refresh(fb);
+    }
+
+    fn handle_tap(&mut self, pos: Point2<u16>) {
+        if self.done() {
+            if again_button().contains(pos) {
+                self.misses.clear();
+                self.message = None;
+            }
+            return;
+        }
+        let aim = target(self.misses.len());
+        let miss = Vector2 {
+            x: aim.x - pos.x as i32,
+            y: aim.y - pos.y as i32,
+        };
+        if miss.x.abs() > MAX_TOUCH_OFFSET || miss.y.abs() > MAX_TOUCH_OFFSET {
+            info!("Tap at {pos:?} too far from {aim:?}");
+            self.message = Some("Too far from the cross, try again".to_string());
+            return;
+        }
+        self.misses.push(miss);
+        self.message = None;
+        if self.done() {
+            let offset = average_offset(&self.misses);
+            info!("Touch offset is {offset:?}");
+            self.message = Some(match save_touch_offset(offset) {
+                Ok(()) => format!("Saved: touches move {} across, {} down", offset.x, offset.y),
+                Err(message) => {
+                    warn!("Touch offset not saved");
+                    message
+                }
+            });
+        }
+    }
+}
+
+impl Routine for Calibration {
+    fn init(&mut self, fb: &mut Framebuffer, _ctrl: &mut Engine) {
+        self.draw(fb);
+    }
+
+    fn on_multitouch_event(
+        &mut self,
+        ctx: &mut appctx::ApplicationContext<'_>,
+        event: MultitouchEvent,
+        _ctrl: &mut Engine,
+    ) {
+        if let MultitouchEvent::Press { finger } = event {
+            if handle_back(ctx, finger.pos) {
+                return;
+            }
+            self.handle_tap(finger.pos);
+            self.draw(ctx.get_framebuffer_ref());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use libremarkable::cgmath::Vector2;
+    use pretty_assertions::assert_eq;
+
+    use crate::calibration::average_offset;
+
+    #[test]
+    fn average() {
+        assert_eq!(Vector2 { x: 0, y: 0 }, average_offset(&[]));
+        assert_eq!(
+            Vector2 { x: -4, y: 2 },
+            average_offset(&[
+                Vector2 { x: -5, y: 1 },
+                Vector2 { x: -3, y: 2 },
+                Vector2 { x: -4, y: 4 },
+            ])
+        );
+    }
+}
@@ -1,16 +1,18 @@
-use lazy_static::lazy_static;
 use std::sync::Mutex;
 
-use gtp::controller::Engine;
 use libremarkable::{
     appctx,
     cgmath::{Point2, Vector2},
     framebuffer::{core::Framebuffer, FramebufferDraw},
     input::MultitouchEvent,
 };
+use log::info;
 
 use crate::{
-    drawing::{draw_button, refresh},
+    drawing::{centred_x, pressed, refresh, scaled_size, Button, Held, HoldTracker},
+    engine::Engine,
+    lock,
+    reset::in_back_debounce,
     routine::Routine,
 };
 
@@ -20,109 +22,147 @@ pub enum Mode {
     AgainstMachine = 2,
     Atari = 3,
     DragonGoServer = 4,
-    Exit = 5,
+    DragonGoLogin = 5,
+    Analysis = 6,
+    Exit = 7,
+    Diagnostics = 8,
+    Review = 9,
+    Problems = 10,
+    Calibration = 11,
+    Demo = 12,
+    Log = 13,
+}
+
+impl Mode {
+    pub fn from_name(name: &str) -> Option<Mode> {
+        match name {
+            "Chooser" => Some(Mode::Chooser),
+            "AgainstMachine" => Some(Mode::AgainstMachine),
+            "Atari" => Some(Mode::Atari),
+            "DragonGoServer" => Some(Mode::DragonGoServer),
+            "DragonGoLogin" => Some(Mode::DragonGoLogin),
+            "Analysis" => Some(Mode::Analysis),
+            "Exit" => Some(Mode::Exit),
+            "Diagnostics" => Some(Mode::Diagnostics),
+            "Review" => Some(Mode::Review),
+            "Problems" => Some(Mode::Problems),
+            "Calibration" => Some(Mode::Calibration),
+            "Demo" => Some(Mode::Demo),
+            "Log" => Some(Mode::Log),
+            _ => None,
+        }
+    }
 }
 
 pub static CURRENT_MODE: Mutex<Mode> = Mutex::new(Mode::Chooser);
 
-struct Button {
-    text: String,
-    top_left: Point2<i32>,
-    size: Vector2<u32>,
-    mode: Mode,
+#[derive(Clone, Copy)]
+enum Action {
+    ChangeMode(Mode),
+    NextPage,
 }
 
 const BUTTON_WIDTH: u32 = 700;
-const TOP_LEFT_X: i32 =
-    ((libremarkable::dimensions::DISPLAYWIDTH as u32 - BUTTON_WIDTH) / 2) as i32;
-
-lazy_static! {
-    static ref BUTTONS: Vec<Button> = {
-        vec![
-            Button {
-                text: "Machine game".to_string(),
-                top_left: Point2 {
-                    x: TOP_LEFT_X,
-                    y: 100,
-                },
-                size: Vector2 {
-                    x: BUTTON_WIDTH,
-                    y: 95,
-                },
-                mode: Mode::AgainstMachine,
-            },
-            Button {
-                text: "Atari game".to_string(),
-                top_left: Point2 {
-                    x: TOP_LEFT_X,
-                    y: 300,
-                },
-                size: Vector2 {
-                    x: BUTTON_WIDTH,
-                    y: 95,
-                },
-                mode: Mode::Atari,
-            },
-            Button {
-                text: "Dragon Go Server".to_string(),
-                top_left: Point2 {
-                    x: TOP_LEFT_X,
-                    y: 500,
-                },
-                size: Vector2 {
-                    x: BUTTON_WIDTH,
-                    y: 95,
-                },
-                mode: Mode::DragonGoServer,
-            },
-            Button {
-                text: "Exit".to_string(),
-                top_left: Point2 {
-                    x: TOP_LEFT_X,
-                    y: 700,
-                },
-                size: Vector2 {
-                    x: BUTTON_WIDTH,
-                    y: 95,
-                },
-                mode: Mode::Exit,
-            },
-        ]
-    };
+const BUTTON_HEIGHT: u32 = 95;
+const BUTTON_SPACING: i32 = 200;
+const FIRST_BUTTON_Y: i32 = 100;
+// Leaves room for the "More" button below a full page
+const BUTTONS_PER_PAGE: usize = 6;
+
+const MODES: [(&str, Mode); 12] = [
+    ("Machine game", Mode::AgainstMachine),
+    ("Atari game", Mode::Atari),
+    ("Set up board", Mode::Analysis),
+    ("Dragon Go Server", Mode::DragonGoServer),
+    ("Dragon Go login", Mode::DragonGoLogin),
+    ("Saved games", Mode::Review),
+    ("Problems", Mode::Problems),
+    ("Machine v machine", Mode::Demo),
+    ("Diagnostics", Mode::Diagnostics),
+    ("Log", Mode::Log),
+    ("Calibrate touch", Mode::Calibration),
+    ("Exit", Mode::Exit),
+];
+
+fn page_count() -> usize {
+    MODES.len().div_ceil(BUTTONS_PER_PAGE)
+}
+
+fn button_at(index: usize, text: &str, action: Action) -> Button<Action> {
+    let size = scaled_size(Vector2 {
+        x: BUTTON_WIDTH,
+        y: BUTTON_HEIGHT,
+    });
+    Button::new(
+        text,
+        Point2 {
+            x: centred_x(size.x),
+            y: FIRST_BUTTON_Y + BUTTON_SPACING * index as i32,
+        },
+        size,
+        action,
+    )
+}
+
+fn page_buttons(page: usize) -> Vec<Button<Action>> {
+    let mut buttons: Vec<Button<Action>> = MODES
+        .iter()
+        .skip(page * BUTTONS_PER_PAGE)
+        .take(BUTTONS_PER_PAGE)
+        .enumerate()
+        .map(|(index, (text, mode))| {
+            let button = button_at(index, text, Action::ChangeMode(*mode));
+            if *mode == Mode::Exit {
+                button.held()
+            } else {
+                button
+            }
+        })
+        .collect();
+    if page_count() > 1 {
+        buttons.push(button_at(BUTTONS_PER_PAGE, "More", Action::NextPage));
+    }
+    buttons
 }
 
-fn draw_chooser(fb: &mut Framebuffer) {
+fn draw_chooser(fb: &mut Framebuffer, page: usize) {
     fb.clear();
-    for button in BUTTONS.iter() {
-        draw_button(fb, &button.text, button.top_left, button.size);
+    for button in page_buttons(page) {
+        button.draw(fb);
     }
     refresh(fb);
 }
 
-fn on_multitouch_event(ctx: &mut appctx::ApplicationContext<'_>, event: MultitouchEvent) {
-    match event {
-        MultitouchEvent::Press { finger } => {
-            for button in BUTTONS.iter() {
-                if (finger.pos.x as i32) >= button.top_left.x
-                    && (finger.pos.x as i32) < (button.top_left.x + button.size.x as i32)
-                    && (finger.pos.y as i32) >= button.top_left.y
-                    && (finger.pos.y as i32) < (button.top_left.y + button.size.y as i32)
-                {
-                    *CURRENT_MODE.lock().unwrap() = button.mode;
-                    ctx.stop();
-                    return;
-                }
+pub struct Chooser {
+    page: usize,
+    hold: HoldTracker,
+}
+
+impl Chooser {
+    pub fn new() -> Chooser {
+        Chooser {
+            page: 0,
+            hold: HoldTracker::default(),
+        }
+    }
+
+    fn act(&mut self, ctx: &mut appctx::ApplicationContext<'_>, action: Action) {
+        match action {
+            Action::ChangeMode(mode) => {
+                *lock(&CURRENT_MODE) = mode;
+                ctx.stop();
+            }
+            Action::NextPage => {
+                self.page = (self.page + 1) % page_count();
+                draw_chooser(ctx.get_framebuffer_ref(), self.page);
             }
         }
-        _ => {}
     }
 }
 
-pub struct Chooser {}
-
 impl Routine for Chooser {
     fn init(&mut self, fb: &mut Framebuffer, _ctrl: &mut Engine) {
-        draw_chooser(fb);
+        draw_chooser(fb, self.page);
     }
 
     fn on_multitouch_event(
@@ -131,6 +171,23 @@ impl Routine for Chooser {
         event: MultitouchEvent,
         _ctrl: &mut Engine,
     ) {
-        on_multitouch_event(ctx, event);
+        if matches!(event, MultitouchEvent::Press { .. }) && in_back_debounce() {
+            info!("Ignoring touch just after going back");
+            return;
+        }
+        let buttons = page_buttons(self.page);
+        match self
+            .hold
+            .handle(ctx.get_framebuffer_ref(), &buttons, &event)
+        {
+            Held::Fired(action) => return self.act(ctx, action),
+            Held::Holding => return,
+            Held::Ignored => {}
+        }
+        if let MultitouchEvent::Press { finger } = event {
+            if let Some(button) = pressed(&buttons, finger.pos) {
+                self.act(ctx, button.action);
+            }
+        }
     }
 }
@@ -0,0 +1,221 @@
+use std::time::{Duration, Instant};
+
+/// Canadian byo-yomi, as GTP `time_settings` has it: `main_time`, then `byo_yomi_stones` moves
+/// in every `byo_yomi_time` after that
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeControl {
+    pub main_time: Duration,
+    pub byo_yomi_time: Duration,
+    pub byo_yomi_stones: u32,
+}
+
+impl TimeControl {
+    fn has_byo_yomi(&self) -> bool {
+        !self.byo_yomi_time.is_zero() && self.byo_yomi_stones > 0
+    }
+}
+
+/// One player's clock, which only runs between `start` and `stop`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clock {
+    control: TimeControl,
+    /// Main time left, or what's left of this byo-yomi period
+    left: Duration,
+    /// Moves still to play in this period, once into byo-yomi
+    stones_left: Option<u32>,
+    running_since: Option<Instant>,
+    out_of_time: bool,
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Clock {
+        let mut clock = Clock {
+            control,
+            left: control.main_time,
+            stones_left: None,
+            running_since: None,
+            out_of_time: false,
+        };
+        if control.main_time.is_zero() && control.has_byo_yomi() {
+            clock.start_period();
+        }
+        clock
+    }
+
+    fn start_period(&mut self) {
+        self.left = self.control.byo_yomi_time;
+        self.stones_left = Some(self.control.byo_yomi_stones);
+    }
+
+    fn spend(&mut self, mut elapsed: Duration) {
+        if self.stones_left.is_none() {
+            if elapsed < self.left {
+                self.left -= elapsed;
+                return;
+            }
+            elapsed -= self.left;
+            if !self.control.has_byo_yomi() {
+                self.left = Duration::ZERO;
+                self.out_of_time = true;
+                return;
+            }
+            self.start_period();
+        }
+        if elapsed >= self.left {
+            self.left = Duration::ZERO;
+            self.out_of_time = true;
+        } else {
+            self.left -= elapsed;
+        }
+    }
+
+    pub fn start(&mut self, now: Instant) {
+        self.running_since.get_or_insert(now);
+    }
+
+    /// For when a move's been played at `now`
+    pub fn stop(&mut self, now: Instant) {
+        let Some(since) = self.running_since.take() else {
+            return;
+        };
+        self.spend(now.saturating_duration_since(since));
+        if self.out_of_time {
+            return;
+        }
+        if let Some(stones) = self.stones_left {
+            if stones <= 1 {
+                self.start_period();
+            } else {
+                self.stones_left = Some(stones - 1);
+            }
+        }
+    }
+
+    /// What the clock would say at `now`, without stopping it
+    fn at(&self, now: Instant) -> Clock {
+        let mut clock = self.clone();
+        if let Some(since) = clock.running_since.take() {
+            clock.spend(now.saturating_duration_since(since));
+        }
+        clock
+    }
+
+    pub fn is_out_of_time(&self, now: Instant) -> bool {
+        self.at(now).out_of_time
+    }
+
+    /// How long until `display` changes, or `None` if it won't as the clock isn't running
+    pub fn until_next_tick(&self, now: Instant) -> Option<Duration> {
+        self.running_since?;
+        let clock = self.at(now);
+        if clock.out_of_time {
+            return None;
+        }
+        // The display rounds up, so changes as the time left passes a whole second
+        let into_second = clock.left.as_millis() as u64 % 1000;
+        Some(Duration::from_millis(if into_second == 0 {
+            1000
+        } else {
+            into_second
+        }))
+    }
+
+    /// e.g. "4:32 left", or "0:45 for 5" in byo-yomi with 5 moves to go
+    pub fn display(&self, now: Instant) -> String {
+        let clock = self.at(now);
+        // Rounded up, so it only says 0:00 once time's actually run out
+        let seconds = (clock.left.as_millis() as u64).div_ceil(1000);
+        let time = format!("{}:{:02}", seconds / 60, seconds % 60);
+        match clock.stones_left {
+            Some(stones) if !clock.out_of_time => format!("{time} for {stones}"),
+            _ => format!("{time} left"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::time::{Duration, Instant};
+
+    use crate::clock::{Clock, TimeControl};
+
+    fn clock(main: u64, byo_yomi: u64, stones: u32) -> Clock {
+        Clock::new(TimeControl {
+            main_time: Duration::from_secs(main),
+            byo_yomi_time: Duration::from_secs(byo_yomi),
+            byo_yomi_stones: stones,
+        })
+    }
+
+    fn secs(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn main_time() {
+        let start = Instant::now();
+        let mut clock = clock(300, 0, 0);
+        assert_eq!("5:00 left", clock.display(start));
+        clock.start(start);
+        assert_eq!(
+            "4:29 left",
+            clock.display(start + Duration::from_millis(31_500))
+        );
+        clock.stop(start + secs(60));
+        // Stopped, so time doesn't pass
+        assert_eq!("4:00 left", clock.display(start + secs(600)));
+        assert!(!clock.is_out_of_time(start + secs(600)));
+    }
+
+    #[test]
+    fn runs_out() {
+        let start = Instant::now();
+        let mut clock = clock(60, 0, 0);
+        clock.start(start);
+        assert!(!clock.is_out_of_time(start + secs(59)));
+        assert!(clock.is_out_of_time(start + secs(60)));
+        assert_eq!("0:00 left", clock.display(start + secs(90)));
+    }
+
+    #[test]
+    fn byo_yomi_periods() {
+        let start = Instant::now();
+        let mut clock = clock(10, 30, 2);
+        clock.start(start);
+        // Into byo-yomi part way through the move, which counts towards the period
+        clock.stop(start + secs(20));
+        assert_eq!("0:20 for 1", clock.display(start + secs(20)));
+        clock.start(start + secs(30));
+        clock.stop(start + secs(45));
+        // Both moves made, so a fresh period
+        assert_eq!("0:30 for 2", clock.display(start + secs(45)));
+        clock.start(start + secs(50));
+        assert!(clock.is_out_of_time(start + secs(80)));
+    }
+
+    #[test]
+    fn ticks() {
+        let start = Instant::now();
+        let mut clock = clock(300, 0, 0);
+        assert_eq!(None, clock.until_next_tick(start));
+        clock.start(start);
+        assert_eq!(Some(secs(1)), clock.until_next_tick(start));
+        let later = start + Duration::from_millis(2_300);
+        assert_eq!(
+            Some(Duration::from_millis(700)),
+            clock.until_next_tick(later)
+        );
+        assert_eq!("4:58 left", clock.display(later));
+        assert_eq!(
+            "4:57 left",
+            clock.display(later + Duration::from_millis(700))
+        );
+        assert_eq!(None, clock.until_next_tick(start + secs(300)));
+    }
+
+    #[test]
+    fn only_byo_yomi() {
+        assert_eq!("0:30 for 5", clock(0, 30, 5).display(Instant::now()));
+    }
+}
@@ -0,0 +1,292 @@
+use gtp::Command;
+use libremarkable::{
+    appctx,
+    cgmath::Point2,
+    framebuffer::{core::Framebuffer, FramebufferDraw},
+    input::MultitouchEvent,
+};
+use log::{error, info, warn};
+use std::time::{Duration, Instant};
+
+use crate::{
+    board::{Board, Stone},
+    drawing::{flush_refresh, refresh, StatusBar},
+    engine::Engine,
+    gtp::{
+        area_score, parse_engine_move, poll_response, replay_moves, restart_engine, set_komi,
+        set_time_settings, EngineMove, GtpError, StoneCache,
+    },
+    reset::{draw_back, handle_back},
+    routine::Routine,
+    settings::SETTINGS,
+};
+
+// Same as the machine game, as it's the same GnuGo thinking
+const MOVE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Between moves, so there's time to see each one
+const MOVE_DELAY: Duration = Duration::from_secs(2);
+/// How long the result stays up before the next game starts
+const RESULT_DELAY: Duration = Duration::from_secs(30);
+/// Games get scored after this many moves per point, in case both sides keep playing in a ko
+const MAX_MOVES_PER_POINT: usize = 3;
+
+/// Whether the game's done: two passes in a row, or on and on for longer than any real game
+fn game_over(history: &[(Stone, Option<Point2<u8>>)], board_size: u8) -> bool {
+    let max_moves = board_size as usize * board_size as usize * MAX_MOVES_PER_POINT;
+    history.len() >= max_moves || matches!(history, [.., (_, None), (_, None)])
+}
+
+/// GnuGo plays both sides of a game, over and over, e.g. for leaving on as a screensaver
+pub struct Demo {
+    board: Board,
+    fb: Option<&'static mut Framebuffer>,
+    stone_cache: StoneCache,
+    /// 0-based from the top-left, with `None` for a pass
+    history: Vec<(Stone, Option<Point2<u8>>)>,
+    /// When the current `genmove` was sent, while GnuGo's thinking
+    thinking: Option<Instant>,
+    /// When the last move was drawn (or the game started), for `MOVE_DELAY`
+    last_move: Instant,
+    /// How the last game ended, and when, while it's being shown
+    result: Option<(String, Instant)>,
+    /// Something went wrong with the engine, so it's stopped until it's started again
+    failed: bool,
+    /// Left for another mode, so the engine's no longer ours to send moves to
+    stopped: bool,
+}
+
+impl Demo {
+    pub fn new() -> Demo {
+        Demo {
+            board: Board::new(SETTINGS.local_board_size()),
+            fb: None,
+            stone_cache: StoneCache::default(),
+            history: vec![],
+            thinking: None,
+            last_move: Instant::now(),
+            result: None,
+            failed: false,
+            stopped: false,
+        }
+    }
+
+    fn next_colour(&self) -> Stone {
+        self.history
+            .last()
+            .map_or(Stone::Black, |(stone, _)| stone.other())
+    }
+
+    fn draw_status(&self, fb: &mut Framebuffer, text: &str, refresh: bool) {
+        StatusBar::new(self.board.spare_width as i32).draw(fb, text, refresh);
+    }
+
+    fn status_text(&self) -> String {
+        match self.result {
+            Some((ref result, _)) => result.clone(),
+            None => format!("Move {} ({:?})", self.history.len() + 1, self.next_colour()),
+        }
+    }
+
+    fn redraw(&mut self, ctrl: &mut Engine, fb: &mut Framebuffer) -> Result<(), GtpError> {
+        let (white_stones, black_stones) = self.stone_cache.stones(ctrl)?;
+        self.board.draw_board(fb, &white_stones, &black_stones);
+        self.draw_status(fb, &self.status_text(), false);
+        draw_back(fb);
+        refresh(fb);
+        Ok(())
+    }
+
+    fn new_game(&mut self, ctrl: &mut Engine, fb: &mut Framebuffer) -> Result<(), GtpError> {
+        info!("Starting demo game");
+        self.history.clear();
+        self.result = None;
+        replay_moves(ctrl, self.board.board_size, &self.history)?;
+        set_komi(ctrl, SETTINGS.rules.komi())?;
+        // Otherwise an earlier timed machine game would still be limiting GnuGo
+        set_time_settings(ctrl, 0, 1, 0)?;
+        self.stone_cache.force_refresh(ctrl)?;
+        self.last_move = Instant::now();
+        self.redraw(ctrl, fb)
+    }
+
+    fn finish(&mut self, ctrl: &mut Engine, fb: &mut Framebuffer) -> Result<(), GtpError> {
+        self.draw_status(fb, "Scoring...", true);
+        let score = area_score(ctrl)?;
+        let result = match score.score {
+            Some(final_score) => final_score.to_string(),
+            None => score.raw_score.trim().to_string(),
+        };
+        info!(
+            "Demo game over after {} moves: {result}",
+            self.history.len()
+        );
+        self.result = Some((result, Instant::now()));
+        self.redraw(ctrl, fb)
+    }
+
+    fn check_move(
+        &mut self,
+        ctrl: &mut Engine,
+        fb: &mut Framebuffer,
+        started: Instant,
+    ) -> Result<Duration, GtpError> {
+        let Some(resp) = poll_response(ctrl, started, MOVE_TIMEOUT)? else {
+            return Ok(POLL_INTERVAL);
+        };
+        self.thinking = None;
+        let colour = self.next_colour();
+        info!(
+            "{colour:?}: {} after {:.2?}",
+            resp.text(),
+            started.elapsed()
+        );
+        // GTP points here are 1-based
+        let point = match parse_engine_move(&resp.text())? {
+            EngineMove::Play(p) => Some(Point2 {
+                x: p.x - 1,
+                y: p.y - 1,
+            }),
+            EngineMove::Pass => None,
+            EngineMove::Resign => {
+                let result = format!("{:?} resigned", colour);
+                info!(
+                    "Demo game over after {} moves: {result}",
+                    self.history.len()
+                );
+                self.result = Some((result, Instant::now()));
+                self.redraw(ctrl, fb)?;
+                return Ok(RESULT_DELAY);
+            }
+        };
+        self.history.push((colour, point));
+        self.stone_cache.board_changed();
+        self.last_move = Instant::now();
+        if game_over(&self.history, self.board.board_size) {
+            self.finish(ctrl, fb)?;
+            return Ok(RESULT_DELAY);
+        }
+        self.redraw(ctrl, fb)?;
+        Ok(MOVE_DELAY)
+    }
+
+    /// Moves things on a step, and says how long until the next one
+    fn step(&mut self, ctrl: &mut Engine, fb: &mut Framebuffer) -> Result<Duration, GtpError> {
+        if self.stopped {
+            return Ok(MOVE_DELAY);
+        }
+        if let Some(started) = self.thinking {
+            return self.check_move(ctrl, fb, started);
+        }
+        if let Some((_, ended)) = self.result {
+            let waited = ended.elapsed();
+            if waited < RESULT_DELAY {
+                return Ok(RESULT_DELAY - waited);
+            }
+            self.new_game(ctrl, fb)?;
+            return Ok(MOVE_DELAY);
+        }
+        let waited = self.last_move.elapsed();
+        if waited < MOVE_DELAY {
+            return Ok(MOVE_DELAY - waited);
+        }
+        let colour = self.next_colour().gtp_name();
+        ctrl.send(Command::new_with_args("genmove", |e| e.s(colour)));
+        self.thinking = Some(Instant::now());
+        self.draw_status(fb, &format!("{} thinking...", self.status_text()), true);
+        Ok(POLL_INTERVAL)
+    }
+
+    fn show_engine_error(&mut self, fb: &mut Framebuffer, err: GtpError) {
+        error!("Engine error: {err}");
+        self.failed = true;
+        self.thinking = None;
+        self.draw_status(fb, err.status_text(), true);
+    }
+}
+
+impl Routine for Demo {
+    fn init(&mut self, fb: &'static mut Framebuffer, ctrl: &mut Engine) {
+        fb.clear();
+        if let Err(err) = self.new_game(ctrl, fb) {
+            self.show_engine_error(fb, err);
+        }
+        self.fb = Some(fb);
+    }
+
+    fn update_loop(&mut self, ctrl: &mut Engine) -> Option<Duration> {
+        let Some(fb) = self.fb.take() else {
+            error!("No framebuffer!");
+            return Some(POLL_INTERVAL);
+        };
+        let next = if self.failed {
+            None
+        } else {
+            match self.step(ctrl, fb) {
+                Ok(next) => Some(next),
+                Err(err) => {
+                    self.show_engine_error(fb, err);
+                    None
+                }
+            }
+        };
+        flush_refresh(fb);
+        let _empty = self.fb.insert(fb);
+        next
+    }
+
+    fn on_leave(&mut self, ctrl: &mut Engine) {
+        self.stopped = true;
+        if self.thinking.take().is_some() {
+            // Otherwise the next mode would get the answer to our genmove
+            warn!("Restarting GnuGo to abandon its move");
+            if let Err(err) = restart_engine(ctrl) {
+                error!("Can't restart GnuGo: {err}");
+            }
+        }
+    }
+
+    fn on_multitouch_event(
+        &mut self,
+        ctx: &mut appctx::ApplicationContext<'_>,
+        event: MultitouchEvent,
+        _ctrl: &mut Engine,
+    ) {
+        if let MultitouchEvent::Press { finger } = event {
+            handle_back(ctx, finger.pos);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use libremarkable::cgmath::Point2;
+
+    use crate::{board::Stone, demo::game_over};
+
+    #[test]
+    fn two_passes_end_it() {
+        let point = Some(Point2 { x: 2, y: 2 });
+        assert!(!game_over(&[], 9));
+        assert!(!game_over(&[(Stone::Black, None)], 9));
+        assert!(!game_over(
+            &[(Stone::Black, None), (Stone::White, point)],
+            9
+        ));
+        assert!(game_over(
+            &[
+                (Stone::Black, point),
+                (Stone::White, None),
+                (Stone::Black, None)
+            ],
+            9
+        ));
+    }
+
+    #[test]
+    fn too_many_moves() {
+        let moves = vec![(Stone::Black, Some(Point2 { x: 0, y: 0 })); 5 * 5 * 3];
+        assert!(!game_over(&moves[1..], 5));
+        assert!(game_over(&moves, 5));
+    }
+}
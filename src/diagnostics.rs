@@ -0,0 +1,133 @@
+use libremarkable::{
+    appctx,
+    cgmath::{Point2, Vector2},
+    framebuffer::{core::Framebuffer, FramebufferDraw},
+    input::MultitouchEvent,
+};
+use log::{error, info};
+
+use crate::{
+    drawing::{centred_x, draw_multiline_text, refresh, scaled_size, Button},
+    engine::Engine,
+    gtp::{count_captures, do_human_move, set_board_size, verify_board, GtpError, PlayResult},
+    reset::{draw_back, handle_back},
+    routine::Routine,
+};
+
+const TEST_BOARD_SIZE: u8 = 9;
+const RUN_BUTTON_SIZE: Vector2<u32> = Vector2 { x: 700, y: 95 };
+
+/// 0-based moves for the self-test. The last one captures the white stone in the corner
+const TEST_MOVES: [(&str, Point2<u8>); 5] = [
+    ("black", Point2 { x: 1, y: 0 }),
+    ("white", Point2 { x: 0, y: 0 }),
+    ("black", Point2 { x: 4, y: 4 }),
+    ("white", Point2 { x: 6, y: 6 }),
+    ("black", Point2 { x: 0, y: 1 }),
+];
+
+fn run_button() -> Button<()> {
+    let size = scaled_size(RUN_BUTTON_SIZE);
+    Button::new(
+        "Run again",
+        Point2 {
+            x: centred_x(size.x),
+            y: 300,
+        },
+        size,
+        (),
+    )
+}
+
+/// Plays a few moves including a capture, then checks GnuGo's own drawing of the board matches
+/// what `list_stones` tells us, as that's what all the game modes draw from
+fn self_test(ctrl: &mut Engine) -> Result<Vec<String>, GtpError> {
+    set_board_size(ctrl, TEST_BOARD_SIZE)?;
+    let mut results = vec![];
+    for (colour, point) in TEST_MOVES {
+        match do_human_move(ctrl, point, colour)? {
+            PlayResult::Played => {}
+            PlayResult::Illegal => {
+                results.push(format!("Engine says {colour} move {point:?} is illegal"))
+            }
+            PlayResult::Refused(text) => {
+                results.push(format!("Engine refused {colour} move {point:?}: {text}"))
+            }
+        }
+    }
+    let captures = count_captures(ctrl, "black")?;
+    if captures != 1 {
+        results.push(format!("Black should have taken 1 stone, not {captures}"));
+    }
+    results.append(&mut verify_board(ctrl)?);
+    if results.is_empty() {
+        results.push("All OK: showboard matches list_stones".to_string());
+    }
+    Ok(results)
+}
+
+pub struct Diagnostics {
+    results: Vec<String>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Diagnostics {
+        Diagnostics { results: vec![] }
+    }
+
+    fn run(&mut self, ctrl: &mut Engine) {
+        self.results = match self_test(ctrl) {
+            Ok(results) => results,
+            Err(err) => {
+                error!("Self-test failed: {err}");
+                vec![format!("{}: {err}", err.status_text())]
+            }
+        };
+        for result in &self.results {
+            info!("Self-test: {result}");
+        }
+    }
+
+    fn draw(&self, fb: &mut Framebuffer) {
+        fb.clear();
+        draw_back(fb);
+        run_button().draw(fb);
+        for (index, result) in self.results.iter().enumerate() {
+            draw_multiline_text(
+                fb,
+                Point2 {
+                    x: 100.0,
+                    y: 600.0 + 120.0 * index as f32,
+                },
+                result,
+                50.0,
+                45,
+            );
+        }
+        refresh(fb);
+    }
+}
+
+impl Routine for Diagnostics {
+    fn init(&mut self, fb: &mut Framebuffer, ctrl: &mut Engine) {
+        self.run(ctrl);
+        self.draw(fb);
+    }
+
+    fn on_multitouch_event(
+        &mut self,
+        ctx: &mut appctx::ApplicationContext<'_>,
+        event: MultitouchEvent,
+        ctrl: &mut Engine,
+    ) {
+        if let MultitouchEvent::Press { finger } = event {
+            if handle_back(ctx, finger.pos) {
+                return;
+            }
+            if run_button().contains(finger.pos) {
+                self.run(ctrl);
+                self.draw(ctx.get_framebuffer_ref());
+            }
+        }
+    }
+}
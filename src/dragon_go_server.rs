@@ -1,38 +1,50 @@
 use crate::{
-    board::{Board, AVAILABLE_WIDTH},
-    chooser::CURRENT_MODE,
-    drawing::{draw_button, draw_multiline_text, refresh, refresh_with_options},
-    game_parse::get_game_data,
-    reset::{draw_reset, reset_button_top_left, RESET_BUTTON_SIZE},
+    board::{point_to_gtp, Board, Stone},
+    drawing::{
+        centred_x, draw_multiline_text, flush_refresh, pressed, refresh, refresh_fast, scaled,
+        scaled_size, text_size, wrap_text, Button, GameInfo, Held, HoldTracker, StatusBar,
+        SCREEN_HEIGHT,
+    },
+    engine::Engine,
+    game_parse::{get_game_data, is_suicide},
+    gtp::{estimate_score, load_sgf, parse_estimate, try_sgf_move, PlayResult},
+    move_choice::{ChoicePress, MoveChoice, COMMIT_BUTTON_SIZE},
+    reset::{back_button_top_left, draw_back, handle_back},
     routine::Routine,
+    settings::SETTINGS,
 };
 use chrono::{DateTime, TimeDelta, Utc};
 use core::fmt;
-use gtp::controller::Engine;
 use lazy_static::lazy_static;
 use libremarkable::{
     appctx,
     cgmath::{Point2, Vector2},
-    framebuffer::{
-        common::{color, mxcfb_rect, waveform_mode},
-        core::Framebuffer,
-        FramebufferDraw,
-    },
-    input::MultitouchEvent,
+    framebuffer::{common::color, core::Framebuffer, FramebufferDraw},
+    input::{MultitouchEvent, PhysicalButton},
 };
 use log::{error, info, warn};
 use serde::{de, Deserialize, Serialize};
 use std::{
+    collections::HashMap,
     fs,
+    path::Path,
+    slice,
+    thread::sleep,
     time::{Duration, Instant},
 };
 
-const LOGIN_FILE: &str = "/opt/dragon-go-server-login";
+pub const LOGIN_FILE: &str = "/opt/dragon-go-server-login";
+// Extra accounts, one JSON login file each
+const LOGIN_DIR: &str = "/opt/dragon-go-server-logins";
+const LAST_ACCOUNT_FILE: &str = "/opt/dragon-go-server-last-account";
+const UPDATE_INTERVAL: Duration = Duration::from_secs(60 * 10);
+// How long `reveal_last_move` shows the board before the opponent's move
+const REVEAL_PAUSE: Duration = Duration::from_millis(700);
 
 #[derive(Serialize, Deserialize, Default, PartialEq)]
-struct LoginInfo {
-    username: String,
-    password: String,
+pub struct LoginInfo {
+    pub username: String,
+    pub password: String,
 }
 
 fn dragon_date<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
@@ -56,9 +68,7 @@ where
 
             match chrono::DateTime::parse_from_rfc3339(&original_date_str) {
                 Ok(date) => Ok(date.to_utc()),
-                Err(_) => {
-                    panic!("bad date: '{}'", original_date_str)
-                }
+                Err(err) => Err(E::custom(format!("bad date '{original_date_str}': {err}"))),
             }
         }
     }
@@ -83,32 +93,29 @@ where
         where
             E: de::Error,
         {
-            match v.replace("'", "").chars().next().unwrap() {
-                'F' => {
-                    // Fischer time
-                    let remaining = &v[3..v.find("(").unwrap()];
-                    let mut delta = TimeDelta::zero();
-                    for piece in remaining.split_whitespace() {
-                        let value = match i64::from_str_radix(&piece[..piece.len() - 1], 10) {
-                            Ok(v) => v,
-                            Err(_) => {
-                                panic!("Error parsing '{}' in '{}'", piece, remaining);
-                            }
-                        };
-                        delta += match &piece.chars().last().unwrap() {
-                            'd' => TimeDelta::days(value),
-                            'h' => TimeDelta::hours(value),
-                            default => {
-                                panic!("Something else! '{}'", default);
-                            }
-                        };
-                    }
-                    Ok(delta)
-                }
-                default => {
-                    panic!("Something else! '{}'", default);
-                }
+            let v = v.replace("'", "");
+            // Fischer time, e.g. "F: 4d 3h (+ 1d)"
+            let Some(fischer) = v.strip_prefix("F:") else {
+                return Err(E::custom(format!("unknown time system in '{v}'")));
+            };
+            let Some(end) = fischer.find('(') else {
+                return Err(E::custom(format!("no increment in '{v}'")));
+            };
+            let mut delta = TimeDelta::zero();
+            for piece in fischer[..end].split_whitespace() {
+                // split_whitespace never gives an empty piece
+                let unit = piece.chars().last().unwrap_or_default();
+                let number = &piece[..piece.len() - unit.len_utf8()];
+                let Ok(value) = number.parse::<i64>() else {
+                    return Err(E::custom(format!("bad time '{piece}' in '{v}'")));
+                };
+                delta += match unit {
+                    'd' => TimeDelta::days(value),
+                    'h' => TimeDelta::hours(value),
+                    _ => return Err(E::custom(format!("unknown time unit '{unit}' in '{v}'"))),
+                };
             }
+            Ok(delta)
         }
     }
 
@@ -123,6 +130,197 @@ enum PlayerColor {
     White,
 }
 
+impl PlayerColor {
+    fn stone(&self) -> Stone {
+        match self {
+            PlayerColor::Black => Stone::Black,
+            PlayerColor::White => Stone::White,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum GamePhase {
+    Play,
+    /// Both players have passed, and need to agree on the dead stones
+    Scoring,
+    /// Resigned, timed out or scored, so nothing left to do
+    Finished,
+}
+
+fn game_phase(game_status: &str) -> GamePhase {
+    match game_status.trim_matches('\'') {
+        "SCORE" | "SCORE2" => GamePhase::Scoring,
+        "FINISHED" => GamePhase::Finished,
+        _ => GamePhase::Play,
+    }
+}
+
+/// Readable version of an SGF result, e.g. "B+R" is "Black won by resignation"
+fn describe_result(result: &str) -> String {
+    let result = result.trim();
+    if result == "0" || result.eq_ignore_ascii_case("draw") || result == "Jigo" {
+        return "Draw".to_string();
+    }
+    if result.eq_ignore_ascii_case("void") {
+        return "No result".to_string();
+    }
+    let Some((winner, how)) = result.split_once('+') else {
+        return format!("Result: {result}");
+    };
+    let winner = match winner {
+        "B" => "Black",
+        "W" => "White",
+        _ => return format!("Result: {result}"),
+    };
+    match how {
+        "" => format!("{winner} won"),
+        "R" | "Resign" => format!("{winner} won by resignation"),
+        "T" | "Time" => format!("{winner} won on time"),
+        "F" | "Forfeit" => format!("{winner} won by forfeit"),
+        margin => format!("{winner} won by {margin}"),
+    }
+}
+
+/// At most `max_lines` of a comment, with "..." on the end if that's not all of it
+fn comment_lines(comment: &str, width: usize, max_lines: usize) -> Vec<String> {
+    let mut lines = wrap_text(comment, width);
+    if lines.len() > max_lines {
+        lines.truncate(max_lines);
+        if let Some(last) = lines.last_mut() {
+            let keep = last.chars().count().min(width.saturating_sub(3));
+            *last = last.chars().take(keep).collect::<String>() + "...";
+        }
+    }
+    lines
+}
+
+/// quick_status.php gives a `game_action` of 0 for games where it's the opponent's turn, which
+/// we can show but not play in
+fn can_move(game_action: u8) -> bool {
+    game_action != 0
+}
+
+/// Waiting for a move from us, rather than finished or needing the score agreed
+fn waiting_for_move(game_action: u8, game_status: &str) -> bool {
+    can_move(game_action) && game_phase(game_status) == GamePhase::Play
+}
+
+/// For when a batch of moves comes to an end
+fn batch_summary(played: usize) -> String {
+    match played {
+        1 => "Played 1 game this batch".to_string(),
+        played => format!("Played {played} games this batch"),
+    }
+}
+
+/// The body of a DGS answer as text. Handles from older accounts can come back as Latin-1, which
+/// would otherwise turn into replacement characters, so that's used when the Content-Type says
+/// so or the body isn't valid UTF-8
+fn decode_body(body: &[u8], content_type: Option<&str>) -> String {
+    let charset = content_type.and_then(|content_type| {
+        content_type
+            .split(';')
+            .filter_map(|param| param.trim().split_once('='))
+            .find(|(name, _)| name.eq_ignore_ascii_case("charset"))
+            .map(|(_, charset)| charset.trim_matches('"').to_ascii_lowercase())
+    });
+    let latin1 = matches!(
+        charset.as_deref(),
+        Some("iso-8859-1" | "iso8859-1" | "latin1" | "latin-1")
+    );
+    if !latin1 {
+        if let Ok(text) = std::str::from_utf8(body) {
+            return text.to_string();
+        }
+        warn!("Response isn't UTF-8 (charset {charset:?}), reading it as Latin-1");
+    }
+    // Every Latin-1 byte is the Unicode code point with the same number
+    body.iter().map(|&byte| byte as char).collect()
+}
+
+/// Like `Response::text`, but with `decode_body`
+fn response_text(resp: reqwest::blocking::Response) -> reqwest::Result<String> {
+    let content_type = resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let body = resp.bytes()?;
+    Ok(decode_body(&body, content_type.as_deref()))
+}
+
+/// The games in a quick_status.php answer, skipping the other kinds of line
+fn parse_games(status: &str) -> Result<Vec<GameRecord>, csv::Error> {
+    let mut games = vec![];
+    for record_raw in csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(status.as_bytes())
+        .records()
+    {
+        let record_raw = record_raw?;
+        if !record_raw.get(0).is_some_and(|kind| kind.starts_with('G')) {
+            continue;
+        }
+        let record: GameRecord = record_raw.deserialize(None)?;
+        info!("Game: {:#?}", record);
+        games.push(record);
+    }
+    Ok(games)
+}
+
+/// Whether `move_id` is still the latest in `game_id`, going by a fresh game list. A game that's
+/// gone from the list has moved on too, e.g. it's finished
+fn move_id_current(games: &[GameRecord], game_id: u32, move_id: u32) -> bool {
+    games
+        .iter()
+        .any(|game| game.game_id == game_id && game.move_id == move_id)
+}
+
+fn game_command_url(game_id: u32, move_id: u32, cmd: &str, extra: &str) -> String {
+    format!(
+        "https://www.dragongoserver.net/quick_do.php?obj=game&cmd={cmd}&gid={game_id}&move_id={move_id}{extra}"
+    )
+}
+
+/// Whether DGS took a move, going by the quick_do.php reply: JSON with an empty "error" field, or
+/// failing that a body without quick mode's "#Error" marker
+fn move_accepted(body: &str) -> bool {
+    match serde_json::from_str::<serde_json::Value>(body) {
+        Ok(reply) => reply
+            .get("error")
+            .and_then(serde_json::Value::as_str)
+            .map_or(true, str::is_empty),
+        Err(_) => !body.trim().is_empty() && !body.contains("#Error"),
+    }
+}
+
+/// quick_status.php wraps strings in single quotes, but cope if it ever doesn't
+fn strip_quotes(raw: &str) -> String {
+    let raw = raw.strip_prefix('\'').unwrap_or(raw);
+    raw.strip_suffix('\'').unwrap_or(raw).to_string()
+}
+
+/// `point` is 0-based from the top-left, like `Board::pixel_to_point`. `None` off the board
+fn dgs_move_url(game_id: u32, move_id: u32, point: Point2<u8>, board_size: u8) -> Option<String> {
+    let vertex = point_to_gtp(point, board_size)?;
+    Some(game_command_url(
+        game_id,
+        move_id,
+        "move",
+        &format!("&move={}", vertex.to_lowercase()),
+    ))
+}
+
+/// Shown read-only when DGS can't be reached, so there's still something to look at (e.g. when
+/// setting up a tablet before it's online)
+const OFFLINE_SAMPLE_SGF: &str = include_str!("offline_sample.sgf");
+/// The quick_status.php line for the sample. An action of 0 means it can't be moved in
+const OFFLINE_SAMPLE_STATUS: &str = "G,0,'OFFLINE SAMPLE',B,'2025-01-01T00:00:00','F: 30d (+ 1d)',0,'PLAY',14,0,0,'GO',0,'2025-01-01T00:00:00',0\n";
+
+// Mirrors the quick_status.php CSV columns, so not every field is read
+#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 struct GameRecord {
     g: String,
@@ -145,17 +343,70 @@ struct GameRecord {
     handicap: u8,
 }
 
-pub const UNDO_BUTTON_SIZE: Vector2<u32> = Vector2 { x: 250, y: 95 };
-pub const COMMIT_BUTTON_SIZE: Vector2<u32> = Vector2 { x: 350, y: 95 };
+pub const NEXT_BUTTON_SIZE: Vector2<u32> = Vector2 { x: 400, y: 95 };
+pub const END_GAME_BUTTON_SIZE: Vector2<u32> = Vector2 { x: 450, y: 95 };
+// Below the board, as the top of the screen is full of status and move buttons
+const END_GAME_BUTTON_Y: i32 = SCREEN_HEIGHT as i32 - 152;
+// Comments share the row with the end game buttons, which leaves room for this many lines
+const COMMENT_TEXT_SIZE: f32 = 40.0;
+const COMMENT_LINES: usize = 3;
+
+/// How loading games went, so the caller can show the right thing
+#[derive(Debug, PartialEq)]
+enum LoadResult {
+    /// There's a game on the board
+    Loaded,
+    /// Nothing to show, which is fine
+    NoGames,
+    LoginFailed,
+    NetworkError(String),
+    ParseError(String),
+}
+
+impl LoadResult {
+    /// Why there's no game, for the screen with the refresh button. `None` if nothing's wrong
+    fn message(&self, username: &str) -> Option<String> {
+        match self {
+            LoadResult::Loaded | LoadResult::NoGames => None,
+            LoadResult::LoginFailed => Some(format!(
+                "Dragon Go Server didn't accept the login for {username}"
+            )),
+            LoadResult::NetworkError(message) | LoadResult::ParseError(message) => {
+                Some(message.clone())
+            }
+        }
+    }
+}
 
 pub struct BoardConfig {
     board: Board,
-    undo_button_top_left: Point2<i32>,
-    commit_button_top_left: Point2<i32>,
+    next_button: Button<()>,
+    stop_batch_button: Button<()>,
+    resign_button: Button<()>,
+    confirm_resign_button: Button<()>,
+    agree_button: Button<()>,
+    estimate_button: Button<()>,
+    phase: GamePhase,
+    /// Not our turn, or the game's over, so just for looking at
+    read_only: bool,
+    /// What happened, if the game's over
+    result: Option<String>,
+    /// For loading into GnuGo
+    raw_sgf: String,
+    /// GnuGo's idea of the score, once asked for during scoring
+    estimate: Option<String>,
+    /// Latest SGF comment, which is usually a message from one of the players
+    comment: Option<String>,
+    /// For `show_game_info`, from the SGF
+    info: GameInfo,
     player_color: PlayerColor,
     game_id: u32,
     last_move_id: u32,
     opponent_handle: String,
+    last_opponent_move: Option<Point2<u8>>,
+    move_count: usize,
+    black_captures: usize,
+    white_captures: usize,
 }
 
 pub struct DragonGoServer {
@@ -163,56 +414,85 @@ pub struct DragonGoServer {
     white_stones: Vec<Point2<u8>>,
     black_stones: Vec<Point2<u8>>,
     board_config: Option<BoardConfig>,
-    chosen: Option<Point2<u8>>,
+    choice: MoveChoice,
+    /// Why the chosen move can't be committed
+    move_error: Option<&'static str>,
     login_info: LoginInfo,
     fb: Option<&'static mut Framebuffer>,
     error: Option<String>,
+    accounts: Vec<String>,
+    choosing_account: bool,
+    games: Vec<GameRecord>,
+    current_game: usize,
+    confirming_resign: bool,
+    resign_hold: HoldTracker,
+    /// Moves played so far, while going through all the games waiting for one
+    batch: Option<usize>,
+    /// How many moves the last batch played, until there's something else to show
+    batch_played: Option<usize>,
+    /// Latest move ID shown for each game ID, so `reveal_last_move` only goes for new moves
+    seen_moves: HashMap<u32, u32>,
+    /// The opponent's last move is left off while `reveal_last_move` shows the board without it
+    hiding_last_move: bool,
+    /// Showing the offline sample, as DGS couldn't be reached
+    offline: bool,
+    last_update: Option<Instant>,
+    last_touch: Instant,
+    idle: bool,
 }
 
 enum Actions {
     Refresh,
-    Exit,
+    ChooseAccount(usize),
 }
 
-struct Button {
-    text: String,
-    top_left: Point2<i32>,
-    size: Vector2<u32>,
-    action: Actions,
+const BUTTON_WIDTH: u32 = 700;
+const TOP_LEFT_X: i32 = centred_x(BUTTON_WIDTH);
+// Where the status goes on the screens without a board
+const NO_BOARD_STATUS_LEFT: i32 = 100;
+
+/// How long until the games are next due a reload, which is zero if that's now. Waits until
+/// `grace` after the last touch as well, so a reload doesn't change the board under someone
+/// looking at it. `None` while `busy` choosing a move or confirming a resign, as a reload would
+/// throw that away; touches wake `update_loop`, so it picks up again once that's done
+fn next_refresh(
+    since_update: Option<Duration>,
+    since_touch: Duration,
+    busy: bool,
+    grace: Duration,
+) -> Option<Duration> {
+    if busy {
+        return None;
+    }
+    let due = since_update.map_or(Duration::ZERO, |since| {
+        UPDATE_INTERVAL.saturating_sub(since)
+    });
+    Some(due.max(grace.saturating_sub(since_touch)))
 }
 
-const BUTTON_WIDTH: u32 = 700;
-const TOP_LEFT_X: i32 =
-    ((libremarkable::dimensions::DISPLAYWIDTH as u32 - BUTTON_WIDTH) / 2) as i32;
+/// Under the top row of buttons, where "Next game" goes
+fn second_row() -> i32 {
+    20 + scaled(120) as i32
+}
+
+fn list_button_size() -> Vector2<u32> {
+    scaled_size(Vector2 {
+        x: BUTTON_WIDTH,
+        y: 95,
+    })
+}
 
 lazy_static! {
-    static ref NO_GAME_BUTTONS: Vec<Button> = {
-        vec![
-            Button {
-                text: "Refresh".to_string(),
-                top_left: Point2 {
-                    x: TOP_LEFT_X,
-                    y: 300,
-                },
-                size: Vector2 {
-                    x: BUTTON_WIDTH,
-                    y: 95,
-                },
-                action: Actions::Refresh,
+    static ref NO_GAME_BUTTONS: Vec<Button<Actions>> = {
+        vec![Button::new(
+            "Refresh",
+            Point2 {
+                x: centred_x(list_button_size().x),
+                y: 400,
             },
-            Button {
-                text: "Exit".to_string(),
-                top_left: Point2 {
-                    x: TOP_LEFT_X,
-                    y: 500,
-                },
-                size: Vector2 {
-                    x: BUTTON_WIDTH,
-                    y: 95,
-                },
-                action: Actions::Exit,
-            },
-        ]
+            list_button_size(),
+            Actions::Refresh,
+        )]
     };
 }
 
@@ -226,36 +506,177 @@ impl DragonGoServer {
                 .build()
                 .unwrap(),
             board_config: None,
-            chosen: None,
+            choice: MoveChoice::top_right(second_row()),
+            move_error: None,
             login_info: LoginInfo::default(),
             fb: None,
             error: None,
+            accounts: vec![],
+            choosing_account: false,
+            games: vec![],
+            current_game: 0,
+            confirming_resign: false,
+            resign_hold: HoldTracker::default(),
+            batch: None,
+            batch_played: None,
+            seen_moves: HashMap::new(),
+            hiding_last_move: false,
+            offline: false,
+            last_update: None,
+            last_touch: Instant::now(),
+            idle: false,
+        }
+    }
+
+    /// Login files, with the last-used one first
+    fn find_accounts() -> Vec<String> {
+        let mut accounts = vec![];
+        if Path::new(LOGIN_FILE).exists() {
+            accounts.push(LOGIN_FILE.to_string());
+        }
+        if let Ok(entries) = fs::read_dir(LOGIN_DIR) {
+            let mut paths: Vec<String> = entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+                .map(|path| path.to_string_lossy().to_string())
+                .collect();
+            paths.sort();
+            accounts.append(&mut paths);
+        }
+        if let Ok(last_account) = fs::read_to_string(LAST_ACCOUNT_FILE) {
+            if let Some(index) = accounts.iter().position(|a| a == last_account.trim()) {
+                let account = accounts.remove(index);
+                accounts.insert(0, account);
+            }
+        }
+        accounts
+    }
+
+    fn account_label(path: &str) -> String {
+        match fs::read(path)
+            .ok()
+            .and_then(|raw| serde_json::from_slice::<LoginInfo>(&raw).ok())
+        {
+            Some(login_info) if !login_info.username.is_empty() => login_info.username,
+            _ => Path::new(path)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or(path.to_string()),
+        }
+    }
+
+    fn account_buttons(&self) -> Vec<Button<Actions>> {
+        self.accounts
+            .iter()
+            .enumerate()
+            .map(|(index, path)| {
+                Button::new(
+                    &Self::account_label(path),
+                    Point2 {
+                        x: centred_x(list_button_size().x),
+                        // Below the back button
+                        y: 200 + 200 * index as i32,
+                    },
+                    list_button_size(),
+                    Actions::ChooseAccount(index),
+                )
+            })
+            .collect()
+    }
+
+    fn draw_account_picker(&self, fb: &mut Framebuffer) {
+        fb.clear();
+        for button in self.account_buttons() {
+            button.draw(fb);
+        }
+        draw_back(fb);
+        refresh(fb);
+    }
+
+    fn load_login(&mut self, path: &str) {
+        let login_raw = fs::read(path);
+        let mut other_error = false;
+        let login_info: LoginInfo = match login_raw {
+            Ok(raw) => match serde_json::from_slice(&raw) {
+                Ok(li) => li,
+                Err(err) => {
+                    warn!("Error loading login data from {}: {}", path, err);
+                    self.error = Some(format!("Login data in {path} is wrongly formatted"));
+                    other_error = true;
+                    LoginInfo::default()
+                }
+            },
+            Err(err) => {
+                warn!("Can't read login data from {}: {}", path, err);
+                self.error = Some(format!("Can't read login data from {path}"));
+                LoginInfo::default()
+            }
+        };
+        if login_info == LoginInfo::default() && !other_error {
+            self.error = Some(format!("Login data in {path} is default, please change"));
+            let dumped = serde_json::to_vec_pretty(&login_info).expect("can dump login info");
+            fs::write(path, dumped).expect("Can write login info");
+            info!("Dumped default login file");
+        } else {
+            info!("Loaded login info");
         }
+        self.login_info = login_info;
     }
 
     fn draw_choices(&self, fb: &mut Framebuffer) {
         if let Some(ref board_config) = self.board_config {
-            if let Some(ref chosen) = self.chosen {
-                draw_button(
-                    fb,
-                    "Undo",
-                    board_config.undo_button_top_left,
-                    UNDO_BUTTON_SIZE,
-                );
-                draw_button(
-                    fb,
-                    "Commit",
-                    board_config.commit_button_top_left,
-                    COMMIT_BUTTON_SIZE,
-                );
+            self.choice.draw(
+                fb,
+                &board_config.board,
+                board_config.player_color == PlayerColor::White,
+            );
+        }
+    }
 
-                board_config.board.draw_piece(
-                    fb,
-                    chosen.x,
-                    chosen.y,
-                    board_config.player_color == PlayerColor::White,
-                );
+    /// After picking a first point, where the only other change is the resign and next game
+    /// buttons going, so just those spaces get refreshed rather than the whole screen
+    fn show_picked(&self, fb: &mut Framebuffer) {
+        if let Some(ref board_config) = self.board_config {
+            board_config.resign_button.refresh_shown(fb, false);
+            if self.games.len() > 1 {
+                board_config.next_button.refresh_shown(fb, false);
             }
+            self.choice.draw_picked(
+                fb,
+                &board_config.board,
+                board_config.player_color == PlayerColor::White,
+            );
+        }
+    }
+
+    /// Part way through something a reload or a physical button would throw away
+    fn busy(&self) -> bool {
+        self.choosing_account || self.choice.chosen.is_some() || self.confirming_resign
+    }
+
+    /// Mostly blank screen for when nobody's using the device, to save on e-ink ghosting
+    fn draw_idle(&self, fb: &mut Framebuffer) {
+        fb.clear();
+        fb.draw_text(
+            Point2 {
+                x: TOP_LEFT_X as f32,
+                y: 900.0,
+            },
+            "Tap to wake",
+            text_size(),
+            color::BLACK,
+            false,
+        );
+        refresh(fb);
+    }
+
+    /// Whatever screen we'd be showing if we weren't idle, which may have a new move to reveal
+    fn redraw(&mut self, fb: &mut Framebuffer) {
+        if self.choosing_account {
+            self.draw_account_picker(fb);
+        } else {
+            self.show_game(fb);
         }
     }
 
@@ -266,26 +687,89 @@ impl DragonGoServer {
             board_config
                 .board
                 .draw_board(fb, &self.white_stones, &self.black_stones);
-            draw_reset(&board_config.board, fb);
-            self.draw_choices(fb);
-            if self.chosen.is_none() {
-                self.draw_status(
+            board_config.info.draw(fb, &board_config.board, false);
+            if let Some(ref last_move) = board_config
+                .last_opponent_move
+                .filter(|_| !self.hiding_last_move)
+            {
+                board_config.board.draw_last_move_marker(
                     fb,
-                    &format!("opp: {}", &board_config.opponent_handle),
-                    false,
-                    0,
+                    last_move.x - 1,
+                    last_move.y - 1,
+                    board_config.player_color == PlayerColor::Black,
                 );
             }
-            self.draw_status(
-                fb,
-                &format!("colour: {:?}", board_config.player_color),
+            draw_back(fb);
+            fb.draw_text(
+                Point2 {
+                    x: board_config.board.spare_width as f32,
+                    // Between the board and the resign button
+                    y: (END_GAME_BUTTON_Y - 60) as f32,
+                },
+                &match (&board_config.result, &board_config.estimate) {
+                    (Some(result), _) => {
+                        format!("{result} after {} moves", board_config.move_count)
+                    }
+                    (None, Some(estimate)) => format!("GnuGo estimate: {estimate}"),
+                    (None, None) => format!(
+                        "Move {}, black took {}, white took {}",
+                        board_config.move_count,
+                        board_config.black_captures,
+                        board_config.white_captures
+                    ),
+                },
+                40.0,
+                color::BLACK,
                 false,
-                120,
             );
+            self.draw_choices(fb);
+            if self.choice.chosen.is_none() && self.games.len() > 1 {
+                board_config.next_button.draw(fb);
+            }
+            if let Some(played) = self.batch_played {
+                self.draw_status(fb, &batch_summary(played), false);
+            } else if board_config.phase == GamePhase::Finished {
+                self.draw_status(fb, "Game over", false);
+            } else if self.offline {
+                self.draw_status(fb, "OFFLINE SAMPLE", false);
+            } else if board_config.read_only {
+                self.draw_status(fb, "Waiting for opponent", false);
+            } else if self.choice.chosen.is_none() {
+                board_config.resign_button.draw(fb);
+                let status = if self.confirming_resign {
+                    board_config.confirm_resign_button.draw(fb);
+                    "Hold to resign".to_string()
+                } else if board_config.phase == GamePhase::Scoring {
+                    board_config.agree_button.draw(fb);
+                    "Scoring".to_string()
+                } else if let Some(played) = self.batch {
+                    format!("Batch: {played} played")
+                } else {
+                    format!("opp: {}", &board_config.opponent_handle)
+                };
+                self.draw_status(fb, &status, false);
+            } else if let Some(message) = self.move_error {
+                self.draw_status(fb, message, false);
+            }
+            if let Some(ref comment) = board_config.comment {
+                self.draw_comment(fb, board_config, comment);
+            }
+            if self.can_estimate() {
+                board_config.estimate_button.draw(fb);
+            } else if self.batch.is_some() && self.choice.chosen.is_none() {
+                board_config.stop_batch_button.draw(fb);
+            } else {
+                self.status_bar().lowered(scaled(120)).draw(
+                    fb,
+                    &format!("colour: {:?}", board_config.player_color),
+                    false,
+                );
+            }
         } else {
-            let text = match self.error {
-                Some(ref err) => err.clone(),
-                None => "All games up-to-date".to_string(),
+            let text = match (&self.error, self.batch_played) {
+                (Some(err), _) => err.clone(),
+                (None, Some(played)) => format!("All games up-to-date\n{}", batch_summary(played)),
+                (None, None) => "All games up-to-date".to_string(),
             };
             draw_multiline_text(
                 fb,
@@ -295,44 +779,158 @@ impl DragonGoServer {
                     } else {
                         100
                     } as f32,
-                    y: 100 as f32,
+                    y: 200_f32,
                 },
                 &text,
-                if self.error.is_none() { 100.0 } else { 50.0 },
+                if self.error.is_none() {
+                    text_size()
+                } else {
+                    50.0
+                },
                 60,
             );
             for button in NO_GAME_BUTTONS.iter() {
-                draw_button(fb, &button.text, button.top_left, button.size);
+                button.draw(fb);
             }
+            draw_back(fb);
         }
         refresh(fb);
         let elapsed = start.elapsed();
         info!("redraw elapsed: {:.2?}", elapsed);
     }
 
-    fn load_next_game(&mut self) {
+    /// `redraw_stones` for a game that's just been loaded. If `reveal_last_move` is on and the
+    /// opponent's moved since we last saw it, that move's left off at first and then drawn in
+    fn show_game(&mut self, fb: &mut Framebuffer) {
+        let reveal = self.board_config.as_ref().and_then(|board_config| {
+            let seen = self
+                .seen_moves
+                .insert(board_config.game_id, board_config.last_move_id);
+            let new_move = seen != Some(board_config.last_move_id);
+            board_config
+                .last_opponent_move
+                .filter(|_| new_move && SETTINGS.reveal_last_move)
+        });
+        let Some(last_move) = reveal else {
+            self.redraw_stones(fb);
+            return;
+        };
+        let Some(ref board_config) = self.board_config else {
+            return;
+        };
+        let opponent_white = board_config.player_color == PlayerColor::Black;
+        let stones = if opponent_white {
+            &mut self.white_stones
+        } else {
+            &mut self.black_stones
+        };
+        let Some(index) = stones.iter().position(|stone| *stone == last_move) else {
+            warn!("Last move {last_move:?} isn't on the board");
+            self.redraw_stones(fb);
+            return;
+        };
+        info!("Revealing {last_move:?}");
+        stones.remove(index);
+        self.hiding_last_move = true;
+        self.redraw_stones(fb);
+        flush_refresh(fb);
+        sleep(REVEAL_PAUSE);
+        self.hiding_last_move = false;
+        let stones = if opponent_white {
+            &mut self.white_stones
+        } else {
+            &mut self.black_stones
+        };
+        stones.insert(index, last_move);
+        if let Some(ref board_config) = self.board_config {
+            let board = &board_config.board;
+            // Stone lists are 1-based
+            let (x, y) = (last_move.x - 1, last_move.y - 1);
+            board.draw_piece(fb, x, y, opponent_white);
+            board.draw_last_move_marker(fb, x, y, opponent_white);
+            let rect = board.draw_highlight(fb, x, y);
+            refresh_fast(fb, &rect);
+        }
+    }
+
+    /// In whatever's left of the end game button row
+    fn draw_comment(&self, fb: &mut Framebuffer, board_config: &BoardConfig, comment: &str) {
+        let showing_end_game = board_config.phase != GamePhase::Finished
+            && !board_config.read_only
+            && self.choice.chosen.is_none();
+        if showing_end_game && (self.confirming_resign || board_config.phase == GamePhase::Scoring)
+        {
+            // Buttons at both ends, so nowhere to put it
+            return;
+        }
+        let mut left = board_config.board.spare_width as i32;
+        if showing_end_game {
+            left += scaled(END_GAME_BUTTON_SIZE.x) as i32 + 40;
+        }
+        let right = (board_config.board.spare_width + board_config.board.grid_size()) as i32;
+        // Roughly how many characters fit, as they're about half as wide as they are high
+        let width = ((right - left).max(0) as f32 / (COMMENT_TEXT_SIZE / 2.0)) as usize;
+        let lines = comment_lines(comment, width, COMMENT_LINES);
+        draw_multiline_text(
+            fb,
+            Point2 {
+                x: left as f32,
+                y: END_GAME_BUTTON_Y as f32 + COMMENT_TEXT_SIZE,
+            },
+            &lines.join("\n"),
+            COMMENT_TEXT_SIZE,
+            width,
+        );
+    }
+
+    /// Logs in and gets the list of games, then loads the most urgent one
+    fn load_next_game(&mut self) -> LoadResult {
         self.white_stones.clear();
         self.black_stones.clear();
-        let login_resp = match self
+        self.offline = false;
+        let login_text = self
             .client
             .post(format!(
                 "https://www.dragongoserver.net/login.php?quick_mode=1&userid={}&passwd={}",
                 self.login_info.username, self.login_info.password
             ))
             .send()
-        {
-            Ok(resp) => resp,
+            .and_then(response_text);
+        let login_text = match login_text {
+            Ok(text) => text,
             Err(err) => {
                 error!("Error logging in: {}", err);
-                self.error = Some(format!("Error logging in: {}", err));
-                return;
+                return self.load_offline_sample();
             }
         };
-        let login_text = login_resp.text().unwrap();
         if !login_text.contains("Ok") {
             warn!("Error logging in: {}", login_text);
-            return;
+            self.board_config = None;
+            return LoadResult::LoginFailed;
         }
+        self.games = match self.fetch_games() {
+            Ok(games) => games,
+            Err(result) => {
+                error!("Can't get games: {result:?}");
+                self.games.clear();
+                self.board_config = None;
+                return result;
+            }
+        };
+        // Games we can play first, most urgent first, then ones we can only look at
+        self.games.sort_by_key(|g| {
+            (
+                game_phase(&g.game_status) == GamePhase::Finished,
+                !can_move(g.game_action),
+                g.time_remaining,
+            )
+        });
+        self.current_game = 0;
+        self.load_current_game()
+    }
+
+    /// Every game from quick_status.php, in the order it gives them
+    fn fetch_games(&self) -> Result<Vec<GameRecord>, LoadResult> {
         let status = self
             .client
             .get(format!(
@@ -340,34 +938,31 @@ impl DragonGoServer {
                 self.login_info.username
             ))
             .send()
-            .unwrap()
-            .text()
-            .unwrap();
-        let mut first_expiring_game: Option<GameRecord> = None;
-        // info!("Status: {}", status);
-        for record_raw_res in csv::ReaderBuilder::new()
-            .has_headers(false)
-            .flexible(true)
-            .from_reader(status.as_bytes())
-            .records()
-        {
-            // info!("Record raw: {:#?}", record_raw_res);
-            let record_raw = record_raw_res.unwrap();
-            if !record_raw.get(0).unwrap().starts_with("G") {
-                continue;
-            }
-            let record: GameRecord = record_raw.deserialize(None).unwrap();
-            info!("Game: {:#?}", record);
-            if first_expiring_game
-                .as_ref()
-                .and_then(|g| Some(g.time_remaining > record.time_remaining))
-                .unwrap_or(true)
-            {
-                first_expiring_game.replace(record);
+            .and_then(response_text)
+            .map_err(|err| LoadResult::NetworkError(format!("Error getting games: {err}")))?;
+        parse_games(&status).map_err(|err| LoadResult::ParseError(format!("Bad game list: {err}")))
+    }
+
+    /// The sample game, in place of the real ones
+    fn load_offline_sample(&mut self) -> LoadResult {
+        warn!("Can't reach DGS, showing the offline sample");
+        self.games = match parse_games(OFFLINE_SAMPLE_STATUS) {
+            Ok(games) => games,
+            Err(err) => {
+                error!("Bad offline sample status: {err}");
+                self.board_config = None;
+                return LoadResult::ParseError("Couldn't load the offline sample".to_string());
             }
-        }
+        };
+        self.current_game = 0;
+        self.offline = true;
+        self.choice.chosen = None;
+        self.confirming_resign = false;
+        self.show_sgf(OFFLINE_SAMPLE_SGF.to_string())
+    }
 
-        if let Some(game) = first_expiring_game {
+    fn load_current_game(&mut self) -> LoadResult {
+        if let Some(game) = self.games.get(self.current_game) {
             let raw_sgf = self
                     .client
                     .get(format!(
@@ -375,248 +970,950 @@ impl DragonGoServer {
                         game.game_id
                     ))
                     .send()
-                    .unwrap()
-                    .text()
-                    .unwrap();
-            let mut game_data = get_game_data(&raw_sgf);
+                    .and_then(response_text);
+            match raw_sgf {
+                Ok(raw_sgf) => self.show_sgf(raw_sgf),
+                Err(err) => {
+                    error!("Can't get SGF for game {}: {err}", game.game_id);
+                    self.board_config = None;
+                    LoadResult::NetworkError(format!("Error getting game: {err}"))
+                }
+            }
+        } else {
+            self.move_error = None;
+            self.white_stones.clear();
+            self.black_stones.clear();
+            self.board_config = None;
+            self.stop_batch();
+            LoadResult::NoGames
+        }
+    }
+
+    /// Sets the board up from `raw_sgf`, for the current game in `games`
+    fn show_sgf(&mut self, raw_sgf: String) -> LoadResult {
+        self.move_error = None;
+        self.white_stones.clear();
+        self.black_stones.clear();
+        if let Some(game) = self.games.get(self.current_game) {
+            let mut game_data = match get_game_data(&raw_sgf) {
+                Ok(game_data) => game_data,
+                Err(err) => {
+                    error!("Bad SGF for game {}: {err}", game.game_id);
+                    self.board_config = None;
+                    return LoadResult::ParseError("Couldn't load game data".to_string());
+                }
+            };
             self.white_stones.append(&mut game_data.white_stones);
             self.black_stones.append(&mut game_data.black_stones);
+            // get_game_data goes with 19 if there's no SZ, as DGS does
             let board = Board::new(game_data.size);
-            let undo_button_top_left = Point2 {
-                x: (board.spare_width + AVAILABLE_WIDTH / 2 - 170) as i32,
-                y: 20,
+            let second_row = second_row();
+            let next_button = Button::new(
+                "Next game",
+                Point2 {
+                    x: back_button_top_left().x,
+                    y: second_row,
+                },
+                scaled_size(NEXT_BUTTON_SIZE),
+                (),
+            );
+            // Where the colour goes, like "Estimate", which is only there when scoring
+            let stop_batch_button = Button::new(
+                "Stop batch",
+                Point2 {
+                    x: board.spare_width as i32,
+                    y: second_row,
+                },
+                scaled_size(NEXT_BUTTON_SIZE),
+                (),
+            );
+            let resign_button = Button::new(
+                "Resign",
+                Point2 {
+                    x: board.spare_width as i32,
+                    y: END_GAME_BUTTON_Y,
+                },
+                scaled_size(END_GAME_BUTTON_SIZE),
+                (),
+            );
+            let end_game_size = scaled_size(END_GAME_BUTTON_SIZE);
+            let end_game_right = Point2 {
+                x: (board.spare_width + board.grid_size()) as i32 - end_game_size.x as i32,
+                y: END_GAME_BUTTON_Y,
             };
-            let commit_button_top_left = Point2 {
-                x: (board.spare_width + AVAILABLE_WIDTH / 2 - 640) as i32,
-                y: 20,
+            let confirm_resign_button =
+                Button::new("Yes, resign", end_game_right, end_game_size, ()).held();
+            let agree_button = Button::new("Agree score", end_game_right, end_game_size, ());
+            // Where the colour goes otherwise, which matters less once it's time to score
+            let estimate_button = Button::new(
+                "Estimate",
+                Point2 {
+                    x: board.spare_width as i32,
+                    y: second_row,
+                },
+                scaled_size(COMMIT_BUTTON_SIZE),
+                (),
+            );
+            // The SGF result is more up to date than the status if they disagree
+            let phase = if game_data.result.is_some() {
+                GamePhase::Finished
+            } else {
+                game_phase(&game.game_status)
             };
+            let last_opponent_move = match game.player_color {
+                PlayerColor::Black => game_data.last_white_move,
+                PlayerColor::White => game_data.last_black_move,
+            };
+            let mut read_only = !can_move(game.game_action) || phase == GamePhase::Finished;
+            // Again the SGF wins, as the game may have moved on since the status list
+            if !read_only && game_data.to_play != game.player_color.stone() {
+                warn!(
+                    "Status says we're {:?} to move, but the SGF has {:?} to play",
+                    game.player_color, game_data.to_play
+                );
+                read_only = true;
+            }
             self.board_config = Some(BoardConfig {
                 player_color: game.player_color.clone(),
                 board,
-                undo_button_top_left,
-                commit_button_top_left,
+                next_button,
+                stop_batch_button,
+                resign_button,
+                confirm_resign_button,
+                agree_button,
+                estimate_button,
+                phase,
+                read_only,
+                result: game_data.result.as_deref().map(describe_result),
                 game_id: game.game_id,
                 last_move_id: game.move_id,
-                opponent_handle: game
-                    .opponent_handle
-                    .strip_prefix("'")
-                    .unwrap()
-                    .strip_suffix("'")
-                    .unwrap()
-                    .to_string(),
+                opponent_handle: strip_quotes(&game.opponent_handle),
+                last_opponent_move,
+                move_count: game_data.move_count,
+                black_captures: game_data.black_captures,
+                white_captures: game_data.white_captures,
+                raw_sgf,
+                estimate: None,
+                comment: game_data.comment.take(),
+                info: GameInfo {
+                    size: game_data.size,
+                    komi: game_data.komi,
+                    handicap: game_data.handicap,
+                    to_play: (phase != GamePhase::Finished).then_some(game_data.to_play),
+                },
             });
         } else {
             self.board_config = None;
         }
+        let playable = self.board_config.as_ref().is_some_and(|board_config| {
+            !board_config.read_only && board_config.phase == GamePhase::Play
+        });
+        if !playable {
+            self.stop_batch();
+        }
+        match self.board_config {
+            Some(_) => LoadResult::Loaded,
+            None => LoadResult::NoGames,
+        }
     }
 
-    fn draw_status(&self, fb: &mut Framebuffer, text: &str, refresh: bool, offset: u16) {
-        if let Some(ref board_config) = self.board_config {
-            let rect_width = 550;
-            fb.fill_rect(
-                Point2 {
-                    x: board_config.board.spare_width as i32,
-                    y: offset as i32,
-                },
-                Vector2 {
-                    x: rect_width,
-                    y: (80 + offset) as u32,
-                },
-                color::WHITE,
-            );
-            fb.draw_text(
-                Point2 {
-                    x: board_config.board.spare_width as f32,
-                    y: (100 + offset) as f32,
-                },
-                text,
-                100.0,
-                color::BLACK,
-                false,
-            );
+    /// Shows how loading went: the game if there is one, otherwise why not
+    fn show_loaded(&mut self, fb: &mut Framebuffer, result: LoadResult) {
+        info!("Load result: {result:?}");
+        self.error = result.message(&self.login_info.username);
+        self.show_game(fb);
+    }
 
-            if refresh {
-                refresh_with_options(
-                    fb,
-                    &mxcfb_rect {
-                        top: offset as u32,
-                        left: board_config.board.spare_width as u32,
-                        width: rect_width,
-                        height: 80,
-                    },
-                    waveform_mode::WAVEFORM_MODE_AUTO,
-                );
-            }
+    /// Ends the batch, if there is one, so it can say how it went
+    fn stop_batch(&mut self) {
+        if let Some(played) = self.batch.take() {
+            info!("Batch done after {played} moves");
+            self.batch_played = Some(played);
         }
     }
-}
 
-impl Routine for DragonGoServer {
-    fn init(&mut self, fb: &'static mut Framebuffer, _ctrl: &mut Engine) {
-        let login_raw = fs::read(LOGIN_FILE);
-        let mut other_error = false;
-        let login_info: LoginInfo = match login_raw {
-            Ok(raw) => match serde_json::from_slice(&raw) {
-                Ok(li) => li,
+    /// 0-based point for a touch, if it's on an empty intersection
+    fn empty_point_at(&self, board: &Board, pos: Point2<u16>) -> Option<Point2<u8>> {
+        let Some(point) = board.pixel_to_point(pos) else {
+            info!("Bad point from {pos:?}");
+            return None;
+        };
+        // FIXME: Because GTP points are offset
+        let offset_point = Point2 {
+            x: point.x + 1,
+            y: point.y + 1,
+        };
+        if self.white_stones.contains(&offset_point) || self.black_stones.contains(&offset_point) {
+            info!("Can't use existing point");
+            return None;
+        }
+        Some(point)
+    }
+
+    /// Skips to the next most urgent game, wrapping back round to the most urgent
+    fn skip_game(&mut self) -> LoadResult {
+        if self.games.is_empty() {
+            return LoadResult::NoGames;
+        }
+        self.current_game = (self.current_game + 1) % self.games.len();
+        info!(
+            "Skipping to game {} of {}",
+            self.current_game + 1,
+            self.games.len()
+        );
+        self.choice.chosen = None;
+        self.confirming_resign = false;
+        self.load_current_game()
+    }
+
+    /// Sends a game command (resign, score, etc.) for the current game, then moves on to the next one
+    fn send_game_command(&mut self, cmd: &str, extra: &str) -> LoadResult {
+        self.confirming_resign = false;
+        if let Some(ref board_config) = self.board_config {
+            let url = game_command_url(board_config.game_id, board_config.last_move_id, cmd, extra);
+            info!("Url: {url}");
+            match self.client.post(url).send().and_then(response_text) {
+                Ok(resp) => info!("{cmd} resp: {resp}"),
                 Err(err) => {
-                    warn!("Error loading login data from {}: {}", LOGIN_FILE, err);
-                    self.error = Some(format!("Login data in {LOGIN_FILE} is wrongly formatted"));
-                    other_error = true;
-                    LoginInfo::default()
+                    error!("Error sending {cmd}: {err}");
+                    self.board_config = None;
+                    return LoadResult::NetworkError(format!("Error sending {cmd}: {err}"));
                 }
+            }
+        }
+        self.load_next_game()
+    }
+
+    fn can_estimate(&self) -> bool {
+        self.board_config.as_ref().is_some_and(|board_config| {
+            board_config.phase == GamePhase::Scoring
+                && !board_config.read_only
+                && self.choice.chosen.is_none()
+                && !self.confirming_resign
+        })
+    }
+
+    /// Gets GnuGo to score the position, so it can be checked before agreeing
+    fn estimate_score(&mut self, ctrl: &mut Engine, fb: &mut Framebuffer) {
+        let Some(ref board_config) = self.board_config else {
+            return;
+        };
+        let loaded = load_sgf(ctrl, &board_config.raw_sgf, |done, total| {
+            // Only for engines without loadsgf. Refreshing is slow too, so not every stone
+            if done % 10 == 0 || done == total {
+                self.draw_status(fb, &format!("Placing {done}/{total}"), true);
+            }
+        });
+        self.draw_status(fb, "Estimating...", true);
+        let estimate = match loaded.and_then(|_| estimate_score(ctrl)) {
+            Ok(text) => match parse_estimate(&text) {
+                Some(score) => score.to_string(),
+                None => text,
             },
             Err(err) => {
-                warn!("Can't read login data from {}: {}", LOGIN_FILE, err);
-                self.error = Some(format!("Can't read login data from {LOGIN_FILE}"));
-                LoginInfo::default()
+                error!("Can't estimate score: {err}");
+                err.status_text().to_string()
             }
         };
-        if login_info == LoginInfo::default() && !other_error {
-            self.error = Some(format!(
-                "Login data in {LOGIN_FILE} is default, please change"
-            ));
-            let dumped = serde_json::to_vec_pretty(&login_info).expect("can dump login info");
-            fs::write(LOGIN_FILE, dumped).expect("Can write login info");
-            info!("Dumped default login file");
+        if let Some(ref mut board_config) = self.board_config {
+            board_config.estimate = Some(estimate);
+        }
+        self.redraw_stones(fb);
+    }
+
+    /// Looks for suicide locally, then has GnuGo try the move on the game so far, which catches
+    /// ko too. Trouble with GnuGo doesn't stop the move, as DGS checks it anyway
+    fn check_move(
+        &self,
+        ctrl: &mut Engine,
+        fb: &mut Framebuffer,
+        point: Point2<u8>,
+    ) -> Result<(), &'static str> {
+        let Some(ref board_config) = self.board_config else {
+            return Ok(());
+        };
+        let colour = board_config.player_color.stone();
+        let size = board_config.board.board_size;
+        let stones = (self.white_stones.clone(), self.black_stones.clone());
+        if is_suicide(&stones, size, point, colour) {
+            info!("{point:?} is suicide");
+            return Err("Suicide");
+        }
+        self.draw_status(fb, "Checking...", true);
+        match try_sgf_move(ctrl, &board_config.raw_sgf, point, size, colour) {
+            Ok(PlayResult::Played) => Ok(()),
+            Ok(PlayResult::Illegal) => {
+                info!("GnuGo won't play {point:?}");
+                Err("Illegal")
+            }
+            // Not about the move itself, so like any other trouble with GnuGo
+            Ok(PlayResult::Refused(text)) => {
+                warn!("GnuGo refused {point:?}: {text}");
+                Ok(())
+            }
+            Err(err) => {
+                warn!("Can't check move with GnuGo: {err}");
+                Ok(())
+            }
+        }
+    }
+
+    /// Left edge of the status text, which lines up with the board if there is one
+    fn status_left(&self) -> i32 {
+        self.board_config
+            .as_ref()
+            .map_or(NO_BOARD_STATUS_LEFT, |board_config| {
+                board_config.board.spare_width as i32
+            })
+    }
+
+    fn status_bar(&self) -> StatusBar {
+        let status = StatusBar::new(self.status_left());
+        if self.board_config.is_some() {
+            // Clear of the undo and commit buttons
+            status.capped(scaled(550))
         } else {
-            info!("Loaded login info");
+            status
+        }
+    }
+
+    fn draw_status(&self, fb: &mut Framebuffer, text: &str, refresh: bool) {
+        self.status_bar().draw(fb, text, refresh);
+    }
+
+    /// Shown straight away, as the network calls that follow can take a few seconds. Goes when
+    /// the screen's next redrawn
+    fn draw_loading(&self, fb: &mut Framebuffer) {
+        self.draw_status(fb, "Loading...", true);
+    }
+}
+
+impl Routine for DragonGoServer {
+    fn init(&mut self, fb: &'static mut Framebuffer, _ctrl: &mut Engine) {
+        self.accounts = Self::find_accounts();
+        match self.accounts.len() {
+            0 => self.load_login(LOGIN_FILE),
+            1 => self.load_login(&self.accounts[0].clone()),
+            _ => {
+                info!("Multiple accounts: {:?}", self.accounts);
+                self.choosing_account = true;
+                self.draw_account_picker(fb);
+            }
         }
-        self.login_info = login_info;
         self.fb = Some(fb);
     }
 
-    fn update_loop(&mut self) -> Option<Duration> {
-        info!("Update game");
-        if self.chosen.is_none() {
-            self.load_next_game();
-            let current_fb = self.fb.take();
-            if current_fb.is_some() {
-                let mut fb = current_fb.unwrap();
-                self.redraw_stones(&mut fb);
-                let _empty = self.fb.insert(fb);
+    fn update_loop(&mut self, _ctrl: &mut Engine) -> Option<Duration> {
+        let Some(fb) = self.fb.take() else {
+            error!("No framebuffer!");
+            return Some(UPDATE_INTERVAL);
+        };
+        let wait = next_refresh(
+            self.last_update.map(|t| t.elapsed()),
+            self.last_touch.elapsed(),
+            self.busy(),
+            SETTINGS.refresh_grace(),
+        );
+        if wait == Some(Duration::ZERO) {
+            info!("Update game");
+            self.last_update = Some(Instant::now());
+            if !self.idle {
+                self.draw_loading(fb);
+            }
+            let result = self.load_next_game();
+            if self.idle {
+                // Shown once it wakes up
+                self.error = result.message(&self.login_info.username);
             } else {
-                error!("No framebuffer!");
+                self.show_loaded(fb, result);
+            }
+        }
+        let mut next_check = match wait {
+            Some(Duration::ZERO) => UPDATE_INTERVAL,
+            Some(wait) => wait,
+            None => {
+                info!("Busy, not updating");
+                UPDATE_INTERVAL
+            }
+        };
+        if let Some(idle_timeout) = SETTINGS.idle_timeout() {
+            if !self.idle {
+                let since_touch = self.last_touch.elapsed();
+                if since_touch >= idle_timeout {
+                    info!("Idle for {:.2?}", since_touch);
+                    self.idle = true;
+                    self.draw_idle(fb);
+                } else {
+                    next_check = next_check.min(idle_timeout - since_touch);
+                }
             }
-        } else {
-            info!("Chosen set, not updating");
         }
-        Some(Duration::from_secs(60 * 10))
+        flush_refresh(fb);
+        let _empty = self.fb.insert(fb);
+        Some(next_check)
+    }
+
+    /// Left reloads the games now rather than waiting for the next update, and right skips to
+    /// the next game. Neither throws away a chosen move or a resign that's being confirmed
+    fn on_button_event(
+        &mut self,
+        ctx: &mut appctx::ApplicationContext<'_>,
+        button: PhysicalButton,
+        _ctrl: &mut Engine,
+    ) {
+        let fb = ctx.get_framebuffer_ref();
+        self.last_touch = Instant::now();
+        if self.idle {
+            info!("Waking from idle");
+            self.idle = false;
+            self.redraw(fb);
+            return;
+        }
+        if self.busy() {
+            info!("Busy, ignoring {button:?}");
+            return;
+        }
+        match button {
+            PhysicalButton::LEFT => {
+                self.last_update = Some(Instant::now());
+                self.draw_loading(fb);
+                let result = self.load_next_game();
+                self.show_loaded(fb, result);
+            }
+            PhysicalButton::RIGHT if self.games.len() > 1 => {
+                let result = self.skip_game();
+                self.show_loaded(fb, result);
+            }
+            _ => {}
+        }
     }
 
     fn on_multitouch_event(
         &mut self,
         ctx: &mut appctx::ApplicationContext<'_>,
         event: MultitouchEvent,
-        _ctrl: &mut Engine,
+        ctrl: &mut Engine,
     ) {
-        match event {
-            MultitouchEvent::Press { finger } => {
-                let start = Instant::now();
+        let held = match self.board_config {
+            Some(ref board_config) if self.confirming_resign && !self.idle => {
+                self.resign_hold.handle(
+                    ctx.get_framebuffer_ref(),
+                    slice::from_ref(&board_config.confirm_resign_button),
+                    &event,
+                )
+            }
+            _ => Held::Ignored,
+        };
+        match held {
+            Held::Fired(()) => {
                 let fb = ctx.get_framebuffer_ref();
-
+                self.last_touch = Instant::now();
                 if let Some(ref board_config) = self.board_config {
-                    let board = &board_config.board;
-                    let rbtl = reset_button_top_left(board);
-                    info!("rbtl: {rbtl:?}");
-                    if (finger.pos.x as i32) >= rbtl.x
-                        && (finger.pos.x as i32) < (rbtl.x + RESET_BUTTON_SIZE.x as i32)
-                        && (finger.pos.y as i32) >= rbtl.y
-                        && (finger.pos.y as i32) < (rbtl.y + RESET_BUTTON_SIZE.y as i32)
+                    info!("Resigning game {}", board_config.game_id);
+                }
+                self.draw_loading(fb);
+                let result = self.send_game_command("resign", "");
+                self.show_loaded(fb, result);
+                return;
+            }
+            Held::Holding => return,
+            Held::Ignored => {}
+        }
+
+        if let MultitouchEvent::Press { finger } = event {
+            let start = Instant::now();
+            let fb = ctx.get_framebuffer_ref();
+            self.last_touch = start;
+
+            if self.idle {
+                // Just wake up, rather than acting on a touch made against a blank screen
+                info!("Waking from idle");
+                self.idle = false;
+                self.redraw(fb);
+                return;
+            }
+
+            if handle_back(ctx, finger.pos) {
+                return;
+            }
+            // Seen by now, so it can make way for the usual status
+            let showed_summary = self.batch_played.take().is_some();
+
+            if self.choosing_account {
+                if let Some(button) = pressed(&self.account_buttons(), finger.pos) {
+                    match button.action {
+                        Actions::ChooseAccount(index) => {
+                            let account = self.accounts[index].clone();
+                            info!("Chose account {account}");
+                            if let Err(err) = fs::write(LAST_ACCOUNT_FILE, &account) {
+                                warn!("Can't write {LAST_ACCOUNT_FILE}: {err}");
+                            }
+                            self.load_login(&account);
+                            self.choosing_account = false;
+                            self.draw_loading(fb);
+                            let result = self.load_next_game();
+                            self.show_loaded(fb, result);
+                        }
+                        Actions::Refresh => {}
+                    }
+                }
+                return;
+            }
+
+            if let Some(ref board_config) = self.board_config {
+                let board = &board_config.board;
+                if board_config.read_only {
+                    if self.games.len() > 1 && board_config.next_button.contains(finger.pos) {
+                        let result = self.skip_game();
+                        self.show_loaded(fb, result);
+                    } else if showed_summary {
+                        self.redraw_stones(fb);
+                    }
+                } else if self.choice.chosen.is_none() {
+                    if self.confirming_resign {
+                        // "Yes, resign" is held rather than tapped, so anything else cancels
+                        info!("Resign cancelled");
+                        self.confirming_resign = false;
+                        self.redraw_stones(fb);
+                        return;
+                    }
+
+                    if self.batch.is_some()
+                        && !self.can_estimate()
+                        && board_config.stop_batch_button.contains(finger.pos)
                     {
-                        *CURRENT_MODE.lock().unwrap() = crate::chooser::Mode::Chooser;
-                        ctx.stop();
+                        info!("Batch stopped");
+                        self.stop_batch();
+                        self.redraw_stones(fb);
                         return;
                     }
 
-                    if self.chosen.is_none() {
-                        let point = board.nearest_spot(finger.pos.x, finger.pos.y);
-                        if point.x >= board.board_size || point.y >= board.board_size {
-                            info!("Bad point {point:?} from {:?}", finger.pos);
-                            return;
-                        }
-                        // FIXME: Because GTP points are offset
-                        let offset_point = Point2 {
-                            x: point.x + 1,
-                            y: point.y + 1,
-                        };
-                        if self.white_stones.contains(&offset_point)
-                            || self.black_stones.contains(&offset_point)
-                        {
-                            info!("Can't use existing point");
-                            return;
+                    if board_config.resign_button.contains(finger.pos) {
+                        self.confirming_resign = true;
+                        self.redraw_stones(fb);
+                        return;
+                    }
+
+                    if self.games.len() > 1 && board_config.next_button.contains(finger.pos) {
+                        let result = self.skip_game();
+                        self.show_loaded(fb, result);
+                        return;
+                    }
+
+                    if self.can_estimate() && board_config.estimate_button.contains(finger.pos) {
+                        self.estimate_score(ctrl, fb);
+                        return;
+                    }
+
+                    if board_config.phase == GamePhase::Scoring {
+                        if board_config.agree_button.contains(finger.pos) {
+                            info!("Agreeing score for game {}", board_config.game_id);
+                            self.draw_loading(fb);
+                            let result = self.send_game_command("score", "&agree=1");
+                            self.show_loaded(fb, result);
+                        } else {
+                            info!("Scoring, so no moves");
                         }
-                        info!("Drawing: {point:?} for {:?}", finger.pos);
-                        self.chosen = Some(point);
+                        return;
+                    }
+
+                    let point = self.empty_point_at(board, finger.pos);
+                    let picked = matches!(
+                        self.choice.handle_press(finger.pos, point),
+                        ChoicePress::Picked
+                    );
+                    if picked && !showed_summary && self.batch.is_none() {
+                        self.show_picked(fb);
+                    } else if picked || showed_summary {
                         self.redraw_stones(fb);
-                    } else {
-                        if (finger.pos.x as i32) >= board_config.undo_button_top_left.x
-                            && (finger.pos.x as i32)
-                                < (board_config.undo_button_top_left.x + UNDO_BUTTON_SIZE.x as i32)
-                            && (finger.pos.y as i32) >= board_config.undo_button_top_left.y
-                            && (finger.pos.y as i32)
-                                < (board_config.undo_button_top_left.y + UNDO_BUTTON_SIZE.y as i32)
-                        {
-                            self.chosen = None;
+                    }
+                } else {
+                    let point = self.empty_point_at(board, finger.pos);
+                    match self.choice.handle_press(finger.pos, point) {
+                        ChoicePress::Nothing => {}
+                        ChoicePress::Picked | ChoicePress::Changed => {
+                            self.move_error = None;
                             self.redraw_stones(fb);
                         }
-
-                        if (finger.pos.x as i32) >= board_config.commit_button_top_left.x
-                            && (finger.pos.x as i32)
-                                < (board_config.commit_button_top_left.x
-                                    + COMMIT_BUTTON_SIZE.x as i32)
-                            && (finger.pos.y as i32) >= board_config.commit_button_top_left.y
-                            && (finger.pos.y as i32)
-                                < (board_config.commit_button_top_left.y
-                                    + COMMIT_BUTTON_SIZE.y as i32)
-                        {
-                            let chosen = self.chosen.take().unwrap();
-                            // because i
-                            let column_chars = (0..board.board_size + 1)
-                                .into_iter()
-                                .map(|x| char::from_u32(('a' as u32) + x as u32).unwrap())
-                                .filter(|p| *p != 'i')
-                                .collect::<Vec<char>>();
-                            let url = format!(
-                                "https://www.dragongoserver.net/quick_do.php?obj=game&cmd=move&gid={}&move_id={}&move={}{}",
+                        ChoicePress::Commit(chosen) => {
+                            if let Err(message) = self.check_move(ctrl, fb, chosen) {
+                                // Left on the board, so it can be moved somewhere that works
+                                self.choice.chosen = Some(chosen);
+                                self.move_error = Some(message);
+                                self.redraw_stones(fb);
+                                return;
+                            }
+                            // The opponent may have done something since the game was loaded,
+                            // and DGS turns down moves with an old move ID
+                            let game_id = board_config.game_id;
+                            match self.fetch_games() {
+                                Ok(games)
+                                    if !move_id_current(
+                                        &games,
+                                        game_id,
+                                        board_config.last_move_id,
+                                    ) =>
+                                {
+                                    info!("Game {game_id} changed, so reloading it");
+                                    self.current_game = games
+                                        .iter()
+                                        .position(|game| game.game_id == game_id)
+                                        .unwrap_or(0);
+                                    self.games = games;
+                                    let result = self.load_current_game();
+                                    let loaded = result == LoadResult::Loaded;
+                                    self.show_loaded(fb, result);
+                                    if loaded {
+                                        self.draw_status(fb, "Game changed, try again", true);
+                                    }
+                                    return;
+                                }
+                                Ok(_) => {}
+                                Err(result) => {
+                                    warn!("Can't check game {game_id} is current: {result:?}");
+                                    self.choice.chosen = Some(chosen);
+                                    self.move_error = Some("Can't reach DGS, try again");
+                                    self.redraw_stones(fb);
+                                    return;
+                                }
+                            }
+                            let Some(url) = dgs_move_url(
                                 board_config.game_id,
                                 board_config.last_move_id,
-                                column_chars.get(chosen.x as usize).unwrap(),
-                                board_config.board.board_size-chosen.y
-                            );
+                                chosen,
+                                board.board_size,
+                            ) else {
+                                warn!("{chosen:?} is off the board");
+                                self.redraw_stones(fb);
+                                return;
+                            };
                             info!("Url: {url}");
-                            let move_resp = self.client.post(url).send().unwrap().text().unwrap();
-                            info!("Move resp: {}", move_resp);
-
-                            self.load_next_game();
-                            self.redraw_stones(fb);
-                        }
-                    }
-                } else {
-                    for button in NO_GAME_BUTTONS.iter() {
-                        if (finger.pos.x as i32) >= button.top_left.x
-                            && (finger.pos.x as i32) < (button.top_left.x + button.size.x as i32)
-                            && (finger.pos.y as i32) >= button.top_left.y
-                            && (finger.pos.y as i32) < (button.top_left.y + button.size.y as i32)
-                        {
-                            match button.action {
-                                Actions::Refresh => {
-                                    self.load_next_game();
+                            match self.client.post(url).send().and_then(response_text) {
+                                Ok(move_resp) if move_accepted(&move_resp) => {
+                                    info!("Move resp: {}", move_resp);
+                                }
+                                Ok(move_resp) => {
+                                    warn!("DGS turned down {chosen:?}: {move_resp}");
+                                    self.choice.chosen = Some(chosen);
+                                    self.move_error = Some("DGS didn't take that move");
                                     self.redraw_stones(fb);
+                                    return;
                                 }
-                                Actions::Exit => {
-                                    *CURRENT_MODE.lock().unwrap() = crate::chooser::Mode::Chooser;
-                                    ctx.stop();
+                                Err(err) => {
+                                    warn!("Can't send {chosen:?}: {err}");
+                                    self.choice.chosen = Some(chosen);
+                                    self.move_error = Some("Can't reach DGS, try again");
+                                    self.redraw_stones(fb);
                                     return;
                                 }
                             }
+
+                            // Carries on through the other games waiting for a move, if there are any
+                            let others_waiting = self.games.iter().any(|game| {
+                                game.game_id != board_config.game_id
+                                    && waiting_for_move(game.game_action, &game.game_status)
+                            });
+                            if let Some(played) = self.batch {
+                                self.batch = Some(played + 1);
+                            } else if others_waiting {
+                                info!("Starting a batch");
+                                self.batch = Some(1);
+                            }
+                            self.draw_loading(fb);
+                            let result = self.load_next_game();
+                            self.show_loaded(fb, result);
                         }
                     }
                 }
-
-                let elapsed = start.elapsed();
-                info!("touch elapsed: {:.2?}", elapsed);
+            } else {
+                if let Some(button) = pressed(&NO_GAME_BUTTONS, finger.pos) {
+                    match button.action {
+                        Actions::Refresh => {
+                            self.draw_loading(fb);
+                            let result = self.load_next_game();
+                            self.show_loaded(fb, result);
+                        }
+                        Actions::ChooseAccount(_) => {}
+                    }
+                }
             }
-            _ => {}
+
+            let elapsed = start.elapsed();
+            info!("touch elapsed: {:.2?}", elapsed);
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeDelta;
+    use pretty_assertions::assert_eq;
+    use std::time::Duration;
+
+    use libremarkable::cgmath::Point2;
+
+    use crate::{
+        dragon_go_server::{
+            batch_summary, can_move, comment_lines, decode_body, describe_result, dgs_move_url,
+            game_command_url, game_phase, move_accepted, move_id_current, next_refresh,
+            parse_games, strip_quotes, waiting_for_move, GamePhase, LoadResult, OFFLINE_SAMPLE_SGF,
+            OFFLINE_SAMPLE_STATUS,
+        },
+        game_parse::get_game_data,
+    };
+
+    #[test]
+    fn refresh_waits() {
+        let grace = Duration::from_secs(10);
+        let minutes = |m: u64| Duration::from_secs(60 * m);
+        // Never loaded, and not touched lately
+        assert_eq!(
+            Some(Duration::ZERO),
+            next_refresh(None, minutes(1), false, grace)
+        );
+        assert_eq!(
+            Some(minutes(4)),
+            next_refresh(Some(minutes(6)), minutes(1), false, grace)
+        );
+        // Due, but just touched
+        assert_eq!(
+            Some(Duration::from_secs(7)),
+            next_refresh(Some(minutes(20)), Duration::from_secs(3), false, grace)
+        );
+        assert_eq!(
+            None,
+            next_refresh(Some(minutes(20)), minutes(1), true, grace)
+        );
+    }
+
+    #[test]
+    fn movable_games() {
+        assert!(can_move(2));
+        assert!(can_move(3));
+        assert!(!can_move(0));
+    }
+
+    #[test]
+    fn waiting_games() {
+        assert!(waiting_for_move(2, "'PLAY'"));
+        assert!(!waiting_for_move(0, "'PLAY'"));
+        assert!(!waiting_for_move(2, "'SCORE'"));
+        assert!(!waiting_for_move(2, "'FINISHED'"));
+    }
+
+    #[test]
+    fn batch_summaries() {
+        assert_eq!("Played 1 game this batch", batch_summary(1));
+        assert_eq!("Played 4 games this batch", batch_summary(4));
+    }
+
+    #[test]
+    fn short_comment() {
+        assert_eq!(vec!["Good game"], comment_lines("Good game", 20, 3));
+    }
+
+    #[test]
+    fn long_comment() {
+        assert_eq!(
+            vec!["one two", "three..."],
+            comment_lines("one two three four five", 8, 2)
+        );
+    }
+
+    #[test]
+    fn handle_quotes() {
+        assert_eq!("whiterobot", strip_quotes("'whiterobot'"));
+        assert_eq!("whiterobot", strip_quotes("whiterobot"));
+        assert_eq!("", strip_quotes("''"));
+        assert_eq!("", strip_quotes(""));
+        assert_eq!("", strip_quotes("'"));
+    }
+
+    fn move_param(point: (u8, u8), board_size: u8) -> String {
+        let url = dgs_move_url(
+            1,
+            2,
+            Point2 {
+                x: point.0,
+                y: point.1,
+            },
+            board_size,
+        )
+        .unwrap();
+        url.split("&move=").nth(1).unwrap().to_string()
+    }
+
+    #[test]
+    fn move_url() {
+        assert_eq!(
+            Some("https://www.dragongoserver.net/quick_do.php?obj=game&cmd=move&gid=1483922&move_id=17&move=d6".to_string()),
+            dgs_move_url(1483922, 17, Point2 { x: 3, y: 3 }, 9)
+        );
+    }
+
+    #[test]
+    fn move_replies() {
+        assert!(move_accepted(
+            r#"{"version":"1.0.15:3","error":"","quota_count":490}"#
+        ));
+        assert!(!move_accepted(
+            r#"{"version":"1.0.15:3","error":"not_your_turn","error_msg":"Not your turn"}"#
+        ));
+        assert!(!move_accepted("#Error: [invalid_move] Illegal move"));
+        assert!(!move_accepted(""));
+    }
+
+    #[test]
+    fn move_url_corners() {
+        assert_eq!("a9", move_param((0, 0), 9));
+        assert_eq!("j1", move_param((8, 8), 9));
+        assert_eq!("a1", move_param((0, 18), 19));
+        assert_eq!("t19", move_param((18, 0), 19));
+    }
+
+    #[test]
+    fn move_url_skips_i() {
+        assert_eq!("h5", move_param((7, 4), 9));
+        assert_eq!("j5", move_param((8, 4), 9));
+    }
+
+    #[test]
+    fn move_url_flips_y() {
+        assert_eq!("c13", move_param((2, 0), 13));
+        assert_eq!("c1", move_param((2, 12), 13));
+    }
+
+    #[test]
+    fn phases() {
+        assert_eq!(GamePhase::Play, game_phase("'PLAY'"));
+        assert_eq!(GamePhase::Play, game_phase("'PASS'"));
+        assert_eq!(GamePhase::Scoring, game_phase("'SCORE'"));
+        assert_eq!(GamePhase::Scoring, game_phase("'SCORE2'"));
+        assert_eq!(GamePhase::Finished, game_phase("'FINISHED'"));
+    }
+
+    #[test]
+    fn results() {
+        assert_eq!("Black won by resignation", describe_result("B+R"));
+        assert_eq!("White won by 3.5", describe_result("W+3.5"));
+        assert_eq!("White won on time", describe_result("W+T"));
+        assert_eq!("Draw", describe_result("0"));
+        assert_eq!("Result: ?", describe_result("?"));
+    }
+
+    #[test]
+    fn resign_url() {
+        assert_eq!(
+            "https://www.dragongoserver.net/quick_do.php?obj=game&cmd=resign&gid=1483922&move_id=17",
+            game_command_url(1483922, 17, "resign", "")
+        );
+    }
+
+    const QUICK_STATUS: &str = "\
+## G,game_id,opponent_handle,player_color,lastmove_date,time_remaining,game_action,game_status,move_id,tournament_id,shape_id,game_type,game_prio,opponent_lastaccess_date,handicap
+G,1483922,'opponent',B,'2024-05-01T10:00:00','F: 10d 3h (+ 1d)',2,'PLAY',17,0,0,'GO',0,'2024-05-01T11:00:00',0
+G,1483923,'other',W,'2024-05-01T10:00:00','F: 2d (+ 1d)',0,'PLAY',30,0,0,'GO',0,'2024-05-01T11:00:00',0
+";
+
+    #[test]
+    fn games_from_status() {
+        let games = parse_games(QUICK_STATUS).unwrap();
+        assert_eq!(
+            vec![(1483922, 17), (1483923, 30)],
+            games
+                .iter()
+                .map(|game| (game.game_id, game.move_id))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn status_times() {
+        let games = parse_games(QUICK_STATUS).unwrap();
+        assert_eq!(
+            TimeDelta::days(10) + TimeDelta::hours(3),
+            games[0].time_remaining
+        );
+        assert_eq!(TimeDelta::days(2), games[1].time_remaining);
+    }
+
+    #[test]
+    fn unreadable_status_times() {
+        for (from, to) in [
+            ("'F: 2d (+ 1d)'", "'J: 2d 5h (+ 1d 5x)'"),
+            ("'F: 2d (+ 1d)'", "'F: 2d 30m (+ 1d)'"),
+            ("'F: 2d (+ 1d)'", "'F: 2d'"),
+            ("'2024-05-01T10:00:00'", "'yesterday'"),
+        ] {
+            assert!(
+                parse_games(&QUICK_STATUS.replacen(from, to, 1)).is_err(),
+                "{to}"
+            );
+        }
+    }
+
+    #[test]
+    fn latin1_handle() {
+        // Every character's under 256, so each is one byte in Latin-1
+        let status: Vec<u8> = QUICK_STATUS
+            .replace("'other'", "'Jos\u{e9}'")
+            .chars()
+            .map(|c| c as u8)
+            .collect();
+        for content_type in [
+            Some("text/plain; charset=ISO-8859-1"),
+            Some("text/plain"),
+            None,
+        ] {
+            let games = parse_games(&decode_body(&status, content_type)).unwrap();
+            assert_eq!("José", strip_quotes(&games[1].opponent_handle));
+        }
+    }
+
+    #[test]
+    fn utf8_handle() {
+        let status = QUICK_STATUS.replace("'other'", "'Jos\u{e9}'");
+        let games = parse_games(&decode_body(status.as_bytes(), Some("text/plain"))).unwrap();
+        assert_eq!("José", strip_quotes(&games[1].opponent_handle));
+    }
+
+    #[test]
+    fn offline_sample() {
+        let games = parse_games(OFFLINE_SAMPLE_STATUS).unwrap();
+        assert_eq!(1, games.len());
+        assert!(!can_move(games[0].game_action));
+        assert_eq!("OFFLINE SAMPLE", strip_quotes(&games[0].opponent_handle));
+        let game_data = get_game_data(OFFLINE_SAMPLE_SGF).unwrap();
+        assert_eq!(9, game_data.size);
+        assert_eq!(games[0].move_id as usize, game_data.move_count);
+        assert!(game_data.comment.is_some());
+    }
+
+    #[test]
+    fn current_move_id() {
+        let games = parse_games(QUICK_STATUS).unwrap();
+        assert!(move_id_current(&games, 1483922, 17));
+        // The opponent's moved since
+        assert!(!move_id_current(&games, 1483922, 16));
+        // Gone from the list
+        assert!(!move_id_current(&games, 1483924, 17));
+    }
+
+    #[test]
+    fn load_messages() {
+        assert_eq!(None, LoadResult::Loaded.message("fred"));
+        assert_eq!(None, LoadResult::NoGames.message("fred"));
+        assert_eq!(
+            Some("Dragon Go Server didn't accept the login for fred".to_string()),
+            LoadResult::LoginFailed.message("fred")
+        );
+        assert_eq!(
+            Some("Error logging in: timed out".to_string()),
+            LoadResult::NetworkError("Error logging in: timed out".to_string()).message("fred")
+        );
+        assert_eq!(
+            Some("Couldn't load game data".to_string()),
+            LoadResult::ParseError("Couldn't load game data".to_string()).message("fred")
+        );
+    }
+}
@@ -5,10 +5,208 @@ use libremarkable::{
         core::Framebuffer,
         FramebufferDraw, FramebufferRefresh,
     },
+    input::MultitouchEvent,
 };
+use log::info;
+use std::{
+    sync::{Condvar, Mutex, PoisonError},
+    thread::{self, ThreadId},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    board::{Board, Stone},
+    lock,
+    reset::space_before_back,
+    settings::SETTINGS,
+};
+
+/// All layout works from these rather than `libremarkable::dimensions`, so a different
+/// orientation only needs changing here
+pub const SCREEN_WIDTH: u16 = libremarkable::dimensions::DISPLAYWIDTH;
+pub const SCREEN_HEIGHT: u16 = libremarkable::dimensions::DISPLAYHEIGHT;
+
+/// Left edge for something `width` wide to be centred on the screen
+pub const fn centred_x(width: u32) -> i32 {
+    ((SCREEN_WIDTH as u32 - width) / 2) as i32
+}
+
+/// Button and status text size with a `ui_scale` of 1
+pub const TEXT_SIZE: f32 = 100.0;
+// Height of most buttons, which `TEXT_SIZE` text is sized for
+const STANDARD_BUTTON_HEIGHT: f32 = 95.0;
+const STATUS_HEIGHT: u32 = 100;
+/// For `show_game_info`, which fits between the top buttons and the board
+const GAME_INFO_HEIGHT: u32 = 50;
+const STATUS_NOTE_GAP: i32 = 20;
+// Enough for "Turn 100" at half the normal text size
+const STATUS_NOTE_MIN_WIDTH: u32 = 200;
+// Roughly how wide a character is as a fraction of the text size. Errs wide, as clearing a bit
+// too much is better than clipping the end off
+const CHAR_WIDTH: f32 = 0.6;
+// Enough for "10:00 for 25" at half the normal text size
+const HUD_MIN_WIDTH: u32 = 300;
+// Fill for buttons being held down, light enough that the text still shows
+const HOLD_GREY: u8 = 100;
+// Fraction of the hold between redraws of the fill, as each one is a refresh
+const HOLD_STEP: f32 = 0.2;
+
+fn scale_by(value: u32, scale: f32) -> u32 {
+    (value as f32 * scale).round() as u32
+}
+
+/// `value` pixels, adjusted by the `ui_scale` setting
+pub fn scaled(value: u32) -> u32 {
+    scale_by(value, SETTINGS.ui_scale())
+}
+
+/// Button size adjusted by the `ui_scale` setting, but never wider than the screen
+pub fn scaled_size(size: Vector2<u32>) -> Vector2<u32> {
+    Vector2 {
+        x: scaled(size.x).min(SCREEN_WIDTH as u32),
+        y: scaled(size.y),
+    }
+}
+
+pub fn text_size() -> f32 {
+    TEXT_SIZE * SETTINGS.ui_scale()
+}
+
+/// Roughly how wide `text` is at `size`, going by its longest line
+fn text_width(text: &str, size: f32) -> u32 {
+    let longest = text.lines().map(|line| line.chars().count()).max();
+    (longest.unwrap_or(0) as f32 * size * CHAR_WIDTH).round() as u32
+}
+
+/// Each line of a status gets an even share of the bar, so more lines means smaller text
+fn status_line_size(text: &str, size: f32) -> f32 {
+    size / text.lines().count().max(1) as f32
+}
+
+/// How much of a status bar to clear and refresh for `text` at `size`, but no more than `max`
+fn status_text_width(text: &str, size: f32, max: u32) -> u32 {
+    text_width(text, status_line_size(text, size)).min(max)
+}
+
+/// Moves `top_left` back so something `size` big doesn't go off the right or bottom of the screen
+pub fn fit_on_screen(top_left: Point2<i32>, size: Vector2<u32>) -> Point2<i32> {
+    Point2 {
+        x: top_left.x.min(SCREEN_WIDTH as i32 - size.x as i32).max(0),
+        y: top_left.y.min(SCREEN_HEIGHT as i32 - size.y as i32).max(0),
+    }
+}
+
+/// Full-screen refreshes that have been asked for but not done yet. Several code paths often
+/// ask for one in response to the same touch, and doing each of them just makes the screen flash
+/// more, so they wait for `flush_refresh` and happen once. Also keeps track of partial refreshes
+/// that have been submitted but not waited for, so they can't overlap with ones from another
+/// thread
+#[derive(Default)]
+struct RefreshQueue {
+    full_pending: bool,
+    in_flight: usize,
+    /// The thread with refreshes in flight, which can start more alongside them
+    owner: Option<ThreadId>,
+}
+
+impl RefreshQueue {
+    fn request_full(&mut self) {
+        self.full_pending = true;
+    }
+
+    /// Whether a full refresh needs doing, which counts as doing it
+    fn take_full(&mut self) -> bool {
+        std::mem::take(&mut self.full_pending)
+    }
 
+    fn can_start(&self, thread: ThreadId) -> bool {
+        self.in_flight == 0 || self.owner == Some(thread)
+    }
+
+    fn start(&mut self, thread: ThreadId) {
+        self.in_flight += 1;
+        self.owner = Some(thread);
+    }
+
+    fn finish(&mut self) {
+        self.in_flight = self.in_flight.saturating_sub(1);
+        if self.in_flight == 0 {
+            self.owner = None;
+        }
+    }
+}
+
+// Also held during every full refresh, and refreshes from another thread wait on REFRESH_DONE
+// until the ones in flight are done, so they can't overlap between threads
+static REFRESH_QUEUE: Mutex<RefreshQueue> = Mutex::new(RefreshQueue {
+    full_pending: false,
+    in_flight: 0,
+    owner: None,
+});
+static REFRESH_DONE: Condvar = Condvar::new();
+
+/// A refresh from `submit_refresh` that's still going. Other threads can't refresh until it's
+/// been through `wait_refresh` (or dropped)
+#[must_use]
+pub struct PendingRefresh {
+    marker: u32,
+}
+
+impl Drop for PendingRefresh {
+    fn drop(&mut self) {
+        lock(&REFRESH_QUEUE).finish();
+        REFRESH_DONE.notify_all();
+    }
+}
+
+/// The queue once any other thread's refreshes are done
+fn queue_for_this_thread() -> std::sync::MutexGuard<'static, RefreshQueue> {
+    let thread = thread::current().id();
+    REFRESH_DONE
+        .wait_while(lock(&REFRESH_QUEUE), |queue| !queue.can_start(thread))
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+const FULL_SCREEN: mxcfb_rect = mxcfb_rect {
+    top: 0,
+    left: 0,
+    width: SCREEN_WIDTH as u32,
+    height: SCREEN_HEIGHT as u32,
+};
+
+/// Refreshes part of the screen now. If a full refresh is waiting it gets done instead, as that
+/// covers this region too
 pub fn refresh_with_options(fb: &Framebuffer, region: &mxcfb_rect, waveform: waveform_mode) {
-    let marker = fb.partial_refresh(
+    let marker = submit_refresh(fb, region, waveform);
+    wait_refresh(fb, marker);
+}
+
+/// Starts the same refresh as `refresh_with_options`, but doesn't wait for it to finish, so
+/// several small updates from this thread can go at once. Pass it to `wait_refresh` afterwards
+pub fn submit_refresh(
+    fb: &Framebuffer,
+    region: &mxcfb_rect,
+    waveform: waveform_mode,
+) -> PendingRefresh {
+    let mut queue = queue_for_this_thread();
+    queue.start(thread::current().id());
+    let marker = if queue.take_full() {
+        info!("Doing pending full refresh instead of partial one");
+        send_refresh(fb, &FULL_SCREEN, waveform_mode::WAVEFORM_MODE_AUTO)
+    } else {
+        send_refresh(fb, region, waveform)
+    };
+    PendingRefresh { marker }
+}
+
+/// Waits for a refresh from `submit_refresh` to get to the screen, after which other threads
+/// can refresh again
+pub fn wait_refresh(fb: &Framebuffer, pending: PendingRefresh) {
+    fb.wait_refresh_complete(pending.marker);
+}
+
+fn send_refresh(fb: &Framebuffer, region: &mxcfb_rect, waveform: waveform_mode) -> u32 {
+    fb.partial_refresh(
         region,
         libremarkable::framebuffer::PartialRefreshMode::Async,
         waveform,
@@ -16,37 +214,561 @@ pub fn refresh_with_options(fb: &Framebuffer, region: &mxcfb_rect, waveform: wav
         dither_mode::EPDC_FLAG_EXP1,
         0,
         false,
-    );
-    fb.wait_refresh_complete(marker);
+    )
 }
 
-pub fn refresh(fb: &Framebuffer) {
-    refresh_with_options(
-        fb,
-        &mxcfb_rect {
-            top: 0,
-            left: 0,
-            width: libremarkable::dimensions::DISPLAYWIDTH as u32,
-            height: libremarkable::dimensions::DISPLAYHEIGHT as u32,
-        },
-        waveform_mode::WAVEFORM_MODE_AUTO,
-    );
+/// Black/white only, but much quicker and less flashy, so good for small updates like one stone
+pub fn refresh_fast(fb: &Framebuffer, region: &mxcfb_rect) {
+    let start = Instant::now();
+    refresh_with_options(fb, region, waveform_mode::WAVEFORM_MODE_DU);
+    info!("fast refresh elapsed: {:.2?}", start.elapsed());
+}
+
+/// Asks for the whole screen to be refreshed, which happens at the next `flush_refresh`
+pub fn refresh(_fb: &Framebuffer) {
+    lock(&REFRESH_QUEUE).request_full();
+}
+
+/// Does the full refresh, if one's been asked for since the last one. Called after each touch
+/// event and update, so routines don't need to
+pub fn flush_refresh(fb: &Framebuffer) {
+    let mut queue = queue_for_this_thread();
+    if queue.take_full() {
+        let start = Instant::now();
+        let marker = send_refresh(fb, &FULL_SCREEN, waveform_mode::WAVEFORM_MODE_AUTO);
+        fb.wait_refresh_complete(marker);
+        info!("full refresh elapsed: {:.2?}", start.elapsed());
+    }
+}
+
+/// Is `point` inside the rectangle? Top/left edges are inside, bottom/right edges aren't
+pub fn hit(point: Point2<u16>, top_left: Point2<i32>, size: Vector2<u32>) -> bool {
+    (point.x as i32) >= top_left.x
+        && (point.x as i32) < (top_left.x + size.x as i32)
+        && (point.y as i32) >= top_left.y
+        && (point.y as i32) < (top_left.y + size.y as i32)
+}
+
+/// A labelled rectangle, with `action` saying what it does when pressed
+pub struct Button<A> {
+    pub text: String,
+    pub top_left: Point2<i32>,
+    pub size: Vector2<u32>,
+    pub action: A,
+    /// Has to be held down for the `hold_ms` setting, via `HoldTracker`, rather than just tapped
+    pub hold: bool,
+}
+
+impl<A> Button<A> {
+    pub fn new(text: &str, top_left: Point2<i32>, size: Vector2<u32>, action: A) -> Button<A> {
+        Button {
+            text: text.to_string(),
+            top_left: fit_on_screen(top_left, size),
+            size,
+            action,
+            hold: false,
+        }
+    }
+
+    /// For buttons that lose a game or quit, so a stray tap can't set them off
+    pub fn held(mut self) -> Button<A> {
+        self.hold = true;
+        self
+    }
+
+    pub fn draw(&self, fb: &mut Framebuffer) {
+        draw_button(fb, &self.text, self.top_left, self.size);
+    }
+
+    fn region(&self) -> mxcfb_rect {
+        mxcfb_rect {
+            top: self.top_left.y as u32,
+            left: self.top_left.x as u32,
+            width: self.size.x,
+            height: self.size.y,
+        }
+    }
+
+    /// Redraws it on its own, filled from the left by `progress` (0 to 1) of the way
+    pub fn draw_progress(&self, fb: &mut Framebuffer, progress: f32) {
+        fb.fill_rect(self.top_left, self.size, color::WHITE);
+        let width = (self.size.x as f32 * progress.clamp(0.0, 1.0)) as u32;
+        if width > 0 {
+            fb.fill_rect(
+                self.top_left,
+                Vector2 {
+                    x: width,
+                    y: self.size.y,
+                },
+                color::GRAY(HOLD_GREY),
+            );
+        }
+        self.draw(fb);
+        refresh_with_options(fb, &self.region(), waveform_mode::WAVEFORM_MODE_AUTO);
+    }
+
+    /// Clears its space, draws it there again if `shown`, and refreshes just that space, for
+    /// when a button comes or goes and nothing else on screen has changed
+    pub fn refresh_shown(&self, fb: &mut Framebuffer, shown: bool) {
+        fb.fill_rect(self.top_left, self.size, color::WHITE);
+        if shown {
+            self.draw(fb);
+        }
+        refresh_with_options(fb, &self.region(), waveform_mode::WAVEFORM_MODE_AUTO);
+    }
+
+    /// Whether `point` is on it or within the `touch_padding` setting of it. Where that could
+    /// mean more than one button, `pressed` picks between them
+    pub fn contains(&self, point: Point2<u16>) -> bool {
+        self.contains_padded(point, SETTINGS.touch_padding())
+    }
+
+    fn contains_padded(&self, point: Point2<u16>, padding: u32) -> bool {
+        let top_left = Point2 {
+            x: self.top_left.x - padding as i32,
+            y: self.top_left.y - padding as i32,
+        };
+        let size = Vector2 {
+            x: self.size.x + 2 * padding,
+            y: self.size.y + 2 * padding,
+        };
+        hit(point, top_left, size)
+    }
+
+    /// Squared, as it's only for comparing
+    fn distance_to_middle(&self, point: Point2<u16>) -> i64 {
+        let dx = 2 * point.x as i64 - (2 * self.top_left.x as i64 + self.size.x as i64);
+        let dy = 2 * point.y as i64 - (2 * self.top_left.y as i64 + self.size.y as i64);
+        dx * dx + dy * dy
+    }
+}
+
+/// Which of `buttons` (as an index) a press at `point` is for. A button actually under it wins,
+/// and otherwise it's the nearest middle out of those whose padding reaches it, so padding on
+/// buttons next to each other can't make the wrong one go off
+fn nearest_pressed<'a, A: 'a>(
+    buttons: impl IntoIterator<Item = &'a Button<A>>,
+    point: Point2<u16>,
+    padding: u32,
+) -> Option<usize> {
+    buttons
+        .into_iter()
+        .enumerate()
+        .filter(|(_, button)| button.contains_padded(point, padding))
+        .min_by_key(|(_, button)| {
+            (
+                !button.contains_padded(point, 0),
+                button.distance_to_middle(point),
+            )
+        })
+        .map(|(index, _)| index)
+}
+
+/// `nearest_pressed` with the `touch_padding` setting, for buttons that aren't in one list
+pub fn pressed_index<'a, A: 'a>(
+    buttons: impl IntoIterator<Item = &'a Button<A>>,
+    point: Point2<u16>,
+) -> Option<usize> {
+    nearest_pressed(buttons, point, SETTINGS.touch_padding())
+}
+
+/// The button a press at `point` is for, if any. Use this rather than the first one that
+/// `contains` it, so padding picks the nearest
+pub fn pressed<A>(buttons: &[Button<A>], point: Point2<u16>) -> Option<&Button<A>> {
+    pressed_index(buttons, point).map(|index| &buttons[index])
+}
+
+/// What a touch did to the button being held down, if it was about one
+#[derive(Debug, PartialEq)]
+enum Hold<A> {
+    Ignored,
+    /// Index into the buttons, and how far through the hold it is
+    Progress(usize, f32),
+    /// Let go or slid off too soon, so it needs drawing without the fill
+    Cancelled(usize),
+    Fired(A),
+}
+
+/// What a routine should do with a touch after `HoldTracker::handle`
+#[derive(Debug, PartialEq)]
+pub enum Held<A> {
+    /// Nothing to do with held buttons, so handle it as usual
+    Ignored,
+    /// Used up by a button being held down
+    Holding,
+    /// Held long enough, so do what the button does
+    Fired(A),
+}
+
+struct HoldPress {
+    index: usize,
+    tracking_id: i32,
+    start: Instant,
+    /// How much of the fill's been drawn, so it's only refreshed every `HOLD_STEP`
+    drawn: f32,
+}
+
+/// Follows a finger held down on one of the `Button::held` buttons, until it's been there for
+/// long enough to fire. Routines pass it every touch along with the buttons on screen
+#[derive(Default)]
+pub struct HoldTracker {
+    press: Option<HoldPress>,
+}
+
+impl HoldTracker {
+    fn track<A: Clone>(
+        &mut self,
+        buttons: &[Button<A>],
+        event: &MultitouchEvent,
+        now: Instant,
+        hold: Duration,
+    ) -> Hold<A> {
+        let (finger, released) = match event {
+            MultitouchEvent::Press { finger } => {
+                let Some(index) =
+                    pressed_index(buttons, finger.pos).filter(|index| buttons[*index].hold)
+                else {
+                    return Hold::Ignored;
+                };
+                if hold.is_zero() {
+                    return Hold::Fired(buttons[index].action.clone());
+                }
+                self.press = Some(HoldPress {
+                    index,
+                    tracking_id: finger.tracking_id,
+                    start: now,
+                    drawn: 0.0,
+                });
+                return Hold::Progress(index, 0.0);
+            }
+            MultitouchEvent::Move { finger } => (finger, false),
+            MultitouchEvent::Release { finger } => (finger, true),
+            _ => return Hold::Ignored,
+        };
+        let Some(ref mut press) = self.press else {
+            return Hold::Ignored;
+        };
+        if press.tracking_id != finger.tracking_id {
+            return Hold::Ignored;
+        }
+        let index = press.index;
+        let Some(button) = buttons.get(index) else {
+            // The buttons have changed under the finger, so it's not holding anything now
+            self.press = None;
+            return Hold::Ignored;
+        };
+        let progress = now.duration_since(press.start).as_secs_f32() / hold.as_secs_f32();
+        if !button.contains(finger.pos) {
+            self.press = None;
+            return Hold::Cancelled(index);
+        }
+        if progress >= 1.0 {
+            self.press = None;
+            return Hold::Fired(button.action.clone());
+        }
+        if released {
+            self.press = None;
+            return Hold::Cancelled(index);
+        }
+        if progress - press.drawn >= HOLD_STEP {
+            press.drawn = progress;
+            return Hold::Progress(index, progress);
+        }
+        Hold::Progress(index, press.drawn)
+    }
+
+    /// Draws the fill as the button's held, and takes it away again if the finger lets go early
+    pub fn handle<A: Clone>(
+        &mut self,
+        fb: &mut Framebuffer,
+        buttons: &[Button<A>],
+        event: &MultitouchEvent,
+    ) -> Held<A> {
+        let drawn = self.press.as_ref().map(|press| press.drawn);
+        match self.track(buttons, event, Instant::now(), SETTINGS.hold_time()) {
+            Hold::Ignored => Held::Ignored,
+            Hold::Progress(index, progress) => {
+                if drawn != Some(progress) {
+                    buttons[index].draw_progress(fb, progress);
+                }
+                Held::Holding
+            }
+            Hold::Cancelled(index) => {
+                info!("Let go of {} too soon", buttons[index].text);
+                buttons[index].draw_progress(fb, 0.0);
+                Held::Holding
+            }
+            Hold::Fired(action) => Held::Fired(action),
+        }
+    }
 }
 
 pub fn draw_button(fb: &mut Framebuffer, text: &str, top_left: Point2<i32>, size: Vector2<u32>) {
+    // Shrink the text on buttons that haven't been scaled up, so it stays inside them
+    let text_size = text_size().min(size.y as f32 * TEXT_SIZE / STANDARD_BUTTON_HEIGHT);
     fb.draw_rect(top_left, size, 5, color::BLACK);
     fb.draw_text(
         Point2 {
             x: (top_left.x + 5) as f32,
-            y: (top_left.y + 80) as f32,
+            y: top_left.y as f32 + text_size * 0.8,
         },
         text,
-        100.0,
+        text_size,
         color::BLACK,
         false,
     );
 }
 
+/// Smaller text to the right of a mode's status, e.g. the move count. `left` is where the status
+/// area ends, and it's skipped if there's not enough room before the back button. Lines stack
+/// upwards from the bottom of the status area
+pub fn draw_status_note(fb: &mut Framebuffer, left: i32, height: u32, text: &str, refresh: bool) {
+    let left = left + STATUS_NOTE_GAP;
+    let width = space_before_back(left);
+    if width < scaled(STATUS_NOTE_MIN_WIDTH) {
+        return;
+    }
+    let region = mxcfb_rect {
+        top: 0,
+        left: left as u32,
+        width,
+        height,
+    };
+    fb.fill_rect(
+        Point2 { x: left, y: 0 },
+        Vector2 {
+            x: width,
+            y: height,
+        },
+        color::WHITE,
+    );
+    let size = text_size() / 2.0;
+    for (index, line) in text.lines().rev().enumerate() {
+        fb.draw_text(
+            Point2 {
+                x: left as f32,
+                y: height as f32 - size * index as f32,
+            },
+            line,
+            size,
+            color::BLACK,
+            false,
+        );
+    }
+    if refresh {
+        refresh_with_options(fb, &region, waveform_mode::WAVEFORM_MODE_AUTO);
+    }
+}
+
+/// The status along the top of a mode's screen, e.g. whose turn it is. Drawing it only clears
+/// and refreshes as much as the text needs, so whatever's to the right of it is left alone
+pub struct StatusBar {
+    left: i32,
+    top: u32,
+    max_width: u32,
+    height: u32,
+    text_size: f32,
+}
+
+impl StatusBar {
+    /// From `left` up to the back button
+    pub fn new(left: i32) -> StatusBar {
+        StatusBar {
+            left,
+            top: 0,
+            max_width: space_before_back(left),
+            height: scaled(STATUS_HEIGHT),
+            text_size: text_size(),
+        }
+    }
+
+    /// A line of smaller text just over the stones of `board`, as wide as the board
+    pub fn above_board(board: &Board) -> StatusBar {
+        let height = scaled(GAME_INFO_HEIGHT);
+        StatusBar {
+            left: board.spare_width as i32,
+            top: board.top().saturating_sub(height),
+            max_width: board.grid_size() as u32,
+            height,
+            text_size: text_size() / 2.0,
+        }
+    }
+
+    /// Moves it `top` down the screen, e.g. for a second row of status
+    pub fn lowered(mut self, top: u32) -> StatusBar {
+        self.top = top;
+        self
+    }
+
+    /// Stops it short of `max`, e.g. to keep clear of buttons on the same row
+    pub fn capped(mut self, max: u32) -> StatusBar {
+        self.max_width = self.max_width.min(max);
+        self
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn width(&self, text: &str) -> u32 {
+        status_text_width(text, self.text_size, self.max_width)
+    }
+
+    /// Where anything after `text` can start, e.g. with `draw_status_note`
+    pub fn right(&self, text: &str) -> i32 {
+        self.left + self.width(text) as i32
+    }
+
+    /// Clears the space for `text` and draws it, one line under another if there's more than
+    /// one. With `refresh`, just that space gets refreshed
+    pub fn draw(&self, fb: &mut Framebuffer, text: &str, refresh: bool) {
+        let width = self.width(text);
+        let height = self.height;
+        fb.fill_rect(
+            Point2 {
+                x: self.left,
+                y: self.top as i32,
+            },
+            Vector2 {
+                x: width,
+                y: height,
+            },
+            color::WHITE,
+        );
+        let size = status_line_size(text, self.text_size);
+        for (index, line) in text.lines().rev().enumerate() {
+            fb.draw_text(
+                Point2 {
+                    x: self.left as f32,
+                    y: (self.top + height) as f32 - size * index as f32,
+                },
+                line,
+                size,
+                color::BLACK,
+                false,
+            );
+        }
+        if refresh {
+            refresh_with_options(
+                fb,
+                &mxcfb_rect {
+                    top: self.top,
+                    left: self.left as u32,
+                    width,
+                    height,
+                },
+                waveform_mode::WAVEFORM_MODE_AUTO,
+            );
+        }
+    }
+}
+
+/// The game's settings and whose turn it is, for `show_game_info`
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameInfo {
+    pub size: u8,
+    pub komi: f64,
+    pub handicap: u8,
+    /// `None` once the game's over
+    pub to_play: Option<Stone>,
+}
+
+impl GameInfo {
+    pub fn text(&self) -> String {
+        let mut parts = vec![format!("{0}x{0}", self.size), format!("Komi {}", self.komi)];
+        if self.handicap > 0 {
+            parts.push(format!("Handicap {}", self.handicap));
+        }
+        if let Some(to_play) = self.to_play {
+            parts.push(format!("{to_play:?} to play"));
+        }
+        parts.join(", ")
+    }
+
+    /// Over `board`, if `show_game_info` is on
+    pub fn draw(&self, fb: &mut Framebuffer, board: &Board, refresh: bool) {
+        if SETTINGS.show_game_info {
+            StatusBar::above_board(board).draw(fb, &self.text(), refresh);
+        }
+    }
+}
+
+/// A small box of text that changes often, like a clock. Updates only redraw the box, with a
+/// quick black and white refresh, so they don't need the rest of the screen redrawn
+pub struct Hud {
+    top_left: Point2<i32>,
+    size: Vector2<u32>,
+}
+
+impl Hud {
+    /// `None` if it'd be too narrow for the text
+    pub fn new(top_left: Point2<i32>, size: Vector2<u32>) -> Option<Hud> {
+        if size.x < scaled(HUD_MIN_WIDTH) {
+            return None;
+        }
+        Some(Hud { top_left, size })
+    }
+
+    pub fn region(&self) -> mxcfb_rect {
+        mxcfb_rect {
+            top: self.top_left.y as u32,
+            left: self.top_left.x as u32,
+            width: self.size.x,
+            height: self.size.y,
+        }
+    }
+
+    /// Just draws it, for when the rest of the screen's being redrawn and refreshed too
+    pub fn draw(&self, fb: &mut Framebuffer, text: &str) {
+        fb.fill_rect(self.top_left, self.size, color::WHITE);
+        let size = text_size() / 2.0;
+        fb.draw_text(
+            Point2 {
+                x: self.top_left.x as f32,
+                y: self.top_left.y as f32 + (self.size.y as f32 + size) / 2.0,
+            },
+            text,
+            size,
+            color::BLACK,
+            false,
+        );
+    }
+
+    pub fn update(&self, fb: &mut Framebuffer, text: &str) {
+        self.draw(fb, text);
+        refresh_fast(fb, &self.region());
+    }
+}
+
+/// Breaks `text` into lines of at most `width` characters, at spaces where it can and keeping
+/// any line breaks it already has
+pub fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = vec![];
+    for paragraph in text.lines() {
+        let mut current = String::new();
+        for word in paragraph.split_whitespace() {
+            let current_len = current.chars().count();
+            let word_len = word.chars().count();
+            if current_len > 0 && current_len + 1 + word_len <= width {
+                current.push(' ');
+                current.push_str(word);
+                continue;
+            }
+            if current_len > 0 {
+                lines.push(current);
+            }
+            // Anything too long for a line on its own gets split wherever it has to be
+            let chars: Vec<char> = word.chars().collect();
+            let mut chunks = chars.chunks(width).map(|c| c.iter().collect::<String>());
+            current = chunks.next_back().unwrap_or_default();
+            lines.extend(chunks);
+        }
+        lines.push(current);
+    }
+    lines
+}
+
 pub fn draw_multiline_text(
     fb: &mut Framebuffer,
     pos: Point2<f32>,
@@ -54,20 +776,343 @@ pub fn draw_multiline_text(
     size: f32,
     split_point: usize,
 ) {
-    if text.len() < split_point {
-        fb.draw_text(pos, text, size, color::BLACK, false);
-    } else {
-        let (first, second) = text.split_at(split_point);
-        fb.draw_text(pos, first, size, color::BLACK, false);
-        draw_multiline_text(
-            fb,
+    for (index, line) in wrap_text(text, split_point).iter().enumerate() {
+        fb.draw_text(
             Point2 {
                 x: pos.x,
-                y: pos.y + size,
+                y: pos.y + size * index as f32,
             },
-            second,
+            line,
             size,
-            split_point,
+            color::BLACK,
+            false,
         );
     }
 }
+
+#[cfg(test)]
+mod test {
+    use libremarkable::{
+        cgmath::{Point2, Vector2},
+        input::{Finger, MultitouchEvent},
+    };
+    use pretty_assertions::assert_eq;
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use crate::{
+        board::Stone,
+        drawing::{
+            fit_on_screen, hit, nearest_pressed, scale_by, status_text_width, text_width,
+            wrap_text, Button, GameInfo, Hold, HoldTracker, RefreshQueue, SCREEN_HEIGHT,
+            SCREEN_WIDTH,
+        },
+    };
+
+    const TOP_LEFT: Point2<i32> = Point2 { x: 100, y: 200 };
+    const SIZE: Vector2<u32> = Vector2 { x: 50, y: 20 };
+
+    #[test]
+    fn hit_inside() {
+        assert!(hit(Point2 { x: 120, y: 210 }, TOP_LEFT, SIZE));
+    }
+
+    #[test]
+    fn hit_top_left_edges_inclusive() {
+        assert!(hit(Point2 { x: 100, y: 200 }, TOP_LEFT, SIZE));
+        assert!(hit(Point2 { x: 100, y: 219 }, TOP_LEFT, SIZE));
+        assert!(hit(Point2 { x: 149, y: 200 }, TOP_LEFT, SIZE));
+    }
+
+    #[test]
+    fn hit_bottom_right_edges_exclusive() {
+        assert!(!hit(Point2 { x: 150, y: 210 }, TOP_LEFT, SIZE));
+        assert!(!hit(Point2 { x: 120, y: 220 }, TOP_LEFT, SIZE));
+        assert!(!hit(Point2 { x: 150, y: 220 }, TOP_LEFT, SIZE));
+    }
+
+    #[test]
+    fn hit_outside() {
+        assert!(!hit(Point2 { x: 99, y: 210 }, TOP_LEFT, SIZE));
+        assert!(!hit(Point2 { x: 120, y: 199 }, TOP_LEFT, SIZE));
+        assert!(!hit(Point2 { x: 0, y: 0 }, TOP_LEFT, SIZE));
+    }
+
+    #[test]
+    fn scale_rounds() {
+        assert_eq!(95, scale_by(95, 1.0));
+        assert_eq!(143, scale_by(95, 1.5));
+        assert_eq!(71, scale_by(95, 0.75));
+    }
+
+    #[test]
+    fn text_widths() {
+        assert_eq!(0, text_width("", 100.0));
+        assert_eq!(600, text_width("Black turn", 100.0));
+        assert_eq!(300, text_width("Black turn", 50.0));
+        // Only the longest line counts
+        assert_eq!(360, text_width("Turn 1\nTaken: B 0", 60.0));
+    }
+
+    #[test]
+    fn status_text_widths() {
+        assert_eq!(600, status_text_width("Black turn", 100.0, 1000));
+        assert_eq!(500, status_text_width("Black turn", 100.0, 500));
+        // Two lines share the height, so they're drawn at half size
+        assert_eq!(
+            450,
+            status_text_width("Machine passed\nPass or play on", 100.0, 1000)
+        );
+        assert_eq!(0, status_text_width("", 100.0, 1000));
+    }
+
+    #[test]
+    fn game_info_text() {
+        let mut info = GameInfo {
+            size: 9,
+            komi: 6.5,
+            handicap: 0,
+            to_play: Some(Stone::Black),
+        };
+        assert_eq!("9x9, Komi 6.5, Black to play", info.text());
+        info.handicap = 3;
+        info.komi = 0.5;
+        info.to_play = None;
+        assert_eq!("9x9, Komi 0.5, Handicap 3", info.text());
+    }
+
+    #[test]
+    fn fit_on_screen_leaves_visible_alone() {
+        assert_eq!(TOP_LEFT, fit_on_screen(TOP_LEFT, SIZE));
+    }
+
+    #[test]
+    fn fit_on_screen_pulls_back() {
+        let top_left = Point2 {
+            x: SCREEN_WIDTH as i32 - 10,
+            y: SCREEN_HEIGHT as i32 - 5,
+        };
+        assert_eq!(
+            Point2 {
+                x: SCREEN_WIDTH as i32 - 50,
+                y: SCREEN_HEIGHT as i32 - 20,
+            },
+            fit_on_screen(top_left, SIZE)
+        );
+    }
+
+    #[test]
+    fn full_refreshes_coalesce() {
+        let mut queue = RefreshQueue::default();
+        assert!(!queue.take_full());
+        queue.request_full();
+        queue.request_full();
+        assert!(queue.take_full());
+        assert!(!queue.take_full());
+    }
+
+    #[test]
+    fn refreshes_wait_for_other_threads() {
+        let mut queue = RefreshQueue::default();
+        let this = thread::current().id();
+        let other = thread::spawn(|| thread::current().id()).join().unwrap();
+        assert!(queue.can_start(other));
+        queue.start(this);
+        queue.start(this);
+        // Several from one thread can go at once, but not alongside another's
+        assert!(queue.can_start(this));
+        assert!(!queue.can_start(other));
+        queue.finish();
+        assert!(!queue.can_start(other));
+        queue.finish();
+        assert!(queue.can_start(other));
+    }
+
+    #[test]
+    fn button_contains() {
+        let button = Button::new("Test", TOP_LEFT, SIZE, ());
+        assert!(button.contains(Point2 { x: 100, y: 200 }));
+        assert!(!button.contains(Point2 { x: 150, y: 220 }));
+    }
+
+    #[test]
+    fn padding_reaches_past_the_edges() {
+        let button = Button::new("Test", TOP_LEFT, SIZE, ());
+        assert!(button.contains_padded(Point2 { x: 90, y: 190 }, 10));
+        assert!(button.contains_padded(Point2 { x: 159, y: 229 }, 10));
+        assert!(!button.contains_padded(Point2 { x: 160, y: 210 }, 10));
+        assert!(!button.contains_padded(Point2 { x: 90, y: 190 }, 0));
+    }
+
+    #[test]
+    fn nearest_button_wins() {
+        // 10 apart, so 20 padding overlaps
+        let buttons = [
+            Button::new("Left", TOP_LEFT, SIZE, "left"),
+            Button::new("Right", Point2 { x: 160, y: 200 }, SIZE, "right"),
+        ];
+        let pressed = |x| nearest_pressed(&buttons, Point2 { x, y: 210 }, 20);
+        assert_eq!(Some(0), pressed(152));
+        assert_eq!(Some(1), pressed(158));
+        // On the right one, even though the left one's middle is no further
+        assert_eq!(Some(1), pressed(160));
+        assert_eq!(Some(1), pressed(229));
+        assert_eq!(None, pressed(230));
+        assert_eq!(
+            None,
+            nearest_pressed(&buttons, Point2 { x: 152, y: 210 }, 0)
+        );
+    }
+
+    const HOLD: Duration = Duration::from_secs(1);
+
+    fn finger(tracking_id: i32, x: u16, y: u16) -> Finger {
+        let mut finger = Finger::default();
+        finger.tracking_id = tracking_id;
+        finger.pos = Point2 { x, y };
+        finger
+    }
+
+    fn hold_buttons() -> Vec<Button<&'static str>> {
+        vec![
+            Button::new("Tap", Point2 { x: 100, y: 100 }, SIZE, "tap"),
+            Button::new("Hold", TOP_LEFT, SIZE, "hold").held(),
+        ]
+    }
+
+    #[test]
+    fn hold_fires_after_time() {
+        let buttons = hold_buttons();
+        let mut tracker = HoldTracker::default();
+        let start = Instant::now();
+        let press = MultitouchEvent::Press {
+            finger: finger(1, 110, 210),
+        };
+        assert_eq!(
+            Hold::Progress(1, 0.0),
+            tracker.track(&buttons, &press, start, HOLD)
+        );
+        let moved = MultitouchEvent::Move {
+            finger: finger(1, 112, 211),
+        };
+        assert_eq!(
+            Hold::Progress(1, 0.5),
+            tracker.track(&buttons, &moved, start + HOLD / 2, HOLD)
+        );
+        // Not far enough on to be worth drawing again
+        assert_eq!(
+            Hold::Progress(1, 0.5),
+            tracker.track(&buttons, &moved, start + HOLD * 6 / 10, HOLD)
+        );
+        let release = MultitouchEvent::Release {
+            finger: finger(1, 112, 211),
+        };
+        assert_eq!(
+            Hold::Fired("hold"),
+            tracker.track(&buttons, &release, start + HOLD, HOLD)
+        );
+        assert_eq!(
+            Hold::Ignored,
+            tracker.track(&buttons, &release, start + HOLD, HOLD)
+        );
+    }
+
+    #[test]
+    fn hold_released_early() {
+        let buttons = hold_buttons();
+        let mut tracker = HoldTracker::default();
+        let start = Instant::now();
+        let press = MultitouchEvent::Press {
+            finger: finger(1, 110, 210),
+        };
+        tracker.track(&buttons, &press, start, HOLD);
+        let release = MultitouchEvent::Release {
+            finger: finger(1, 110, 210),
+        };
+        assert_eq!(
+            Hold::Cancelled(1),
+            tracker.track(&buttons, &release, start + HOLD / 2, HOLD)
+        );
+    }
+
+    #[test]
+    fn hold_slid_off() {
+        let buttons = hold_buttons();
+        let mut tracker = HoldTracker::default();
+        let start = Instant::now();
+        let press = MultitouchEvent::Press {
+            finger: finger(1, 110, 210),
+        };
+        tracker.track(&buttons, &press, start, HOLD);
+        // Another finger doesn't count
+        let other = MultitouchEvent::Move {
+            finger: finger(2, 0, 0),
+        };
+        assert_eq!(
+            Hold::Ignored,
+            tracker.track(&buttons, &other, start + HOLD / 2, HOLD)
+        );
+        let moved = MultitouchEvent::Move {
+            finger: finger(1, 0, 0),
+        };
+        assert_eq!(
+            Hold::Cancelled(1),
+            tracker.track(&buttons, &moved, start + HOLD * 2, HOLD)
+        );
+    }
+
+    #[test]
+    fn hold_ignores_tap_buttons() {
+        let buttons = hold_buttons();
+        let mut tracker = HoldTracker::default();
+        let press = MultitouchEvent::Press {
+            finger: finger(1, 110, 110),
+        };
+        assert_eq!(
+            Hold::Ignored,
+            tracker.track(&buttons, &press, Instant::now(), HOLD)
+        );
+    }
+
+    #[test]
+    fn no_hold_time() {
+        let buttons = hold_buttons();
+        let mut tracker = HoldTracker::default();
+        let press = MultitouchEvent::Press {
+            finger: finger(1, 110, 210),
+        };
+        assert_eq!(
+            Hold::Fired("hold"),
+            tracker.track(&buttons, &press, Instant::now(), Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn wrap_at_spaces() {
+        assert_eq!(
+            vec!["the quick", "brown fox"],
+            wrap_text("the quick brown fox", 10)
+        );
+    }
+
+    #[test]
+    fn wrap_long_word() {
+        assert_eq!(vec!["abcd", "efgh", "ij k"], wrap_text("abcdefghij k", 4));
+    }
+
+    #[test]
+    fn wrap_keeps_line_breaks() {
+        assert_eq!(
+            vec!["Good game!", "", "Thanks"],
+            wrap_text("Good game!\n\nThanks", 20)
+        );
+    }
+
+    #[test]
+    fn wrap_non_ascii() {
+        // Splitting by bytes would land in the middle of a character
+        assert_eq!(vec!["ééé", "éé"], wrap_text("ééééé", 3));
+    }
+}
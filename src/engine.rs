@@ -0,0 +1,216 @@
+use std::{
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use gtp::{
+    controller::{self, Error},
+    detached_command, Command, Response, ResponseParser,
+};
+use log::{info, warn};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+// Same as the gtp crate's wait_response, which polls four times per timeout
+const WAIT_POLL_DIV: u32 = 4;
+
+/// A GTP engine: GnuGo run on the tablet, or something stronger elsewhere on the network
+pub enum Engine {
+    // Boxed, as it's much bigger than a socket
+    Local(Box<controller::Engine>),
+    Remote(RemoteEngine),
+}
+
+impl Engine {
+    /// Starts (or restarts) the engine, which for a remote one means a new connection
+    pub fn start(&mut self) -> Result<(), Error> {
+        match self {
+            Engine::Local(engine) => engine.start(),
+            Engine::Remote(engine) => engine.connect(),
+        }
+    }
+
+    /// Returns the id the command was sent with
+    pub fn send(&mut self, cmd: Command) -> u32 {
+        match self {
+            Engine::Local(engine) => engine.send(cmd),
+            Engine::Remote(engine) => engine.send(cmd),
+        }
+    }
+
+    /// `Error::PollAgain` if nothing came back within `timeout`
+    pub fn wait_response(&mut self, timeout: Duration) -> Result<Response, Error> {
+        match self {
+            Engine::Local(engine) => engine.wait_response(timeout),
+            Engine::Remote(engine) => engine.wait_response(timeout),
+        }
+    }
+
+    /// `Error::PollAgain` if there's no response yet
+    pub fn poll_response(&mut self) -> Result<Response, Error> {
+        match self {
+            Engine::Local(engine) => engine.poll_response(),
+            Engine::Remote(engine) => engine.poll_response(),
+        }
+    }
+}
+
+/// GTP over a TCP socket, e.g. from `gogui-server` or `socat` in front of KataGo
+pub struct RemoteEngine {
+    address: String,
+    stream: Option<TcpStream>,
+    parser: ResponseParser,
+    cur_id: u32,
+}
+
+impl RemoteEngine {
+    pub fn new(address: &str) -> RemoteEngine {
+        RemoteEngine {
+            address: address.to_string(),
+            stream: None,
+            parser: ResponseParser::new(),
+            cur_id: 0,
+        }
+    }
+
+    fn connect(&mut self) -> Result<(), Error> {
+        self.stream = None;
+        self.parser = ResponseParser::new();
+        let startup_failed = |err: io::Error| {
+            warn!("Can't connect to {}: {err}", self.address);
+            Error::ProcessError(detached_command::Error::StartupFailed(err))
+        };
+        let addresses = self.address.to_socket_addrs().map_err(startup_failed)?;
+        let mut last_err = io::Error::new(io::ErrorKind::NotFound, "no addresses");
+        for address in addresses {
+            match TcpStream::connect_timeout(&address, CONNECT_TIMEOUT) {
+                Ok(stream) => {
+                    info!("Connected to {address}");
+                    self.stream = Some(stream);
+                    return Ok(());
+                }
+                Err(err) => last_err = err,
+            }
+        }
+        Err(startup_failed(last_err))
+    }
+
+    fn disconnected(&mut self, err: io::Error) -> Error {
+        warn!("Lost connection to {}: {err}", self.address);
+        self.stream = None;
+        Error::ProcessError(detached_command::Error::Disconnected)
+    }
+
+    fn send(&mut self, mut cmd: Command) -> u32 {
+        let Some(ref mut stream) = self.stream else {
+            return 0;
+        };
+        self.cur_id += 1;
+        cmd.set_id(self.cur_id);
+        if let Err(err) = stream.write_all(&cmd.to_bytes()) {
+            // Shows up as an error on the next poll, like a local engine that's died
+            self.disconnected(err);
+        }
+        self.cur_id
+    }
+
+    /// Everything that's arrived so far, without waiting for more, and whether the engine's
+    /// closed the connection after it
+    fn read_available(&mut self) -> Result<(String, bool), Error> {
+        let Some(ref mut stream) = self.stream else {
+            return Err(Error::NoHandle);
+        };
+        let mut received = vec![];
+        let mut buffer = [0; 4096];
+        let result = stream.set_nonblocking(true).and_then(|_| loop {
+            match stream.read(&mut buffer) {
+                Ok(0) => break Ok(true),
+                Ok(count) => received.extend_from_slice(&buffer[..count]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break Ok(false),
+                Err(err) => break Err(err),
+            }
+        });
+        let result = result.and_then(|closed| stream.set_nonblocking(false).map(|_| closed));
+        match result {
+            Ok(closed) => Ok((String::from_utf8_lossy(&received).to_string(), closed)),
+            Err(err) => Err(self.disconnected(err)),
+        }
+    }
+
+    fn poll_response(&mut self) -> Result<Response, Error> {
+        let (received, closed) = self.read_available()?;
+        if !received.is_empty() {
+            self.parser.feed(&received);
+        }
+        match self.parser.get_response() {
+            Ok(resp) => Ok(resp),
+            Err(_) if closed => Err(self.disconnected(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "closed by the engine",
+            ))),
+            Err(_) => Err(Error::PollAgain),
+        }
+    }
+
+    fn wait_response(&mut self, timeout: Duration) -> Result<Response, Error> {
+        let interval = timeout / WAIT_POLL_DIV;
+        let start = Instant::now();
+        loop {
+            match self.poll_response() {
+                Err(Error::PollAgain) if start.elapsed() <= timeout => sleep(interval),
+                other => return other,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use gtp::{controller::Error, Command, Response};
+    use pretty_assertions::assert_eq;
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread::spawn,
+        time::Duration,
+    };
+
+    use crate::engine::{Engine, RemoteEngine};
+
+    #[test]
+    fn remote_round_trip() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let server = spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            assert_eq!("1 protocol_version\n", line);
+            // Split up, like a slow network would
+            stream.write_all(b"=1 ").unwrap();
+            stream.flush().unwrap();
+            stream.write_all(b"2\n\n").unwrap();
+        });
+        let mut engine = Engine::Remote(RemoteEngine::new(&address));
+        engine.start().unwrap();
+        engine.send(Command::new_with_args("protocol_version", |e| e));
+        let resp = engine.wait_response(Duration::from_secs(5)).unwrap();
+        assert!(matches!(resp, Response::Result(_)));
+        assert_eq!("2", resp.text());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn remote_not_there() {
+        // Bound then dropped, so nothing's listening
+        let address = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap().to_string()
+        };
+        let mut engine = Engine::Remote(RemoteEngine::new(&address));
+        assert!(matches!(engine.start(), Err(Error::ProcessError(_))));
+        assert!(matches!(engine.poll_response(), Err(Error::NoHandle)));
+    }
+}
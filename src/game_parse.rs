@@ -1,15 +1,41 @@
 use libremarkable::cgmath::Point2;
-use log::info;
+use log::{info, warn};
+
+use crate::{board::Stone, go_grid::GoGrid, gtp::StoneLists};
 use sgf_parse::{
     go::{parse, Move, Prop},
-    SgfNode,
+    Color, SgfNode, SgfParseError,
 };
 
+// Enough of a bad SGF to see what went wrong, without filling the log
+const SGF_LOG_PREFIX: usize = 200;
+// The biggest board SGF can describe, as points are single letters a-z then A-Z
+const MAX_SGF_SIZE: u8 = 52;
+
+/// Who played a move, and where, with `None` for a pass
+pub type PlayedMove = (Stone, Option<Point2<u8>>);
+
 #[derive(PartialEq, Debug)]
 pub struct GameData {
     pub white_stones: Vec<Point2<u8>>,
     pub black_stones: Vec<Point2<u8>>,
     pub size: u8,
+    pub komi: f64,
+    /// From `HA`, with 0 for an even game
+    pub handicap: u8,
+    pub last_white_move: Option<Point2<u8>>,
+    pub last_black_move: Option<Point2<u8>>,
+    /// Including passes, but not setup stones
+    pub move_count: usize,
+    /// Stones each side has taken
+    pub black_captures: usize,
+    pub white_captures: usize,
+    /// SGF `RE` value, e.g. "B+R", once the game's over
+    pub result: Option<String>,
+    /// Whoever didn't play last, unless `PL` says otherwise
+    pub to_play: Stone,
+    /// The most recent `C`, e.g. a message from the opponent
+    pub comment: Option<String>,
 }
 
 fn get_sgf_properties_for_node(node: &SgfNode<Prop>) -> Vec<Prop> {
@@ -23,199 +49,214 @@ fn get_sgf_properties_for_node(node: &SgfNode<Prop>) -> Vec<Prop> {
     output
 }
 
-fn get_sgf_properties(raw_sgf: &str) -> Vec<Prop> {
+fn get_sgf_properties(raw_sgf: &str) -> Result<Vec<Prop>, SgfParseError> {
+    let nodes = parse(raw_sgf).map_err(|err| {
+        warn!(
+            "Can't parse SGF ({} bytes, starting '{}'): {err}",
+            raw_sgf.len(),
+            raw_sgf.chars().take(SGF_LOG_PREFIX).collect::<String>()
+        );
+        err
+    })?;
     let mut output = vec![];
-    for node in parse(&raw_sgf).unwrap() {
+    for node in nodes {
         output.append(&mut get_sgf_properties_for_node(&node));
     }
-    output
-}
-
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum GridPoint {
-    White,
-    Black,
-    Empty,
+    Ok(output)
 }
 
-fn find_dead_stones(
-    grid: &mut [&mut [GridPoint]],
-    unknown_spots: Vec<Point2<u8>>,
+/// Liberties of the group `colour` gets by playing at the 0-based `point`, once anything it takes
+/// is off the board. `None` if the point's off the board or taken. `stones` are 1-based white then
+/// black, as `list_stones` gives them
+fn liberties_after(
+    stones: &StoneLists,
     size: u8,
-) -> Vec<Point2<u8>> {
-    // for x in 0..(size as usize) {
-    //     for y in 0..(size as usize) {
-    //         match grid[x][y] {
-    //             GridPoint::White => print!("W"),
-    //             GridPoint::Black => print!("B"),
-    //             GridPoint::Empty => print!("."),
-    //         }
-    //     }
-    //     println!("");
-    // }
-
-    let mut safe_spots = vec![];
-    loop {
-        let mut new_safe_spot = false;
-        for check in &unknown_spots {
-            if safe_spots.contains(&check) {
-                continue;
-            }
-            let mut neighbours = vec![];
-            if check.x > 0 {
-                neighbours.push((check.y, check.x - 1));
-            }
-            if check.x < (size - 1) {
-                neighbours.push((check.y, check.x + 1));
-            }
-            if check.y > 0 {
-                neighbours.push((check.y - 1, check.x));
-            }
-            if (check.y) < (size - 1) {
-                neighbours.push((check.y + 1, check.x));
-            }
-            if neighbours.iter().any(|(y, x)| {
-                grid[*y as usize][*x as usize] == GridPoint::Empty
-                    || safe_spots.contains(&&Point2 { x: *x, y: *y })
-            }) {
-                new_safe_spot = true;
-                safe_spots.push(check);
-                // println!("New safe {check:?}. Neighbours: {neighbours:?}");
-            }
-        }
-        if !new_safe_spot {
-            break;
-        }
+    point: Point2<u8>,
+    colour: Stone,
+) -> Option<usize> {
+    let mut grid = GoGrid::from_stones(stones, size);
+    if point.x >= size || point.y >= size || grid.get(point).is_some() {
+        return None;
     }
-    let mut dead_stones = vec![];
-    for check in &unknown_spots {
-        if safe_spots.contains(&check) {
-            continue;
-        }
-        dead_stones.push(check.clone());
-    }
-    return dead_stones;
+    // Suicide takes the group off, leaving no liberties at `point`
+    grid.play(point, colour);
+    Some(grid.liberties(point).len())
+}
+
+/// Whether `colour` playing at `point` leaves that group with one liberty, as `liberties_after`.
+/// Taking a ko counts, as the stone is in atari even if it can't be taken back yet
+pub fn is_self_atari(stones: &StoneLists, size: u8, point: Point2<u8>, colour: Stone) -> bool {
+    liberties_after(stones, size, point, colour) == Some(1)
+}
+
+/// Whether `colour` playing at `point` would leave its own group with no liberties, which no
+/// rules we play by allow
+pub fn is_suicide(stones: &StoneLists, size: u8, point: Point2<u8>, colour: Stone) -> bool {
+    liberties_after(stones, size, point, colour) == Some(0)
+}
+
+pub fn get_game_data(raw_sgf: &str) -> Result<GameData, SgfParseError> {
+    get_game_data_up_to(raw_sgf, None)
+}
+
+/// The first `max_moves` moves in the order they were played, or all of them for `None`. Points
+/// are 1-based like `GameData`'s stones, with `None` for a pass, and setup stones aren't included
+pub fn get_moves(
+    raw_sgf: &str,
+    max_moves: Option<usize>,
+) -> Result<Vec<PlayedMove>, SgfParseError> {
+    let one_based = |played: Move| match played {
+        Move::Move(point) => Some(Point2 {
+            x: point.x + 1,
+            y: point.y + 1,
+        }),
+        Move::Pass => None,
+    };
+    Ok(get_sgf_properties(raw_sgf)?
+        .into_iter()
+        .filter_map(|prop| match prop {
+            Prop::B(played) => Some((Stone::Black, one_based(played))),
+            Prop::W(played) => Some((Stone::White, one_based(played))),
+            _ => None,
+        })
+        .take(max_moves.unwrap_or(usize::MAX))
+        .collect())
 }
 
-pub fn get_game_data(raw_sgf: &str) -> GameData {
+/// The position after the first `max_moves` moves, or the whole game for `None`
+pub fn get_game_data_up_to(
+    raw_sgf: &str,
+    max_moves: Option<usize>,
+) -> Result<GameData, SgfParseError> {
     let mut gd = GameData {
         white_stones: vec![],
         black_stones: vec![],
-        size: 0,
+        // The SGF default for Go
+        size: 19,
+        komi: 0.0,
+        handicap: 0,
+        last_white_move: None,
+        last_black_move: None,
+        move_count: 0,
+        black_captures: 0,
+        white_captures: 0,
+        result: None,
+        to_play: Stone::Black,
+        comment: None,
     };
-    let props = get_sgf_properties(raw_sgf);
+    let props = get_sgf_properties(raw_sgf)?;
 
     for prop in &props {
-        match prop {
-            Prop::SZ(size) => {
-                gd.size = size.0;
+        if let Prop::SZ(size) = prop {
+            if size.0 != size.1 || size.0 > MAX_SGF_SIZE {
+                warn!("Can't play on a {}x{} board", size.0, size.1);
+                return Err(SgfParseError::InvalidFF4Property);
             }
-            _ => {}
+            gd.size = size.0;
         }
     }
 
-    // From https://stackoverflow.com/a/36376568
-    let mut grid_raw = vec![GridPoint::Empty; (gd.size * gd.size) as usize];
-    let mut grid_base: Vec<_> = grid_raw
-        .as_mut_slice()
-        .chunks_mut(gd.size as usize)
-        .collect();
-    let grid = grid_base.as_mut_slice();
+    let mut grid = GoGrid::new(gd.size);
 
     for prop in props {
-        let mut current_move = GridPoint::Empty;
+        if matches!(prop, Prop::W(_) | Prop::B(_)) && Some(gd.move_count) == max_moves {
+            break;
+        }
+        let mut current_move = None;
         match prop {
             Prop::W(white_move) => {
+                gd.move_count += 1;
+                gd.to_play = Stone::Black;
+                gd.last_white_move = None;
                 if let Move::Move(point) = white_move {
-                    gd.white_stones.push(Point2 {
+                    let point = Point2 {
                         x: point.x,
                         y: point.y,
-                    });
-                    grid[point.y as usize][point.x as usize] = GridPoint::White;
-                    current_move = GridPoint::White;
+                    };
+                    gd.last_white_move = Some(point);
+                    current_move = Some((point, Stone::White));
                 }
             }
             Prop::B(black_move) => {
+                gd.move_count += 1;
+                gd.to_play = Stone::White;
+                gd.last_black_move = None;
                 if let Move::Move(point) = black_move {
-                    gd.black_stones.push(Point2 {
+                    let point = Point2 {
                         x: point.x,
                         y: point.y,
-                    });
-                    grid[point.y as usize][point.x as usize] = GridPoint::Black;
-                    current_move = GridPoint::Black;
+                    };
+                    gd.last_black_move = Some(point);
+                    current_move = Some((point, Stone::Black));
                 }
             }
             Prop::AB(black_moves) => {
                 for point in black_moves {
-                    gd.black_stones.push(Point2 {
-                        x: point.x,
-                        y: point.y,
-                    });
-                    grid[point.y as usize][point.x as usize] = GridPoint::Black;
+                    grid.place(
+                        Point2 {
+                            x: point.x,
+                            y: point.y,
+                        },
+                        Stone::Black,
+                    );
                 }
             }
             Prop::AW(white_moves) => {
                 for point in white_moves {
-                    gd.white_stones.push(Point2 {
-                        x: (point.x + 1),
-                        y: (point.y + 1),
+                    grid.place(
+                        Point2 {
+                            x: point.x,
+                            y: point.y,
+                        },
+                        Stone::White,
+                    );
+                }
+            }
+            Prop::AE(empty_points) => {
+                for point in empty_points {
+                    grid.remove(Point2 {
+                        x: point.x,
+                        y: point.y,
                     });
-                    grid[point.y as usize][point.x as usize] = GridPoint::White;
                 }
             }
+            Prop::KM(komi) => {
+                gd.komi = komi;
+            }
+            Prop::HA(handicap) => {
+                gd.handicap = handicap.clamp(0, u8::MAX as i64) as u8;
+            }
+            Prop::RE(result) => {
+                gd.result = Some(result.text);
+            }
+            Prop::C(comment) => {
+                gd.comment = Some(comment.text);
+            }
+            Prop::PL(colour) => {
+                gd.to_play = match colour {
+                    Color::Black => Stone::Black,
+                    Color::White => Stone::White,
+                };
+            }
             other => {
                 info!("Other prop: {other}")
             }
         }
 
-        match current_move {
-            GridPoint::Empty => {}
-            GridPoint::Black => {
-                let dead_black_stones = find_dead_stones(grid, gd.black_stones.clone(), gd.size);
-                if dead_black_stones.len() > 0 {
-                    gd.black_stones = gd
-                        .black_stones
-                        .iter()
-                        .filter(|s| !dead_black_stones.contains(s))
-                        .cloned()
-                        .collect();
-                }
-                let dead_white_stones = find_dead_stones(grid, gd.white_stones.clone(), gd.size);
-                if dead_white_stones.len() > 0 {
-                    gd.white_stones = gd
-                        .white_stones
-                        .iter()
-                        .filter(|s| !dead_white_stones.contains(s))
-                        .cloned()
-                        .collect();
-                }
-            }
-            GridPoint::White => {
-                let dead_white_stones = find_dead_stones(grid, gd.white_stones.clone(), gd.size);
-                if dead_white_stones.len() > 0 {
-                    gd.white_stones = gd
-                        .white_stones
-                        .iter()
-                        .filter(|s| !dead_white_stones.contains(s))
-                        .cloned()
-                        .collect();
-                }
-                let dead_black_stones = find_dead_stones(grid, gd.black_stones.clone(), gd.size);
-                if dead_black_stones.len() > 0 {
-                    gd.black_stones = gd
-                        .black_stones
-                        .iter()
-                        .filter(|s| !dead_black_stones.contains(s))
-                        .cloned()
-                        .collect();
+        if let Some((point, stone)) = current_move {
+            for (_, taken) in grid.play(point, stone) {
+                // Suicided stones count as taken by the other side too
+                match taken {
+                    Stone::White => gd.black_captures += 1,
+                    Stone::Black => gd.white_captures += 1,
                 }
             }
         }
     }
 
-    gd.white_stones.sort_by_key(|p| (p.x * gd.size) + p.y);
-    gd.black_stones.sort_by_key(|p| (p.x * gd.size) + p.y);
-    GameData {
+    gd.white_stones = grid.stones_of(Stone::White);
+    gd.black_stones = grid.stones_of(Stone::Black);
+    Ok(GameData {
         white_stones: gd
             .white_stones
             .iter()
@@ -233,7 +274,23 @@ pub fn get_game_data(raw_sgf: &str) -> GameData {
             })
             .collect(),
         size: gd.size,
-    }
+        komi: gd.komi,
+        handicap: gd.handicap,
+        last_white_move: gd.last_white_move.map(|s| Point2 {
+            x: s.x + 1,
+            y: s.y + 1,
+        }),
+        last_black_move: gd.last_black_move.map(|s| Point2 {
+            x: s.x + 1,
+            y: s.y + 1,
+        }),
+        move_count: gd.move_count,
+        black_captures: gd.black_captures,
+        white_captures: gd.white_captures,
+        result: gd.result,
+        to_play: gd.to_play,
+        comment: gd.comment,
+    })
 }
 
 #[cfg(test)]
@@ -242,7 +299,12 @@ mod test {
     use pretty_assertions::assert_eq;
     use std::fs;
 
-    use crate::game_parse::{get_game_data, GameData};
+    use crate::{
+        board::Stone,
+        game_parse::{
+            get_game_data, get_game_data_up_to, get_moves, is_self_atari, is_suicide, GameData,
+        },
+    };
 
     fn points(input: Vec<(u8, u8)>) -> Vec<Point2<u8>> {
         input.iter().map(|(x, y)| Point2 { x: *x, y: *y }).collect()
@@ -251,7 +313,7 @@ mod test {
     fn get_data(name: &str) -> GameData {
         let raw_data = fs::read(format!("src/test_data/{name}.sgf")).unwrap();
         let data = str::from_utf8(&raw_data).unwrap();
-        get_game_data(&data)
+        get_game_data(data).unwrap()
     }
 
     #[test]
@@ -261,7 +323,17 @@ mod test {
             GameData {
                 white_stones: points(vec![(7, 9)]),
                 black_stones: points(vec![(4, 4), (4, 10), (10, 4), (10, 10)]),
-                size: 13
+                size: 13,
+                komi: 0.5,
+                handicap: 4,
+                last_white_move: Some(Point2 { x: 7, y: 9 }),
+                last_black_move: None,
+                move_count: 1,
+                black_captures: 0,
+                white_captures: 0,
+                result: None,
+                to_play: Stone::Black,
+                comment: None,
             },
             game_data
         );
@@ -295,9 +367,194 @@ mod test {
                     (8, 6),
                     (8, 7)
                 ]),
-                size: 9
+                size: 9,
+                komi: 0.5,
+                handicap: 3,
+                last_white_move: Some(Point2 { x: 7, y: 4 }),
+                last_black_move: Some(Point2 { x: 8, y: 7 }),
+                move_count: 17,
+                black_captures: 0,
+                white_captures: 1,
+                result: None,
+                to_play: Stone::Black,
+                comment: None,
             },
             game_data
         );
     }
+
+    #[test]
+    fn remove_stones_load() {
+        // Without the AE, B[ab] would take the white stone in the corner
+        let game_data = get_data("remove-stones");
+        assert_eq!(points(vec![(1, 1)]), game_data.white_stones);
+        assert_eq!(points(vec![(1, 2), (7, 3)]), game_data.black_stones);
+        assert_eq!(0, game_data.white_captures);
+        assert_eq!(Some(Point2 { x: 1, y: 2 }), game_data.last_black_move);
+    }
+
+    #[test]
+    fn result_load() {
+        let raw_data = fs::read("src/test_data/basic.sgf").unwrap();
+        let data = str::from_utf8(&raw_data)
+            .unwrap()
+            .replacen("SZ[", "RE[W+R]SZ[", 1);
+        assert_eq!(
+            Some("W+R".to_string()),
+            get_game_data(&data).unwrap().result
+        );
+    }
+
+    #[test]
+    fn to_play_load() {
+        let raw_data = fs::read("src/test_data/basic.sgf").unwrap();
+        // Just the handicap stones, so it's down to PL
+        let data = str::from_utf8(&raw_data)
+            .unwrap()
+            .replacen(";MN[1]W[gi]", "", 1);
+        assert_eq!(Stone::White, get_game_data(&data).unwrap().to_play);
+    }
+
+    #[test]
+    fn no_size_load() {
+        let game_data = get_data("no-size");
+        assert_eq!(19, game_data.size);
+        assert_eq!(points(vec![(4, 16), (16, 4)]), game_data.black_stones);
+        assert_eq!(points(vec![(17, 16)]), game_data.white_stones);
+        assert_eq!(1, game_data.move_count);
+    }
+
+    #[test]
+    fn setup_to_play_load() {
+        let raw_data = fs::read_to_string("src/test_data/setup-to-play.sgf").unwrap();
+        // Handicap, so white goes first
+        let setup = get_game_data_up_to(&raw_data, Some(0)).unwrap();
+        assert_eq!(Stone::White, setup.to_play);
+        assert_eq!(2, setup.black_stones.len());
+        let first = get_game_data_up_to(&raw_data, Some(1)).unwrap();
+        assert_eq!(Stone::Black, first.to_play);
+        assert_eq!(Stone::White, get_data("setup-to-play").to_play);
+    }
+
+    #[test]
+    fn comment_load() {
+        let raw_data = fs::read("src/test_data/one-capture.sgf").unwrap();
+        let data = str::from_utf8(&raw_data)
+            .unwrap()
+            .replacen(";B[cc]", ";B[cc]C[Hello]", 1)
+            .replacen(";W[gd]", ";W[gd]C[Good luck\\] (1:1)]", 1);
+        assert_eq!(
+            Some("Good luck] (1:1)".to_string()),
+            get_game_data(&data).unwrap().comment
+        );
+    }
+
+    #[test]
+    fn partial_load() {
+        let raw_data = fs::read("src/test_data/one-capture.sgf").unwrap();
+        let data = str::from_utf8(&raw_data).unwrap();
+        let game_data = get_game_data_up_to(data, Some(2)).unwrap();
+        assert_eq!(2, game_data.move_count);
+        // Handicap stones, plus the first move each
+        assert_eq!(
+            points(vec![(3, 3), (3, 7), (7, 3), (7, 7)]),
+            game_data.black_stones
+        );
+        assert_eq!(points(vec![(4, 5)]), game_data.white_stones);
+        assert_eq!(Stone::White, game_data.to_play);
+        let start = get_game_data_up_to(data, Some(0)).unwrap();
+        assert_eq!(0, start.move_count);
+        assert!(start.white_stones.is_empty());
+        assert_eq!(
+            get_data("one-capture"),
+            get_game_data_up_to(data, Some(100)).unwrap()
+        );
+    }
+
+    #[test]
+    fn malformed_load() {
+        let raw_data = fs::read("src/test_data/one-capture.sgf").unwrap();
+        let data = str::from_utf8(&raw_data).unwrap();
+        // Cut off part way through a move, as a dropped connection would
+        let truncated = &data[..data.find(";W[ff]").unwrap() + 4];
+        assert!(get_game_data(truncated).is_err());
+        assert!(get_game_data("not an sgf").is_err());
+    }
+
+    #[test]
+    fn unplayable_sizes() {
+        assert!(get_game_data("(;FF[4]GM[1]SZ[200];B[aa])").is_err());
+        assert!(get_game_data("(;FF[4]GM[1]SZ[19:5];B[aa])").is_err());
+        assert_eq!(52, get_game_data("(;FF[4]GM[1]SZ[52])").unwrap().size);
+    }
+
+    #[test]
+    fn self_atari() {
+        let stones = (points(vec![(2, 1), (4, 4)]), points(vec![(3, 3)]));
+        // Into the corner next to white
+        assert!(is_self_atari(
+            &stones,
+            5,
+            Point2 { x: 0, y: 0 },
+            Stone::Black
+        ));
+        assert!(!is_self_atari(
+            &stones,
+            5,
+            Point2 { x: 0, y: 0 },
+            Stone::White
+        ));
+        // Joins onto the black stone, with plenty of room
+        assert!(!is_self_atari(
+            &stones,
+            5,
+            Point2 { x: 2, y: 3 },
+            Stone::Black
+        ));
+        // Already taken
+        assert!(!is_self_atari(
+            &stones,
+            5,
+            Point2 { x: 1, y: 0 },
+            Stone::Black
+        ));
+        assert!(!is_self_atari(
+            &stones,
+            5,
+            Point2 { x: 9, y: 9 },
+            Stone::Black
+        ));
+    }
+
+    #[test]
+    fn self_atari_with_capture() {
+        // Black at B1 would have no liberties, but takes the corner stone and gets one back
+        let ko = (points(vec![(1, 1), (3, 1), (2, 2)]), points(vec![(1, 2)]));
+        assert!(is_self_atari(&ko, 5, Point2 { x: 1, y: 0 }, Stone::Black));
+        // Taking two stones leaves more room
+        let two = (points(vec![(1, 1), (2, 1)]), points(vec![(1, 2), (2, 2)]));
+        assert!(!is_self_atari(&two, 5, Point2 { x: 2, y: 0 }, Stone::Black));
+    }
+
+    #[test]
+    fn suicide() {
+        let stones = (points(vec![(2, 1), (1, 2)]), points(vec![(3, 1)]));
+        assert!(is_suicide(&stones, 5, Point2 { x: 0, y: 0 }, Stone::Black));
+        assert!(!is_suicide(&stones, 5, Point2 { x: 0, y: 0 }, Stone::White));
+        // Taking B1 first makes it fine
+        let taking = (points(vec![(2, 1), (1, 2)]), points(vec![(3, 1), (2, 2)]));
+        assert!(!is_suicide(&taking, 5, Point2 { x: 0, y: 0 }, Stone::Black));
+        assert!(!is_suicide(&stones, 5, Point2 { x: 1, y: 0 }, Stone::Black));
+    }
+
+    #[test]
+    fn moves_in_order() {
+        let raw_data = fs::read_to_string("src/test_data/one-capture.sgf").unwrap();
+        let moves = get_moves(&raw_data, None).unwrap();
+        assert_eq!(17, moves.len());
+        // Same as `last_white_move` and `last_black_move`, with white playing last
+        assert_eq!((Stone::White, Some(Point2 { x: 7, y: 4 })), moves[16]);
+        assert_eq!((Stone::Black, Some(Point2 { x: 8, y: 7 })), moves[15]);
+        assert_eq!(moves[..3], get_moves(&raw_data, Some(3)).unwrap());
+    }
 }
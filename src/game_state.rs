@@ -0,0 +1,238 @@
+use libremarkable::{
+    appctx,
+    cgmath::{Point2, Vector2},
+    framebuffer::{core::Framebuffer, FramebufferDraw},
+    input::MultitouchEvent,
+};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::{fs, io};
+
+use crate::{
+    board::Stone,
+    drawing::{centred_x, pressed, refresh, scaled_size, Button, Held, HoldTracker},
+    reset::{draw_back, handle_back},
+};
+
+/// The game in progress, written after every move so it survives a crash or power cut
+const STATE_FILE: &str = "/opt/fuseki-game-state";
+// Written first and then renamed over the real one, so a power cut can't leave half a file
+const STATE_TEMP_FILE: &str = "/opt/fuseki-game-state.tmp";
+const RESUME_BUTTON_SIZE: Vector2<u32> = Vector2 { x: 700, y: 95 };
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum LocalGame {
+    Machine,
+    Atari,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct GameState {
+    pub game: LocalGame,
+    pub size: u8,
+    /// The human's colour in machine games, or whoever went first in Atari games
+    pub player: Stone,
+    /// 0-based `[x, y]` from the top-left, with `None` for a pass
+    moves: Vec<(Stone, Option<[u8; 2]>)>,
+}
+
+impl GameState {
+    pub fn new(
+        game: LocalGame,
+        size: u8,
+        player: Stone,
+        history: &[(Stone, Option<Point2<u8>>)],
+    ) -> GameState {
+        GameState {
+            game,
+            size,
+            player,
+            moves: history
+                .iter()
+                .map(|(stone, point)| (*stone, point.map(|p| [p.x, p.y])))
+                .collect(),
+        }
+    }
+
+    /// Every move, like the machine game's history
+    pub fn history(&self) -> Vec<(Stone, Option<Point2<u8>>)> {
+        self.moves
+            .iter()
+            .map(|(stone, point)| (*stone, point.map(|[x, y]| Point2 { x, y })))
+            .collect()
+    }
+
+    /// Whether it's worth offering to resume, and can't have anything off the board
+    fn is_valid(&self) -> bool {
+        !self.moves.is_empty()
+            && self
+                .moves
+                .iter()
+                .all(|(_, point)| point.map_or(true, |[x, y]| x < self.size && y < self.size))
+    }
+}
+
+/// Replaces the saved game, or clears it if there aren't any moves to save yet
+pub fn save_state(state: &GameState) {
+    if state.moves.is_empty() {
+        clear_state();
+        return;
+    }
+    let raw = serde_json::to_vec(state).expect("can dump game state");
+    if let Err(err) =
+        fs::write(STATE_TEMP_FILE, raw).and_then(|_| fs::rename(STATE_TEMP_FILE, STATE_FILE))
+    {
+        warn!("Can't write {STATE_FILE}: {err}");
+    }
+}
+
+fn parse_state(raw: &[u8]) -> Option<GameState> {
+    match serde_json::from_slice::<GameState>(raw) {
+        Ok(state) if state.is_valid() => Some(state),
+        Ok(state) => {
+            warn!("Ignoring saved game with nothing to resume or bad moves: {state:?}");
+            None
+        }
+        Err(err) => {
+            warn!("Ignoring bad saved game in {STATE_FILE}: {err}");
+            None
+        }
+    }
+}
+
+/// The saved game for `game` on a `size` board, if there's one to resume
+pub fn load_state(game: LocalGame, size: u8) -> Option<GameState> {
+    let raw = match fs::read(STATE_FILE) {
+        Ok(raw) => raw,
+        Err(err) => {
+            info!("No saved game in {STATE_FILE}: {err}");
+            return None;
+        }
+    };
+    parse_state(&raw).filter(|state| {
+        if state.game != game || state.size != size {
+            info!(
+                "Saved game is {:?} on {}x{}, not resuming",
+                state.game, state.size, state.size
+            );
+            return false;
+        }
+        true
+    })
+}
+
+/// For once a game's over or thrown away, so it's not offered again
+pub fn clear_state() {
+    match fs::remove_file(STATE_FILE) {
+        Ok(()) => info!("Cleared saved game"),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => warn!("Can't remove {STATE_FILE}: {err}"),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResumeChoice {
+    Resume,
+    NewGame,
+}
+
+/// "New game" throws the saved one away, so it has to be held
+fn resume_buttons() -> Vec<Button<ResumeChoice>> {
+    let size = scaled_size(RESUME_BUTTON_SIZE);
+    let button = |text, y, choice| {
+        Button::new(
+            text,
+            Point2 {
+                x: centred_x(size.x),
+                y,
+            },
+            size,
+            choice,
+        )
+    };
+    vec![
+        button("Resume last game", 300, ResumeChoice::Resume),
+        button("New game", 500, ResumeChoice::NewGame).held(),
+    ]
+}
+
+/// Shown instead of starting a game when there's one to pick up again
+pub fn draw_resume_choice(fb: &mut Framebuffer) {
+    fb.clear();
+    for button in resume_buttons() {
+        button.draw(fb);
+    }
+    draw_back(fb);
+    refresh(fb);
+}
+
+/// What a touch on the resume screen picked, if anything. Handles the back button as well
+pub fn resume_choice(
+    hold: &mut HoldTracker,
+    ctx: &mut appctx::ApplicationContext<'_>,
+    event: &MultitouchEvent,
+) -> Option<ResumeChoice> {
+    let buttons = resume_buttons();
+    match hold.handle(ctx.get_framebuffer_ref(), &buttons, event) {
+        Held::Fired(choice) => Some(choice),
+        Held::Holding => None,
+        Held::Ignored => match event {
+            MultitouchEvent::Press { finger } if !handle_back(ctx, finger.pos) => {
+                pressed(&buttons, finger.pos).map(|button| button.action)
+            }
+            _ => None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use libremarkable::cgmath::Point2;
+    use pretty_assertions::assert_eq;
+
+    use crate::{
+        board::Stone,
+        game_state::{parse_state, GameState, LocalGame},
+    };
+
+    #[test]
+    fn round_trip() {
+        let history = vec![
+            (Stone::Black, Some(Point2 { x: 2, y: 3 })),
+            (Stone::White, None),
+            (Stone::Black, Some(Point2 { x: 8, y: 0 })),
+        ];
+        let state = GameState::new(LocalGame::Machine, 9, Stone::White, &history);
+        let raw = serde_json::to_vec(&state).unwrap();
+        let loaded = parse_state(&raw).unwrap();
+        assert_eq!(state, loaded);
+        assert_eq!(history, loaded.history());
+    }
+
+    #[test]
+    fn corrupt() {
+        assert_eq!(None, parse_state(b""));
+        assert_eq!(
+            None,
+            parse_state(br#"{"game": "Machine", "size": 9, "play"#)
+        );
+        assert_eq!(None, parse_state(b"not json"));
+    }
+
+    #[test]
+    fn nothing_to_resume() {
+        let state = GameState::new(LocalGame::Atari, 9, Stone::Black, &[]);
+        assert_eq!(None, parse_state(&serde_json::to_vec(&state).unwrap()));
+    }
+
+    #[test]
+    fn off_the_board() {
+        let state = GameState::new(
+            LocalGame::Atari,
+            9,
+            Stone::Black,
+            &[(Stone::Black, Some(Point2 { x: 9, y: 0 }))],
+        );
+        assert_eq!(None, parse_state(&serde_json::to_vec(&state).unwrap()));
+    }
+}
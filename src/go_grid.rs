@@ -0,0 +1,250 @@
+use libremarkable::cgmath::Point2;
+
+use crate::{board::Stone, gtp::StoneLists};
+
+/// A board that knows how captures work, so positions can be worked out without asking GnuGo,
+/// e.g. replaying an SGF or checking a move first. Points are 0-based from the top-left
+#[derive(Debug, Clone, PartialEq)]
+pub struct GoGrid {
+    size: u8,
+    /// Row by row
+    points: Vec<Option<Stone>>,
+}
+
+impl GoGrid {
+    pub fn new(size: u8) -> GoGrid {
+        GoGrid {
+            size,
+            points: vec![None; size as usize * size as usize],
+        }
+    }
+
+    /// From 1-based white then black stones, as `list_stones` gives them. Anything off the board
+    /// is left out
+    pub fn from_stones(stones: &StoneLists, size: u8) -> GoGrid {
+        let mut grid = GoGrid::new(size);
+        let (white_stones, black_stones) = stones;
+        for (list, stone) in [(white_stones, Stone::White), (black_stones, Stone::Black)] {
+            for point in list {
+                if point.x > 0 && point.y > 0 {
+                    grid.place(
+                        Point2 {
+                            x: point.x - 1,
+                            y: point.y - 1,
+                        },
+                        stone,
+                    );
+                }
+            }
+        }
+        grid
+    }
+
+    fn index(&self, point: Point2<u8>) -> Option<usize> {
+        if point.x < self.size && point.y < self.size {
+            Some(point.y as usize * self.size as usize + point.x as usize)
+        } else {
+            None
+        }
+    }
+
+    /// What's at `point`, with `None` for empty or off the board
+    pub fn get(&self, point: Point2<u8>) -> Option<Stone> {
+        self.index(point).and_then(|index| self.points[index])
+    }
+
+    /// Puts a stone down without taking anything, e.g. for setup stones. Does nothing off the
+    /// board
+    pub fn place(&mut self, point: Point2<u8>, stone: Stone) {
+        if let Some(index) = self.index(point) {
+            self.points[index] = Some(stone);
+        }
+    }
+
+    pub fn remove(&mut self, point: Point2<u8>) {
+        if let Some(index) = self.index(point) {
+            self.points[index] = None;
+        }
+    }
+
+    /// On-board neighbours of `point`
+    fn neighbours(&self, point: Point2<u8>) -> Vec<Point2<u8>> {
+        let mut found = vec![];
+        if point.x > 0 {
+            found.push(Point2 {
+                x: point.x - 1,
+                y: point.y,
+            });
+        }
+        if point.x + 1 < self.size {
+            found.push(Point2 {
+                x: point.x + 1,
+                y: point.y,
+            });
+        }
+        if point.y > 0 {
+            found.push(Point2 {
+                x: point.x,
+                y: point.y - 1,
+            });
+        }
+        if point.y + 1 < self.size {
+            found.push(Point2 {
+                x: point.x,
+                y: point.y + 1,
+            });
+        }
+        found
+    }
+
+    /// The stones connected to the one at `point`. Empty if there's no stone there
+    pub fn group(&self, point: Point2<u8>) -> Vec<Point2<u8>> {
+        let Some(colour) = self.get(point) else {
+            return vec![];
+        };
+        let mut group = vec![point];
+        let mut index = 0;
+        while index < group.len() {
+            for next in self.neighbours(group[index]) {
+                if self.get(next) == Some(colour) && !group.contains(&next) {
+                    group.push(next);
+                }
+            }
+            index += 1;
+        }
+        group
+    }
+
+    /// The empty points next to the group at `point`
+    pub fn liberties(&self, point: Point2<u8>) -> Vec<Point2<u8>> {
+        let mut liberties = vec![];
+        for stone in self.group(point) {
+            for next in self.neighbours(stone) {
+                if self.index(next).is_some()
+                    && self.get(next).is_none()
+                    && !liberties.contains(&next)
+                {
+                    liberties.push(next);
+                }
+            }
+        }
+        liberties
+    }
+
+    /// Takes the group at `point` off the board if it's out of liberties, and gives back what
+    /// went
+    pub fn capture(&mut self, point: Point2<u8>) -> Vec<Point2<u8>> {
+        if !self.liberties(point).is_empty() {
+            return vec![];
+        }
+        let group = self.group(point);
+        for stone in &group {
+            self.remove(*stone);
+        }
+        group
+    }
+
+    /// Plays `stone` at `point`: any of the other colour's groups left without liberties come
+    /// off first, then its own group if that's still got none (suicide, which SGF files can
+    /// have). Gives back every stone that came off, with its colour
+    pub fn play(&mut self, point: Point2<u8>, stone: Stone) -> Vec<(Point2<u8>, Stone)> {
+        self.place(point, stone);
+        let mut taken = vec![];
+        for next in self.neighbours(point) {
+            if self.get(next) == Some(stone.other()) {
+                taken.extend(self.capture(next).into_iter().map(|p| (p, stone.other())));
+            }
+        }
+        taken.extend(self.capture(point).into_iter().map(|p| (p, stone)));
+        taken
+    }
+
+    /// Every `stone` on the board, sorted by `x` then `y`
+    pub fn stones_of(&self, stone: Stone) -> Vec<Point2<u8>> {
+        let mut found = vec![];
+        for x in 0..self.size {
+            for y in 0..self.size {
+                let point = Point2 { x, y };
+                if self.get(point) == Some(stone) {
+                    found.push(point);
+                }
+            }
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use libremarkable::cgmath::Point2;
+    use pretty_assertions::assert_eq;
+
+    use crate::{board::Stone, go_grid::GoGrid};
+
+    fn grid_with(size: u8, white: &[(u8, u8)], black: &[(u8, u8)]) -> GoGrid {
+        let mut grid = GoGrid::new(size);
+        for (list, stone) in [(white, Stone::White), (black, Stone::Black)] {
+            for (x, y) in list {
+                grid.place(Point2 { x: *x, y: *y }, stone);
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn groups_and_liberties() {
+        let grid = grid_with(5, &[(1, 1), (1, 2)], &[(0, 1), (2, 1)]);
+        let mut group = grid.group(Point2 { x: 1, y: 2 });
+        group.sort_by_key(|p| (p.x, p.y));
+        assert_eq!(vec![Point2 { x: 1, y: 1 }, Point2 { x: 1, y: 2 }], group);
+        assert_eq!(4, grid.liberties(Point2 { x: 1, y: 1 }).len());
+        // Corner-ish black stone on the edge
+        assert_eq!(2, grid.liberties(Point2 { x: 0, y: 1 }).len());
+        assert!(grid.group(Point2 { x: 4, y: 4 }).is_empty());
+    }
+
+    #[test]
+    fn corner_capture() {
+        let mut grid = grid_with(5, &[(0, 0)], &[(1, 0)]);
+        let taken = grid.play(Point2 { x: 0, y: 1 }, Stone::Black);
+        assert_eq!(vec![(Point2 { x: 0, y: 0 }, Stone::White)], taken);
+        assert_eq!(None, grid.get(Point2 { x: 0, y: 0 }));
+        assert_eq!(
+            vec![Point2 { x: 0, y: 1 }, Point2 { x: 1, y: 0 }],
+            grid.stones_of(Stone::Black)
+        );
+    }
+
+    #[test]
+    fn capture_before_suicide() {
+        // White at (0,0) has no liberties once it's played, but it takes the black stone at
+        // (1,0) first, so it stays
+        let mut grid = grid_with(5, &[(2, 0), (1, 1)], &[(1, 0), (0, 1)]);
+        let taken = grid.play(Point2 { x: 0, y: 0 }, Stone::White);
+        assert_eq!(vec![(Point2 { x: 1, y: 0 }, Stone::Black)], taken);
+        assert_eq!(Some(Stone::White), grid.get(Point2 { x: 0, y: 0 }));
+    }
+
+    #[test]
+    fn suicide() {
+        let mut grid = grid_with(5, &[], &[(1, 0), (0, 1)]);
+        let taken = grid.play(Point2 { x: 0, y: 0 }, Stone::White);
+        assert_eq!(vec![(Point2 { x: 0, y: 0 }, Stone::White)], taken);
+        assert_eq!(None, grid.get(Point2 { x: 0, y: 0 }));
+    }
+
+    #[test]
+    fn from_list_stones() {
+        let grid = GoGrid::from_stones(
+            &(
+                vec![Point2 { x: 1, y: 1 }],
+                vec![Point2 { x: 5, y: 5 }, Point2 { x: 6, y: 1 }],
+            ),
+            5,
+        );
+        assert_eq!(Some(Stone::White), grid.get(Point2 { x: 0, y: 0 }));
+        assert_eq!(Some(Stone::Black), grid.get(Point2 { x: 4, y: 4 }));
+        // Off the board
+        assert_eq!(vec![Point2 { x: 4, y: 4 }], grid.stones_of(Stone::Black));
+    }
+}
@@ -1,38 +1,224 @@
-use std::time::{Duration, Instant};
+use std::{
+    collections::HashSet,
+    env, fmt, fs,
+    time::{Duration, Instant},
+};
 
-use gtp::{controller::Engine, Command, Response};
+use gtp::{Command, Response};
 use libremarkable::cgmath::Point2;
-use log::info;
+use log::{info, warn};
 
-pub fn get_response(ctrl: &mut Engine) -> Response {
+use crate::{
+    board::{gtp_vertex, point_to_gtp, removed_stones, Stone},
+    engine::Engine,
+    game_parse::get_game_data,
+};
+
+/// How long to wait overall for a normal GTP command before giving up on the engine
+pub const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+/// GnuGo has to load its pattern databases first, which is slow on a Remarkable
+pub const STARTUP_TIMEOUT: Duration = Duration::from_secs(2 * 60);
+/// Hints take as long as a real machine move
+const SUGGEST_MOVE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Everything that can go wrong talking to the engine. The helpers here all return these rather
+/// than panicking, so routines can show `status_text` and carry on
+#[derive(Debug)]
+pub enum GtpError {
+    /// Nothing came back within the time allowed
+    Timeout(Duration),
+    /// GnuGo's crashed or the connection to a remote engine's gone
+    EngineDead(gtp::controller::Error),
+    /// The engine wouldn't play a move we needed it to, e.g. replaying a saved game
+    IllegalMove { command: String, response: String },
+    /// The engine answered with an error, e.g. a board size it can't do
+    Rejected { command: String, response: String },
+    /// The engine answered, but not with something we could parse
+    Parse { command: String, response: String },
+}
+
+impl fmt::Display for GtpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GtpError::Timeout(timeout) => {
+                write!(f, "no response from engine after {:.2?}", timeout)
+            }
+            GtpError::EngineDead(err) => write!(f, "engine failure: {:?}", err),
+            GtpError::IllegalMove { command, response } => {
+                write!(f, "illegal move {command}: '{response}'")
+            }
+            GtpError::Rejected { command, response } => {
+                write!(f, "engine refused {command}: '{response}'")
+            }
+            GtpError::Parse { command, response } => {
+                write!(f, "unexpected response to {command}: '{response}'")
+            }
+        }
+    }
+}
+
+impl GtpError {
+    /// Short enough to fit in a game's status area
+    pub fn status_text(&self) -> &'static str {
+        match self {
+            GtpError::Timeout(_) => "Engine not responding",
+            GtpError::EngineDead(_) => "Engine stopped",
+            GtpError::IllegalMove { .. } => "Illegal move",
+            GtpError::Rejected { .. } => "Engine refused",
+            GtpError::Parse { .. } => "Engine confused",
+        }
+    }
+}
+
+pub fn get_response(ctrl: &mut Engine) -> Result<Response, GtpError> {
+    get_response_with_timeout(ctrl, RESPONSE_TIMEOUT)
+}
+
+pub fn get_response_with_timeout(
+    ctrl: &mut Engine,
+    timeout: Duration,
+) -> Result<Response, GtpError> {
+    let start = Instant::now();
     loop {
         match ctrl.wait_response(Duration::from_secs(1)) {
             Ok(resp) => {
-                return resp;
+                return Ok(resp);
             }
             Err(gtp::controller::Error::PollAgain) => {
+                if start.elapsed() >= timeout {
+                    warn!("No response after {:.2?}", start.elapsed());
+                    return Err(GtpError::Timeout(timeout));
+                }
                 info!("repoll...");
             }
             Err(err) => {
-                panic!("Other error {err:?}")
+                warn!("Engine error {err:?}");
+                return Err(GtpError::EngineDead(err));
             }
         }
     }
 }
 
-pub fn set_board_size(ctrl: &mut Engine, board_size: u8) {
-    ctrl.send(Command::new_with_args("boardsize", |e| {
-        e.i(board_size as u32)
-    }));
-    get_response(ctrl);
+/// Checks once for a response to a command sent at `started`, without waiting.
+/// `Ok(None)` means it's still running
+pub fn poll_response(
+    ctrl: &mut Engine,
+    started: Instant,
+    timeout: Duration,
+) -> Result<Option<Response>, GtpError> {
+    match ctrl.poll_response() {
+        Ok(resp) => Ok(Some(resp)),
+        Err(gtp::controller::Error::PollAgain) => {
+            if started.elapsed() >= timeout {
+                warn!("No response after {:.2?}", started.elapsed());
+                Err(GtpError::Timeout(timeout))
+            } else {
+                Ok(None)
+            }
+        }
+        Err(err) => {
+            warn!("Engine error {err:?}");
+            Err(GtpError::EngineDead(err))
+        }
+    }
+}
+
+/// Waits for a freshly started engine to answer `protocol_version`, so the first real command
+/// doesn't time out while it's still loading
+pub fn wait_until_ready(ctrl: &mut Engine) -> Result<(), GtpError> {
+    let start = Instant::now();
+    ctrl.send(Command::new_with_args("protocol_version", |e| e));
+    let resp = get_response_with_timeout(ctrl, STARTUP_TIMEOUT)?;
+    info!(
+        "protocol_version: '{}' after {:.2?}",
+        resp.text(),
+        start.elapsed()
+    );
+    match resp {
+        Response::Result(_) if resp.text().trim() == "2" => Ok(()),
+        _ => Err(GtpError::Parse {
+            command: "protocol_version".to_string(),
+            response: resp.text(),
+        }),
+    }
+}
+
+/// Starts the engine again and waits for it, which is the only way to stop it thinking about a
+/// `genmove`. It forgets the game, so that needs playing back afterwards
+pub fn restart_engine(ctrl: &mut Engine) -> Result<(), GtpError> {
+    ctrl.start().map_err(GtpError::EngineDead)?;
+    wait_until_ready(ctrl)
+}
+
+/// Sends `cmd` and waits for it to succeed, with any error answer coming back as `Rejected`.
+/// Returns whatever the engine said
+fn run(ctrl: &mut Engine, cmd: Command) -> Result<String, GtpError> {
+    run_with_timeout(ctrl, cmd, RESPONSE_TIMEOUT)
+}
+
+fn run_with_timeout(
+    ctrl: &mut Engine,
+    cmd: Command,
+    timeout: Duration,
+) -> Result<String, GtpError> {
+    let command = cmd.to_string().trim().to_string();
+    ctrl.send(cmd);
+    match get_response_with_timeout(ctrl, timeout)? {
+        Response::Result((_, text)) => Ok(text),
+        Response::Error((_, response)) => {
+            warn!("{command} failed: {response}");
+            Err(GtpError::Rejected { command, response })
+        }
+    }
+}
+
+pub fn set_board_size(ctrl: &mut Engine, board_size: u8) -> Result<(), GtpError> {
+    run(
+        ctrl,
+        Command::new_with_args("boardsize", |e| e.i(board_size as u32)),
+    )?;
+    Ok(())
 }
 
-pub fn list_stones(ctrl: &mut Engine, colour: &str) -> Vec<Point2<u8>> {
+/// `set_supported_board_size` tries these in order when the engine won't take the size asked for
+const FALLBACK_BOARD_SIZES: [u8; 3] = [9, 13, 19];
+
+/// `set_board_size`, but if the engine turns `board_size` down (some builds only do certain
+/// sizes), the first of `FALLBACK_BOARD_SIZES` it takes instead. Returns the size that's set, so
+/// the `Board` can match it
+pub fn set_supported_board_size(ctrl: &mut Engine, board_size: u8) -> Result<u8, GtpError> {
+    let rejected = match set_board_size(ctrl, board_size) {
+        Ok(()) => return Ok(board_size),
+        Err(err @ GtpError::Rejected { .. }) => err,
+        Err(err) => return Err(err),
+    };
+    for size in FALLBACK_BOARD_SIZES {
+        if size == board_size {
+            continue;
+        }
+        match set_board_size(ctrl, size) {
+            Ok(()) => {
+                warn!("Engine won't play on {board_size}x{board_size}, so using {size}x{size}");
+                return Ok(size);
+            }
+            Err(GtpError::Rejected { .. }) => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Err(rejected)
+}
+
+/// For the status, after `set_supported_board_size` had to use another size
+pub fn board_size_fallback_text(asked: u8, size: u8) -> String {
+    format!("No {asked}x{asked} in the engine\nPlaying {size}x{size}")
+}
+
+pub fn list_stones(ctrl: &mut Engine, colour: &str) -> Result<Vec<Point2<u8>>, GtpError> {
     let start = Instant::now();
     let cmd = Command::new_with_args("list_stones", |e| e.s(colour));
     info!("list_stones: {}", cmd.to_string());
     ctrl.send(cmd);
-    let resp = get_response(ctrl);
+    let resp = get_response(ctrl)?;
     info!("list_stones resp: {}", resp.text());
     let ev = resp.entities(|ep| {
         let mut ret = ep;
@@ -43,22 +229,130 @@ pub fn list_stones(ctrl: &mut Engine, colour: &str) -> Vec<Point2<u8>> {
     });
     let elapsed = start.elapsed();
     info!("list_stones elapsed: {:.2?}", elapsed);
-    return ev
-        .unwrap()
+    let ev = ev.map_err(|err| {
+        warn!(
+            "Can't parse list_stones response '{}': {err:?}",
+            resp.text()
+        );
+        GtpError::Parse {
+            command: format!("list_stones {colour}"),
+            response: resp.text(),
+        }
+    })?;
+    Ok(ev
         .iter()
-        .map(|entity| match entity {
+        .filter_map(|entity| match entity {
             gtp::Entity::Vertex((x, y)) => Some(Point2 {
                 x: *x as u8,
                 y: *y as u8,
             }),
             _ => None,
         })
-        .filter(|p| p.is_some())
-        .map(|p| p.unwrap())
-        .collect();
+        .collect())
 }
 
-pub fn do_human_move(ctrl: &mut Engine, pos: Point2<u8>, colour: &str) -> bool {
+/// White then black stones, in GTP coordinates like `list_stones`
+pub type StoneLists = (Vec<Point2<u8>>, Vec<Point2<u8>>);
+
+/// Both colours from `list_stones`, only asked for again once the board's changed. Saves two GTP
+/// round trips on redraws that don't follow a move, like showing a hint
+#[derive(Default)]
+pub struct StoneCache {
+    /// Goes up on every change to the engine's board
+    move_counter: u32,
+    /// The `move_counter` they were listed at, and the stones
+    stones: Option<(u32, StoneLists)>,
+}
+
+impl StoneCache {
+    /// Call after anything that changes the engine's board: moves, undos, clearing and resizing
+    pub fn board_changed(&mut self) {
+        self.move_counter = self.move_counter.wrapping_add(1);
+    }
+
+    fn cached(&self) -> Option<&StoneLists> {
+        match self.stones {
+            Some((counter, ref stones)) if counter == self.move_counter => Some(stones),
+            _ => None,
+        }
+    }
+
+    pub fn stones(&mut self, ctrl: &mut Engine) -> Result<StoneLists, GtpError> {
+        if let Some(stones) = self.cached() {
+            info!("Using cached stones");
+            return Ok(stones.clone());
+        }
+        let stones = (list_stones(ctrl, "white")?, list_stones(ctrl, "black")?);
+        self.stones = Some((self.move_counter, stones.clone()));
+        Ok(stones)
+    }
+
+    /// Stones that have gone since they were last listed, e.g. captures, as 0-based points with
+    /// whether they're white. Lists them again, like `stones`
+    pub fn removed(&mut self, ctrl: &mut Engine) -> Result<Vec<(Point2<u8>, bool)>, GtpError> {
+        let before = self.stones.as_ref().map(|(_, stones)| stones.clone());
+        let after = self.stones(ctrl)?;
+        Ok(before.map_or_else(Vec::new, |before| removed_stones(&before, &after)))
+    }
+
+    /// Ignores anything cached, for when the board may have changed without `board_changed`
+    pub fn force_refresh(&mut self, ctrl: &mut Engine) -> Result<StoneLists, GtpError> {
+        self.stones = None;
+        self.stones(ctrl)
+    }
+}
+
+/// What the engine made of a `play`
+#[derive(Debug, PartialEq)]
+pub enum PlayResult {
+    Played,
+    /// Occupied, suicide or ko, which Gnu Go calls "illegal move"
+    Illegal,
+    /// Any other error, e.g. a point off the board, with what the engine said
+    Refused(String),
+}
+
+impl PlayResult {
+    pub fn played(&self) -> bool {
+        *self == PlayResult::Played
+    }
+
+    /// For replays, where anything but `Played` means the moves are wrong
+    fn or_error(self, command: String) -> Result<(), GtpError> {
+        match self {
+            PlayResult::Played => Ok(()),
+            PlayResult::Illegal => Err(GtpError::IllegalMove {
+                command,
+                response: "illegal move".to_string(),
+            }),
+            PlayResult::Refused(response) => Err(GtpError::Rejected { command, response }),
+        }
+    }
+}
+
+/// Success is an empty response, but Gnu Go sometimes has whitespace in it, so that's ignored
+fn parse_play_response(resp: &Response) -> PlayResult {
+    match resp {
+        Response::Result((_, text)) => {
+            if !text.trim().is_empty() {
+                warn!("Unexpected text after a move: '{text}'");
+            }
+            PlayResult::Played
+        }
+        Response::Error((_, text)) if text.trim() == "illegal move" => PlayResult::Illegal,
+        Response::Error((_, text)) => PlayResult::Refused(text.trim().to_string()),
+    }
+}
+
+/// Plays a move in a local game. `pos` is 0-based from the top-left of the board on screen, and
+/// goes to the engine as (x + 1, y + 1), so local games have GTP's A1 in the top-left corner and
+/// `list_stones` gives points back the same way up. Dragon Go Server games are the other way up,
+/// see `board::point_to_gtp`
+pub fn do_human_move(
+    ctrl: &mut Engine,
+    pos: Point2<u8>,
+    colour: &str,
+) -> Result<PlayResult, GtpError> {
     let start = Instant::now();
     let cmd = Command::new_with_args("play", |e| {
         e.s(colour)
@@ -67,34 +361,1148 @@ pub fn do_human_move(ctrl: &mut Engine, pos: Point2<u8>, colour: &str) -> bool {
     });
     info!("human: {}", cmd.to_string());
     ctrl.send(cmd);
-    let resp = get_response(ctrl);
+    let resp = get_response(ctrl)?;
     info!("human resp: '{}'", resp.text());
     let elapsed = start.elapsed();
     info!("human move elapsed: {:.2?}", elapsed);
-    return resp.text() == "";
+    Ok(parse_play_response(&resp))
+}
+
+pub fn play_pass(ctrl: &mut Engine, colour: &str) -> Result<PlayResult, GtpError> {
+    ctrl.send(Command::new_with_args("play", |e| {
+        e.s(colour).s("pass").list()
+    }));
+    let resp = get_response(ctrl)?;
+    info!("{colour} pass resp: '{}'", resp.text());
+    Ok(parse_play_response(&resp))
 }
 
-pub fn count_captures(ctrl: &mut Engine, colour: &str) -> usize {
+pub fn count_captures(ctrl: &mut Engine, colour: &str) -> Result<usize, GtpError> {
     let start = Instant::now();
     let cmd = Command::new_with_args("captures", |e| e.s(colour));
     info!("captures: {}", cmd.to_string());
-    ctrl.send(cmd);
-    let resp = get_response(ctrl);
-    info!("captures resp: '{}'", resp.text());
+    let text = run(ctrl, cmd)?;
+    info!("captures resp: '{text}'");
     let elapsed = start.elapsed();
     info!("count captures elapsed: {:.2?}", elapsed);
-    resp.text().parse::<usize>().unwrap()
+    parse_captures(&text)
+}
+
+fn parse_captures(text: &str) -> Result<usize, GtpError> {
+    text.trim().parse::<usize>().map_err(|err| {
+        warn!("Can't parse captures response '{text}': {err}");
+        GtpError::Parse {
+            command: "captures".to_string(),
+            response: text.to_string(),
+        }
+    })
+}
+
+/// Sets GnuGo's playing strength, which only affects the moves it makes from now on, so it can
+/// change mid-game without touching the board. `false` for engines without `level`
+pub fn set_level(ctrl: &mut Engine, level: u8) -> Result<bool, GtpError> {
+    match run(ctrl, Command::new_with_args("level", |e| e.i(level as u32))) {
+        Ok(_) => Ok(true),
+        Err(GtpError::Rejected { response, .. }) if response.contains("unknown command") => {
+            warn!("No level command, so the machine plays at the engine's own level");
+            Ok(false)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+pub fn set_komi(ctrl: &mut Engine, komi: f64) -> Result<(), GtpError> {
+    run(ctrl, Command::new_with_args("komi", |e| e.f(komi as f32)))?;
+    Ok(())
+}
+
+/// Puts a position's stones (in GTP coordinates, like `list_stones`) onto an empty board.
+/// `progress` gets told how many have been placed out of how many, as this is slow on big boards.
+/// Order doesn't matter for a legal position, as no part of a group can run out of liberties
+/// before the whole group is there
+pub fn replay_position(
+    ctrl: &mut Engine,
+    board_size: u8,
+    black: &[Point2<u8>],
+    white: &[Point2<u8>],
+    mut progress: impl FnMut(usize, usize),
+) -> Result<(), GtpError> {
+    let start = Instant::now();
+    set_board_size(ctrl, board_size)?;
+    clear_board(ctrl)?;
+    let total = black.len() + white.len();
+    let stones = black
+        .iter()
+        .map(|p| ("black", p))
+        .chain(white.iter().map(|p| ("white", p)));
+    for (index, (colour, point)) in stones.enumerate() {
+        // do_human_move wants 0-based points
+        let local = Point2 {
+            x: point.x - 1,
+            y: point.y - 1,
+        };
+        do_human_move(ctrl, local, colour)?.or_error(format!(
+            "play {colour} {}",
+            gtp_vertex(*point).unwrap_or_else(|| format!("{point:?}"))
+        ))?;
+        progress(index + 1, total);
+    }
+    info!("replay of {total} stones elapsed: {:.2?}", start.elapsed());
+    Ok(())
 }
 
-pub fn clear_board(ctrl: &mut Engine) {
-    ctrl.send(Command::new_with_args("clear_board", |e| e));
-    let resp = get_response(ctrl);
-    info!("clear_board: {}", resp.text());
+/// Plays a whole game's moves (0-based, with `None` for a pass) onto an empty board, so captures
+/// and whose turn it is come out the same as when they were played
+pub fn replay_moves(
+    ctrl: &mut Engine,
+    board_size: u8,
+    moves: &[(Stone, Option<Point2<u8>>)],
+) -> Result<(), GtpError> {
+    let start = Instant::now();
+    set_board_size(ctrl, board_size)?;
+    clear_board(ctrl)?;
+    for (colour, point) in moves {
+        let played = match point {
+            Some(point) => do_human_move(ctrl, *point, colour.gtp_name())?,
+            None => play_pass(ctrl, colour.gtp_name())?,
+        };
+        played.or_error(format!("play {} {point:?}", colour.gtp_name()))?;
+    }
+    info!(
+        "replay of {} moves elapsed: {:.2?}",
+        moves.len(),
+        start.elapsed()
+    );
+    Ok(())
+}
+
+/// Sets the engine up with the final position of an SGF, including komi, and says whose move it
+/// is. Engines without `loadsgf` get the stones replayed instead, with `progress` as for
+/// `replay_position`
+pub fn load_sgf(
+    ctrl: &mut Engine,
+    contents: &str,
+    progress: impl FnMut(usize, usize),
+) -> Result<Stone, GtpError> {
+    let start = Instant::now();
+    // loadsgf only takes a filename
+    let path = env::temp_dir().join("fuseki-loadsgf.sgf");
+    // As good as the engine turning it down, as it never gets to see the game
+    fs::write(&path, contents).map_err(|err| GtpError::Rejected {
+        command: "loadsgf".to_string(),
+        response: format!("can't write {}: {err}", path.display()),
+    })?;
+    let cmd = Command::new_with_args("loadsgf", |e| e.s(&path.to_string_lossy()));
+    info!("loadsgf: {}", cmd.to_string());
+    ctrl.send(cmd);
+    let resp = get_response(ctrl)?;
+    info!("loadsgf resp: '{}'", resp.text());
+    let to_play = match resp {
+        Response::Result((_, text)) => parse_colour(&text).ok_or_else(|| GtpError::Parse {
+            command: "loadsgf".to_string(),
+            response: text,
+        })?,
+        Response::Error((_, text)) if text.contains("unknown command") => {
+            warn!("No loadsgf, so replaying instead");
+            replay_sgf(ctrl, contents, progress)?
+        }
+        Response::Error((_, text)) => {
+            return Err(GtpError::Rejected {
+                command: "loadsgf".to_string(),
+                response: text,
+            });
+        }
+    };
+    info!("load sgf elapsed: {:.2?}", start.elapsed());
+    Ok(to_play)
+}
+
+fn replay_sgf(
+    ctrl: &mut Engine,
+    contents: &str,
+    progress: impl FnMut(usize, usize),
+) -> Result<Stone, GtpError> {
+    let game_data = get_game_data(contents).map_err(|err| GtpError::Parse {
+        command: "loadsgf replay".to_string(),
+        response: err.to_string(),
+    })?;
+    set_komi(ctrl, game_data.komi)?;
+    // The SGF's rows count down from the top, but GTP's count up from the bottom, so this comes
+    // out the same way up as `loadsgf` has it
+    let size = game_data.size;
+    let flip = |stones: &[Point2<u8>]| -> Vec<Point2<u8>> {
+        stones
+            .iter()
+            .map(|point| Point2 {
+                x: point.x,
+                y: size + 1 - point.y,
+            })
+            .collect()
+    };
+    replay_position(
+        ctrl,
+        size,
+        &flip(&game_data.black_stones),
+        &flip(&game_data.white_stones),
+        progress,
+    )?;
+    Ok(game_data.to_play)
+}
+
+/// Sets the engine up with an SGF's game (see `load_sgf`) and has `colour` play `point` on it,
+/// so the answer says whether the move's legal there, ko included. `point` is 0-based from the
+/// top-left like the SGF, so it goes as the same vertex Dragon Go Server gets
+pub fn try_sgf_move(
+    ctrl: &mut Engine,
+    contents: &str,
+    point: Point2<u8>,
+    board_size: u8,
+    colour: Stone,
+) -> Result<PlayResult, GtpError> {
+    let vertex = point_to_gtp(point, board_size).ok_or_else(|| GtpError::Rejected {
+        command: "play".to_string(),
+        response: format!("{point:?} is off the board"),
+    })?;
+    load_sgf(ctrl, contents, |_, _| {})?;
+    let cmd = Command::new_with_args("play", |e| e.s(colour.gtp_name()).s(&vertex).list());
+    info!("sgf move: {}", cmd.to_string());
+    ctrl.send(cmd);
+    let resp = get_response(ctrl)?;
+    info!("sgf move resp: '{}'", resp.text());
+    Ok(parse_play_response(&resp))
+}
+
+/// "black" or "white", as `loadsgf` says whose move it is
+fn parse_colour(text: &str) -> Option<Stone> {
+    match text.trim().to_lowercase().as_str() {
+        "black" | "b" => Some(Stone::Black),
+        "white" | "w" => Some(Stone::White),
+        _ => None,
+    }
+}
+
+/// GnuGo's guess at the score, e.g. "W+15.5 (upper bound: -14.5, lower: -16.5)"
+pub fn estimate_score(ctrl: &mut Engine) -> Result<String, GtpError> {
+    let start = Instant::now();
+    let text = run_with_timeout(
+        ctrl,
+        Command::new_with_args("estimate_score", |e| e),
+        SUGGEST_MOVE_TIMEOUT,
+    )?;
+    info!("estimate_score: '{text}'");
+    info!("estimate score elapsed: {:.2?}", start.elapsed());
+    Ok(text)
 }
 
-pub fn undo_move(ctrl: &mut Engine) -> bool {
+/// Just the score from `estimate_score`, without the bounds
+pub fn parse_estimate(text: &str) -> Option<GameResult> {
+    parse_final_score(text.split_whitespace().next()?)
+}
+
+/// All in seconds. A `byo_yomi_stones` of 0 with some `byo_yomi_time` means no time limit at all
+pub fn set_time_settings(
+    ctrl: &mut Engine,
+    main_time: u32,
+    byo_yomi_time: u32,
+    byo_yomi_stones: u32,
+) -> Result<(), GtpError> {
+    let cmd = Command::new_with_args("time_settings", |e| {
+        e.i(main_time).i(byo_yomi_time).i(byo_yomi_stones).list()
+    });
+    info!("time_settings: {}", cmd.to_string());
+    let text = run(ctrl, cmd)?;
+    info!("time_settings resp: '{text}'");
+    Ok(())
+}
+
+pub fn clear_board(ctrl: &mut Engine) -> Result<(), GtpError> {
+    let text = run(ctrl, Command::new_with_args("clear_board", |e| e))?;
+    info!("clear_board: {text}");
+    Ok(())
+}
+
+pub fn undo_move(ctrl: &mut Engine) -> Result<bool, GtpError> {
     ctrl.send(Command::new_with_args("undo", |e| e));
-    let resp = get_response(ctrl);
+    let resp = get_response(ctrl)?;
     info!("undo: {}", resp.text());
-    resp.text().is_empty()
+    Ok(resp.text().is_empty())
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Winner {
+    Black,
+    White,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum GameResult {
+    Win {
+        winner: Winner,
+        margin: f32,
+    },
+    /// Jigo, which can only happen with a whole number komi
+    Draw,
+}
+
+impl GameResult {
+    /// For an SGF's `RE`, which has "0" for a draw
+    pub fn sgf_result(&self) -> String {
+        match self {
+            GameResult::Win {
+                winner: Winner::Black,
+                margin,
+            } => format!("B+{margin}"),
+            GameResult::Win {
+                winner: Winner::White,
+                margin,
+            } => format!("W+{margin}"),
+            GameResult::Draw => "0".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameResult::Win {
+                winner: Winner::Black,
+                margin,
+            } => write!(f, "Black +{margin}"),
+            GameResult::Win {
+                winner: Winner::White,
+                margin,
+            } => write!(f, "White +{margin}"),
+            GameResult::Draw => write!(f, "Draw (jigo)"),
+        }
+    }
+}
+
+pub struct AreaScore {
+    pub score: Option<GameResult>,
+    pub raw_score: String,
+    /// In GTP coordinates, like `list_stones`
+    pub dead_stones: Option<Vec<Point2<u8>>>,
+    pub raw_dead_stones: String,
+}
+
+/// Parses `final_score` output like "W+3.5" or "B+12.0", or "0" for a draw (which some engines
+/// give as "W+0" or "B+0" instead)
+pub fn parse_final_score(text: &str) -> Option<GameResult> {
+    let text = text.trim();
+    if text == "0" {
+        return Some(GameResult::Draw);
+    }
+    let (winner, margin) = text.split_once('+')?;
+    let winner = match winner {
+        "B" => Winner::Black,
+        "W" => Winner::White,
+        _ => return None,
+    };
+    let margin: f32 = margin.parse().ok()?;
+    if margin == 0.0 {
+        return Some(GameResult::Draw);
+    }
+    Some(GameResult::Win { winner, margin })
+}
+
+fn parse_vertex(vertex: &str) -> Option<Point2<u8>> {
+    let vertex = vertex.to_uppercase();
+    let column = vertex.chars().next()?;
+    if !column.is_ascii_uppercase() || column == 'I' {
+        return None;
+    }
+    // No 'I' column in GTP
+    let x = column as u8 - b'A' + if column > 'I' { 0 } else { 1 };
+    let y = vertex[1..].parse::<u8>().ok()?;
+    Some(Point2 { x, y })
+}
+
+/// Parses a vertex list that may be split over several lines, as `final_status_list` does
+pub fn parse_vertices(text: &str) -> Option<Vec<Point2<u8>>> {
+    text.split_whitespace().map(parse_vertex).collect()
+}
+
+/// Stones from the ASCII drawing `showboard` gives, in GTP coordinates like `list_stones`
+#[derive(Debug, PartialEq, Default)]
+pub struct ShownBoard {
+    pub black: Vec<Point2<u8>>,
+    pub white: Vec<Point2<u8>>,
+}
+
+/// Parses GnuGo's `showboard` output, which looks like
+/// ```text
+///    A B C D E
+///  5 . . . . . 5
+///  4 . X . . . 4     WHITE (O) has captured 0 stones
+///  3 . . + O . 3     BLACK (X) has captured 0 stones
+///  2 . . . . . 2
+///  1 . . . . . 1
+///    A B C D E
+/// ```
+/// Anything after the row's closing number is ignored. `None` if it doesn't look like a board
+pub fn parse_showboard(text: &str) -> Option<ShownBoard> {
+    let mut columns: Option<Vec<Point2<u8>>> = None;
+    let mut rows_seen = 0;
+    let mut board = ShownBoard::default();
+    for line in text.lines() {
+        // Some versions mark the last move with brackets, e.g. "(X)"
+        let line = line.replace(['(', ')'], " ");
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let Some(first) = tokens.first() else {
+            continue;
+        };
+        if columns.is_none() {
+            // Header of column letters, which we turn into x coordinates
+            if tokens.iter().all(|t| t.len() == 1) {
+                columns = tokens
+                    .iter()
+                    .map(|t| parse_vertex(&format!("{t}1")))
+                    .collect();
+                columns.as_ref()?;
+            }
+            continue;
+        }
+        let columns = columns.as_ref()?;
+        let Ok(y) = first.parse::<u8>() else {
+            // The footer of column letters again
+            continue;
+        };
+        if tokens.len() < columns.len() + 1 {
+            return None;
+        }
+        for (column, token) in columns.iter().zip(&tokens[1..]) {
+            let point = Point2 { x: column.x, y };
+            match *token {
+                "X" => board.black.push(point),
+                "O" => board.white.push(point),
+                // '+' is a star point
+                "." | "+" => {}
+                _ => return None,
+            }
+        }
+        rows_seen += 1;
+    }
+    if rows_seen == 0 || rows_seen != columns?.len() {
+        return None;
+    }
+    Some(board)
+}
+
+pub fn show_board(ctrl: &mut Engine) -> Result<ShownBoard, GtpError> {
+    let text = run(ctrl, Command::new_with_args("showboard", |e| e))?;
+    info!("showboard: {text}");
+    parse_showboard(&text).ok_or_else(|| {
+        warn!("Can't parse showboard response '{text}'");
+        GtpError::Parse {
+            command: "showboard".to_string(),
+            response: text,
+        }
+    })
+}
+
+fn compare_stones(
+    colour: &str,
+    shown: &[Point2<u8>],
+    listed: &[Point2<u8>],
+    problems: &mut Vec<String>,
+) {
+    for point in shown.iter().filter(|p| !listed.contains(p)) {
+        problems.push(format!(
+            "{colour} {} in showboard but not list_stones",
+            gtp_vertex(*point).unwrap_or_else(|| format!("{point:?}"))
+        ));
+    }
+    for point in listed.iter().filter(|p| !shown.contains(p)) {
+        problems.push(format!(
+            "{colour} {} in list_stones but not showboard",
+            gtp_vertex(*point).unwrap_or_else(|| format!("{point:?}"))
+        ));
+    }
+}
+
+/// Differences between the engine's drawing of the board and the stones we draw from
+pub fn board_differences(
+    shown: &ShownBoard,
+    black: &[Point2<u8>],
+    white: &[Point2<u8>],
+) -> Vec<String> {
+    let mut problems = vec![];
+    compare_stones("Black", &shown.black, black, &mut problems);
+    compare_stones("White", &shown.white, white, &mut problems);
+    problems
+}
+
+/// Checks `showboard` against `list_stones`, logging any differences, which are also returned
+pub fn verify_board(ctrl: &mut Engine) -> Result<Vec<String>, GtpError> {
+    let shown = show_board(ctrl)?;
+    let black = list_stones(ctrl, "black")?;
+    let white = list_stones(ctrl, "white")?;
+    let problems = board_differences(&shown, &black, &white);
+    if problems.is_empty() {
+        info!("showboard agrees with list_stones");
+    }
+    for problem in &problems {
+        warn!("Board mismatch: {problem}");
+    }
+    Ok(problems)
+}
+
+/// Asks the engine what it'd play for `colour`, without playing it. `None` means it'd pass
+pub fn suggest_move(ctrl: &mut Engine, colour: &str) -> Result<Option<Point2<u8>>, GtpError> {
+    let start = Instant::now();
+    let text = run_with_timeout(
+        ctrl,
+        Command::new_with_args("reg_genmove", |e| e.s(colour)),
+        SUGGEST_MOVE_TIMEOUT,
+    )?;
+    info!("reg_genmove {colour}: '{text}'");
+    let elapsed = start.elapsed();
+    info!("suggest move elapsed: {:.2?}", elapsed);
+    parse_move(&text)
+}
+
+/// Plays a move for `colour` that captures dead stones rather than passing while there are any,
+/// using GnuGo's `kgs-genmove_cleanup`, or plain `genmove` for engines without it. `None` is a pass
+pub fn genmove_cleanup(ctrl: &mut Engine, colour: &str) -> Result<Option<Point2<u8>>, GtpError> {
+    let text = match run_with_timeout(
+        ctrl,
+        Command::new_with_args("kgs-genmove_cleanup", |e| e.s(colour)),
+        SUGGEST_MOVE_TIMEOUT,
+    ) {
+        Ok(text) => text,
+        Err(GtpError::Rejected { response, .. }) if response.contains("unknown command") => {
+            warn!("No kgs-genmove_cleanup, so using genmove");
+            run_with_timeout(
+                ctrl,
+                Command::new_with_args("genmove", |e| e.s(colour)),
+                SUGGEST_MOVE_TIMEOUT,
+            )?
+        }
+        Err(err) => return Err(err),
+    };
+    info!("genmove_cleanup {colour}: '{text}'");
+    parse_move(&text)
+}
+
+/// Has the engine play both sides with `genmove_cleanup`, starting with `first`, until they both
+/// pass, so dead stones are off the board before scoring. Gives up after `max_moves` in case they
+/// never do, and returns how many moves got played. `progress` gets that count after each one
+pub fn cleanup_dead_stones(
+    ctrl: &mut Engine,
+    first: Stone,
+    max_moves: usize,
+    mut progress: impl FnMut(usize),
+) -> Result<usize, GtpError> {
+    let start = Instant::now();
+    let mut colour = first;
+    let mut passes = 0;
+    let mut played = 0;
+    while passes < 2 && played < max_moves {
+        match genmove_cleanup(ctrl, colour.gtp_name())? {
+            Some(_) => {
+                passes = 0;
+                played += 1;
+                progress(played);
+            }
+            None => passes += 1,
+        }
+        colour = colour.other();
+    }
+    info!("cleanup of {played} moves elapsed: {:.2?}", start.elapsed());
+    Ok(played)
+}
+
+/// GnuGo's idea of who controls each point, from `initial_influence`, with `to_move` being whose
+/// turn it is. Row-major from the top-left, like the board, and see `parse_influence` for the values
+pub fn influence_regions(
+    ctrl: &mut Engine,
+    to_move: Stone,
+    board_size: u8,
+) -> Result<Vec<i8>, GtpError> {
+    let start = Instant::now();
+    let text = run(
+        ctrl,
+        Command::new_with_args("initial_influence", |e| {
+            e.s(to_move.gtp_name()).s("influence_regions").list()
+        }),
+    )?;
+    info!("influence elapsed: {:.2?}", start.elapsed());
+    parse_influence(&text, board_size).ok_or_else(|| GtpError::Parse {
+        command: "initial_influence".to_string(),
+        response: text,
+    })
+}
+
+/// One number per point, a row per line with the top row first. 4 is a white stone, 3 white
+/// territory, 2 white moyo and 1 white area, then 0 for neutral and the same again negative for
+/// black
+pub fn parse_influence(text: &str, board_size: u8) -> Option<Vec<i8>> {
+    let values = text
+        .split_whitespace()
+        .map(|value| value.parse::<i8>().ok().filter(|v| (-4..=4).contains(v)))
+        .collect::<Option<Vec<i8>>>()?;
+    if values.len() != board_size as usize * board_size as usize {
+        warn!(
+            "Expected {board_size}x{board_size} influence values, got {}",
+            values.len()
+        );
+        return None;
+    }
+    Some(values)
+}
+
+/// What the engine did with a `genmove`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EngineMove {
+    /// GTP coordinates
+    Play(Point2<u8>),
+    Pass,
+    Resign,
+}
+
+/// Parses a `genmove`-style answer. `None` means a pass (or resignation)
+pub fn parse_move(text: &str) -> Result<Option<Point2<u8>>, GtpError> {
+    Ok(match parse_engine_move(text)? {
+        EngineMove::Play(point) => Some(point),
+        EngineMove::Pass | EngineMove::Resign => None,
+    })
+}
+
+/// Parses a `genmove` answer, keeping passing and resigning apart
+pub fn parse_engine_move(text: &str) -> Result<EngineMove, GtpError> {
+    let text = text.trim();
+    if text.eq_ignore_ascii_case("pass") {
+        return Ok(EngineMove::Pass);
+    }
+    if text.eq_ignore_ascii_case("resign") {
+        return Ok(EngineMove::Resign);
+    }
+    match parse_vertex(text) {
+        Some(point) => Ok(EngineMove::Play(point)),
+        None => {
+            warn!("Can't parse genmove response '{text}'");
+            Err(GtpError::Parse {
+                command: "genmove".to_string(),
+                response: text.to_string(),
+            })
+        }
+    }
+}
+
+/// GTP points `colour` could play, from `all_legal`, which rules out suicide and ko. `None` for
+/// engines without it
+pub fn legal_moves(
+    ctrl: &mut Engine,
+    colour: &str,
+) -> Result<Option<HashSet<Point2<u8>>>, GtpError> {
+    let start = Instant::now();
+    let text = match run(ctrl, Command::new_with_args("all_legal", |e| e.s(colour))) {
+        Ok(text) => text,
+        Err(GtpError::Rejected { response, .. }) if response.contains("unknown command") => {
+            warn!("No all_legal, so no legal moves to show");
+            return Ok(None);
+        }
+        Err(err) => return Err(err),
+    };
+    info!("all_legal elapsed: {:.2?}", start.elapsed());
+    match parse_vertices(&text) {
+        Some(points) => Ok(Some(points.into_iter().collect())),
+        None => Err(GtpError::Parse {
+            command: format!("all_legal {colour}"),
+            response: text,
+        }),
+    }
+}
+
+/// GnuGo's verdict on a group, from `final_status_list`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupStatus {
+    Alive,
+    Dead,
+    Seki,
+}
+
+impl GroupStatus {
+    const ALL: [GroupStatus; 3] = [GroupStatus::Alive, GroupStatus::Dead, GroupStatus::Seki];
+
+    fn gtp_name(self) -> &'static str {
+        match self {
+            GroupStatus::Alive => "alive",
+            GroupStatus::Dead => "dead",
+            GroupStatus::Seki => "seki",
+        }
+    }
+}
+
+/// The stones in one `final_status_list` answer
+fn parse_status_list(status: GroupStatus, text: &str) -> Result<HashSet<Point2<u8>>, GtpError> {
+    match parse_vertices(text) {
+        Some(points) => Ok(points.into_iter().collect()),
+        None => Err(GtpError::Parse {
+            command: format!("final_status_list {}", status.gtp_name()),
+            response: text.to_string(),
+        }),
+    }
+}
+
+/// Whether the group with a stone on the GTP `point` is alive, dead or in seki. `None` if
+/// there's no stone there
+pub fn group_status(ctrl: &mut Engine, point: Point2<u8>) -> Result<Option<GroupStatus>, GtpError> {
+    let start = Instant::now();
+    for status in GroupStatus::ALL {
+        let text = run_with_timeout(
+            ctrl,
+            Command::new_with_args("final_status_list", |e| e.s(status.gtp_name())),
+            SUGGEST_MOVE_TIMEOUT,
+        )?;
+        if parse_status_list(status, &text)?.contains(&point) {
+            info!("group status elapsed: {:.2?}", start.elapsed());
+            return Ok(Some(status));
+        }
+    }
+    Ok(None)
+}
+
+pub fn area_score(ctrl: &mut Engine) -> Result<AreaScore, GtpError> {
+    let start = Instant::now();
+    ctrl.send(Command::new_with_args("final_status_list", |e| e.s("dead")));
+    let dead_resp = get_response(ctrl)?;
+    info!("final_status_list dead: '{}'", dead_resp.text());
+    ctrl.send(Command::new_with_args("final_score", |e| e));
+    let score_resp = get_response(ctrl)?;
+    info!("final_score: '{}'", score_resp.text());
+    let elapsed = start.elapsed();
+    info!("area score elapsed: {:.2?}", elapsed);
+    Ok(AreaScore {
+        score: parse_final_score(&score_resp.text()),
+        raw_score: score_resp.text(),
+        dead_stones: parse_vertices(&dead_resp.text()),
+        raw_dead_stones: dead_resp.text(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use libremarkable::cgmath::Point2;
+    use pretty_assertions::assert_eq;
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        sync::{Arc, Mutex},
+        thread::spawn,
+    };
+
+    use gtp::Response;
+
+    use crate::{
+        board::Stone,
+        engine::{Engine, RemoteEngine},
+        gtp::{
+            board_differences, cleanup_dead_stones, genmove_cleanup, group_status, legal_moves,
+            parse_captures, parse_colour, parse_engine_move, parse_estimate, parse_final_score,
+            parse_influence, parse_move, parse_play_response, parse_showboard, parse_vertices,
+            set_board_size, set_level, set_supported_board_size, try_sgf_move, EngineMove,
+            GameResult, GroupStatus, GtpError, PlayResult, ShownBoard, StoneCache, Winner,
+        },
+    };
+
+    const SHOWBOARD: &str = "
+   A B C D E F G H J
+ 9 . . . . . . . . . 9
+ 8 . . . . . . . . . 8
+ 7 . . + . . . + . . 7     WHITE (O) has captured 1 stones
+ 6 . . . . . . . . . 6     BLACK (X) has captured 0 stones
+ 5 . . . . X . . . . 5
+ 4 . . . . . . . . . 4
+ 3 . . + . . . O . . 3
+ 2 X . . . . . . . . 2
+ 1 . X . . . . . . J 1
+   A B C D E F G H J
+";
+
+    #[test]
+    fn suggestion() {
+        assert_eq!(Some(Point2 { x: 3, y: 4 }), parse_move("C4").unwrap());
+        assert_eq!(None, parse_move("PASS").unwrap());
+        assert!(parse_move("? illegal").is_err());
+    }
+
+    #[test]
+    fn engine_moves() {
+        assert_eq!(
+            EngineMove::Play(Point2 { x: 9, y: 9 }),
+            parse_engine_move(" J9\n").unwrap()
+        );
+        assert_eq!(EngineMove::Pass, parse_engine_move("pass").unwrap());
+        assert_eq!(EngineMove::Pass, parse_engine_move("PASS ").unwrap());
+        assert_eq!(EngineMove::Resign, parse_engine_move("resign").unwrap());
+        assert_eq!(EngineMove::Resign, parse_engine_move("Resign").unwrap());
+        assert!(parse_engine_move("").is_err());
+        assert!(parse_engine_move("passed").is_err());
+    }
+
+    #[test]
+    fn captures_count() {
+        assert_eq!(3, parse_captures("3").unwrap());
+    }
+
+    #[test]
+    fn captures_garbage() {
+        assert!(matches!(
+            parse_captures("invalid color"),
+            Err(GtpError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn loadsgf_colour() {
+        assert_eq!(Some(Stone::White), parse_colour("white"));
+        assert_eq!(Some(Stone::Black), parse_colour("black\n"));
+        assert_eq!(None, parse_colour("cannot open or parse 'x.sgf'"));
+    }
+
+    #[test]
+    fn final_score_white() {
+        assert_eq!(
+            Some(GameResult::Win {
+                winner: Winner::White,
+                margin: 3.5
+            }),
+            parse_final_score("W+3.5")
+        );
+    }
+
+    #[test]
+    fn final_score_black() {
+        assert_eq!(
+            Some(GameResult::Win {
+                winner: Winner::Black,
+                margin: 12.0
+            }),
+            parse_final_score("B+12.0\n")
+        );
+    }
+
+    #[test]
+    fn estimate() {
+        assert_eq!(
+            Some(GameResult::Win {
+                winner: Winner::White,
+                margin: 15.5
+            }),
+            parse_estimate("W+15.5 (upper bound: -14.5, lower: -16.5)")
+        );
+        assert_eq!(None, parse_estimate(""));
+    }
+
+    #[test]
+    fn final_score_draw() {
+        assert_eq!(Some(GameResult::Draw), parse_final_score("0"));
+        assert_eq!(Some(GameResult::Draw), parse_final_score("W+0\n"));
+        assert_eq!(Some(GameResult::Draw), parse_final_score("B+0.0"));
+        assert_eq!(
+            Some(GameResult::Win {
+                winner: Winner::Black,
+                margin: 0.5
+            }),
+            parse_final_score("B+0.5")
+        );
+        assert_eq!("Draw (jigo)", GameResult::Draw.to_string());
+        assert_eq!("0", GameResult::Draw.sgf_result());
+    }
+
+    #[test]
+    fn final_score_garbage() {
+        assert_eq!(None, parse_final_score("cannot score"));
+        assert_eq!(None, parse_final_score("X+1"));
+    }
+
+    #[test]
+    fn vertices_multiline() {
+        assert_eq!(
+            Some(vec![
+                Point2 { x: 1, y: 1 },
+                Point2 { x: 8, y: 9 },
+                Point2 { x: 9, y: 2 },
+                Point2 { x: 19, y: 19 }
+            ]),
+            parse_vertices("A1 H9\nJ2\nT19\n")
+        );
+    }
+
+    #[test]
+    fn vertices_empty() {
+        assert_eq!(Some(vec![]), parse_vertices(""));
+    }
+
+    #[test]
+    fn vertices_bad() {
+        assert_eq!(None, parse_vertices("A1 I3"));
+        assert_eq!(None, parse_vertices("A1 ??"));
+    }
+
+    #[test]
+    fn showboard() {
+        let shown = parse_showboard(&SHOWBOARD.replace(" J 1", " . 1")).unwrap();
+        assert_eq!(
+            ShownBoard {
+                black: vec![
+                    Point2 { x: 5, y: 5 },
+                    Point2 { x: 1, y: 2 },
+                    Point2 { x: 2, y: 1 }
+                ],
+                white: vec![Point2 { x: 7, y: 3 }],
+            },
+            shown
+        );
+    }
+
+    #[test]
+    fn showboard_last_move_marker() {
+        let shown =
+            parse_showboard(&SHOWBOARD.replace(" J 1", " . 1").replace(" O . ", "(O). ")).unwrap();
+        assert_eq!(vec![Point2 { x: 7, y: 3 }], shown.white);
+    }
+
+    #[test]
+    fn showboard_bad() {
+        assert_eq!(None, parse_showboard(SHOWBOARD));
+        assert_eq!(None, parse_showboard(""));
+        assert_eq!(
+            None,
+            parse_showboard(&SHOWBOARD.replace(" 4 . . . . . . . . . 4\n", ""))
+        );
+    }
+
+    #[test]
+    fn differences() {
+        let shown = ShownBoard {
+            black: vec![Point2 { x: 1, y: 2 }, Point2 { x: 2, y: 1 }],
+            white: vec![],
+        };
+        assert_eq!(
+            Vec::<String>::new(),
+            board_differences(&shown, &[Point2 { x: 2, y: 1 }, Point2 { x: 1, y: 2 }], &[])
+        );
+        assert_eq!(
+            vec![
+                "Black A2 in showboard but not list_stones".to_string(),
+                "White A1 in list_stones but not showboard".to_string()
+            ],
+            board_differences(&shown, &[Point2 { x: 2, y: 1 }], &[Point2 { x: 1, y: 1 }])
+        );
+    }
+
+    #[test]
+    fn stone_cache() {
+        let mut cache = StoneCache::default();
+        assert_eq!(None, cache.cached());
+        let stones = (vec![Point2 { x: 1, y: 1 }], vec![Point2 { x: 2, y: 2 }]);
+        cache.stones = Some((0, stones.clone()));
+        assert_eq!(Some(&stones), cache.cached());
+        cache.board_changed();
+        assert_eq!(None, cache.cached());
+    }
+
+    #[test]
+    fn influence() {
+        // GnuGo pads the numbers out into columns
+        let text =
+            " 4  3  0 -1 -2\n 3  3  0 -2 -4\n 2  1  0 -3 -3\n 1  0  0 -3 -4\n 0  0  0 -2 -3\n";
+        let values = parse_influence(text, 5).unwrap();
+        assert_eq!(25, values.len());
+        assert_eq!(&[4, 3, 0, -1, -2], &values[..5]);
+        assert_eq!(-3, values[24]);
+    }
+
+    #[test]
+    fn bad_influence() {
+        assert_eq!(None, parse_influence("0 0 0", 5));
+        assert_eq!(None, parse_influence("0.5 1.2 3.0 4.0", 2));
+        assert_eq!(None, parse_influence("0 0 0 9", 2));
+    }
+
+    /// A remote engine that gives `answers` to the commands it gets, in order
+    fn engine_answering(answers: &'static [&'static str]) -> Engine {
+        engine_recording(answers).0
+    }
+
+    /// `engine_answering`, along with the commands it got so far
+    fn engine_recording(answers: &'static [&'static str]) -> (Engine, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let received = Arc::new(Mutex::new(vec![]));
+        let recorded = received.clone();
+        spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            for answer in answers {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                recorded.lock().unwrap().push(line.trim().to_string());
+                stream.write_all(answer.as_bytes()).unwrap();
+            }
+            // Kept open until the engine's dropped, so it doesn't see a disconnect
+            let _ = reader.read_line(&mut String::new());
+        });
+        let mut engine = Engine::Remote(RemoteEngine::new(&address));
+        engine.start().unwrap();
+        (engine, received)
+    }
+
+    #[test]
+    fn sgf_move_same_way_up_as_dgs() {
+        let (mut engine, received) = engine_recording(&["=1 black\n\n", "=2 \n\n"]);
+        let played = try_sgf_move(
+            &mut engine,
+            "(;GM[1]SZ[9])",
+            Point2 { x: 2, y: 1 },
+            9,
+            Stone::Black,
+        )
+        .unwrap();
+        assert_eq!(PlayResult::Played, played);
+        let received = received.lock().unwrap();
+        assert!(received[0].contains("loadsgf"), "{received:?}");
+        // Second row down on a 9x9 is row 8, not 2
+        assert_eq!("2 play black C8", received[1]);
+    }
+
+    #[test]
+    fn error_answer_is_rejected() {
+        let mut engine = engine_answering(&["?1 unacceptable size\n\n"]);
+        let err = set_board_size(&mut engine, 42).unwrap_err();
+        match err {
+            GtpError::Rejected {
+                ref command,
+                ref response,
+            } => {
+                assert!(command.ends_with("boardsize 42"), "{command}");
+                assert_eq!("unacceptable size", response);
+            }
+            ref other => panic!("Expected rejection, got {other:?}"),
+        }
+        assert_eq!("Engine refused", err.status_text());
+    }
+
+    #[test]
+    fn board_size_fallback() {
+        let mut engine = engine_answering(&["?1 unacceptable size\n\n", "=2 \n\n"]);
+        assert_eq!(9, set_supported_board_size(&mut engine, 17).unwrap());
+    }
+
+    #[test]
+    fn board_size_no_fallback() {
+        let mut engine = engine_answering(&[
+            "?1 unacceptable size\n\n",
+            "?2 unacceptable size\n\n",
+            "?3 unacceptable size\n\n",
+            "?4 unacceptable size\n\n",
+        ]);
+        let err = set_supported_board_size(&mut engine, 17).unwrap_err();
+        // The error's about the size that was asked for
+        assert!(
+            matches!(err, GtpError::Rejected { ref command, .. } if command.ends_with("boardsize 17")),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn success_answer() {
+        let mut engine = engine_answering(&["=1 \n\n"]);
+        set_board_size(&mut engine, 9).unwrap();
+    }
+
+    #[test]
+    fn play_responses() {
+        let played = |text: &str| parse_play_response(&Response::Result((None, text.to_string())));
+        assert_eq!(PlayResult::Played, played(""));
+        assert_eq!(PlayResult::Played, played(" "));
+        assert_eq!(PlayResult::Played, played("\n"));
+        let error = |text: &str| parse_play_response(&Response::Error((None, text.to_string())));
+        assert_eq!(PlayResult::Illegal, error("illegal move"));
+        assert_eq!(PlayResult::Illegal, error("illegal move "));
+        assert_eq!(
+            PlayResult::Refused("invalid color or coordinate".to_string()),
+            error("invalid color or coordinate")
+        );
+        assert!(!error("illegal move").played());
+        assert!(played(" ").played());
+    }
+
+    #[test]
+    fn cleanup_falls_back_to_genmove() {
+        let mut engine = engine_answering(&["?1 unknown command\n\n", "=2 C3\n\n"]);
+        assert_eq!(
+            Some(Point2 { x: 3, y: 3 }),
+            genmove_cleanup(&mut engine, "black").unwrap()
+        );
+    }
+
+    #[test]
+    fn cleanup_until_both_pass() {
+        let mut engine = engine_answering(&[
+            "=1 D4\n\n",
+            "=2 pass\n\n",
+            "=3 E5\n\n",
+            "=4 PASS\n\n",
+            "=5 pass\n\n",
+        ]);
+        let mut progress = vec![];
+        let played = cleanup_dead_stones(&mut engine, Stone::Black, 10, |played| {
+            progress.push(played)
+        })
+        .unwrap();
+        assert_eq!(2, played);
+        assert_eq!(vec![1, 2], progress);
+    }
+
+    #[test]
+    fn cleanup_stops_at_limit() {
+        let mut engine = engine_answering(&["=1 D4\n\n", "=2 E5\n\n"]);
+        assert_eq!(
+            2,
+            cleanup_dead_stones(&mut engine, Stone::White, 2, |_| {}).unwrap()
+        );
+    }
+
+    #[test]
+    fn status_of_group() {
+        let mut engine = engine_answering(&["=1 A1 B1\n\n", "=2 C3\nD4\n\n"]);
+        assert_eq!(
+            Some(GroupStatus::Dead),
+            group_status(&mut engine, Point2 { x: 4, y: 4 }).unwrap()
+        );
+    }
+
+    #[test]
+    fn no_group_status() {
+        let mut engine = engine_answering(&["=1 A1\n\n", "=2 \n\n", "=3 \n\n"]);
+        assert_eq!(
+            None,
+            group_status(&mut engine, Point2 { x: 5, y: 5 }).unwrap()
+        );
+    }
+
+    #[test]
+    fn legal_points() {
+        let mut engine = engine_answering(&["=1 A1 B2\nC3\n\n"]);
+        let legal = legal_moves(&mut engine, "black").unwrap().unwrap();
+        assert_eq!(3, legal.len());
+        assert!(legal.contains(&Point2 { x: 2, y: 2 }));
+        assert!(!legal.contains(&Point2 { x: 1, y: 2 }));
+    }
+
+    #[test]
+    fn levels() {
+        let mut engine = engine_answering(&["=1 \n\n", "?2 unknown command\n\n"]);
+        assert!(set_level(&mut engine, 5).unwrap());
+        assert!(!set_level(&mut engine, 5).unwrap());
+    }
+
+    #[test]
+    fn no_all_legal() {
+        let mut engine = engine_answering(&["?1 unknown command\n\n"]);
+        assert_eq!(None, legal_moves(&mut engine, "white").unwrap());
+    }
 }
@@ -0,0 +1,173 @@
+use libremarkable::{
+    cgmath::{Point2, Vector2},
+    framebuffer::core::Framebuffer,
+};
+
+use crate::drawing::{centred_x, Button};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Key {
+    Char(char),
+    Backspace,
+    Shift,
+    Symbols,
+    Done,
+}
+
+const LETTER_ROWS: [&str; 4] = ["1234567890", "qwertyuiop", "asdfghjkl", "zxcvbnm"];
+const SYMBOL_ROWS: [&str; 4] = ["1234567890", "!@#$%^&*()", "-_=+[]{};:", "'\",.<>/?~|"];
+
+const KEY_PITCH: i32 = 138;
+const KEY_SIZE: Vector2<u32> = Vector2 { x: 128, y: 110 };
+const ROW_PITCH: i32 = 130;
+const LEFT_X: i32 = centred_x((KEY_PITCH * 10) as u32);
+pub const KEYBOARD_TOP_Y: i32 = 1000;
+
+pub struct Keyboard {
+    pub shift: bool,
+    pub symbols: bool,
+}
+
+impl Keyboard {
+    pub fn new() -> Keyboard {
+        Keyboard {
+            shift: false,
+            symbols: false,
+        }
+    }
+
+    fn key_at(text: &str, column: i32, row: i32, width: i32, key: Key) -> Button<Key> {
+        Button::new(
+            text,
+            Point2 {
+                x: LEFT_X + KEY_PITCH * column,
+                y: KEYBOARD_TOP_Y + ROW_PITCH * row,
+            },
+            Vector2 {
+                x: KEY_SIZE.x + (KEY_PITCH * (width - 1)) as u32,
+                y: KEY_SIZE.y,
+            },
+            key,
+        )
+    }
+
+    pub fn buttons(&self) -> Vec<Button<Key>> {
+        let rows = if self.symbols {
+            SYMBOL_ROWS
+        } else {
+            LETTER_ROWS
+        };
+        let mut buttons = vec![];
+        for (row, keys) in rows.iter().enumerate() {
+            for (column, c) in keys.chars().enumerate() {
+                let c = if self.shift {
+                    c.to_ascii_uppercase()
+                } else {
+                    c
+                };
+                buttons.push(Self::key_at(
+                    &c.to_string(),
+                    column as i32,
+                    row as i32,
+                    1,
+                    Key::Char(c),
+                ));
+            }
+        }
+        let bottom = rows.len() as i32;
+        buttons.push(Self::key_at("Shift", 0, bottom, 2, Key::Shift));
+        buttons.push(Self::key_at(
+            if self.symbols { "abc" } else { "#+=" },
+            2,
+            bottom,
+            2,
+            Key::Symbols,
+        ));
+        buttons.push(Self::key_at("Space", 4, bottom, 2, Key::Char(' ')));
+        buttons.push(Self::key_at("Del", 6, bottom, 2, Key::Backspace));
+        buttons.push(Self::key_at("Done", 8, bottom, 2, Key::Done));
+        buttons
+    }
+
+    pub fn draw(&self, fb: &mut Framebuffer) {
+        for button in self.buttons() {
+            button.draw(fb);
+        }
+    }
+
+    /// Applies `key` to `text`, returning true once the user is finished
+    pub fn press(&mut self, key: Key, text: &mut String) -> bool {
+        match key {
+            Key::Char(c) => {
+                text.push(c);
+                // Shift only applies to the next character, like a phone keyboard
+                self.shift = false;
+            }
+            Key::Backspace => {
+                text.pop();
+            }
+            Key::Shift => self.shift = !self.shift,
+            Key::Symbols => self.symbols = !self.symbols,
+            Key::Done => return true,
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use libremarkable::cgmath::Point2;
+    use pretty_assertions::assert_eq;
+
+    use crate::{
+        drawing::{SCREEN_HEIGHT, SCREEN_WIDTH},
+        keyboard::{Key, Keyboard},
+    };
+
+    #[test]
+    fn typing_and_backspace() {
+        let mut keyboard = Keyboard::new();
+        let mut text = String::new();
+        keyboard.press(Key::Backspace, &mut text);
+        assert_eq!("", text);
+        keyboard.press(Key::Char('a'), &mut text);
+        keyboard.press(Key::Char('b'), &mut text);
+        keyboard.press(Key::Backspace, &mut text);
+        assert_eq!("a", text);
+        assert!(keyboard.press(Key::Done, &mut text));
+    }
+
+    #[test]
+    fn shift_is_one_shot() {
+        let mut keyboard = Keyboard::new();
+        let mut text = String::new();
+        keyboard.press(Key::Shift, &mut text);
+        let upper_q = keyboard
+            .buttons()
+            .into_iter()
+            .find(|b| b.action == Key::Char('Q'));
+        assert!(upper_q.is_some());
+        keyboard.press(upper_q.unwrap().action, &mut text);
+        assert!(!keyboard.shift);
+        assert_eq!("Q", text);
+    }
+
+    #[test]
+    fn keys_fit_on_screen() {
+        for symbols in [false, true] {
+            let keyboard = Keyboard {
+                shift: false,
+                symbols,
+            };
+            for button in keyboard.buttons() {
+                let bottom_right = Point2 {
+                    x: button.top_left.x + button.size.x as i32,
+                    y: button.top_left.y + button.size.y as i32,
+                };
+                assert!(button.top_left.x >= 0, "{}", button.text);
+                assert!(bottom_right.x <= SCREEN_WIDTH as i32, "{}", button.text);
+                assert!(bottom_right.y <= SCREEN_HEIGHT as i32, "{}", button.text);
+            }
+        }
+    }
+}
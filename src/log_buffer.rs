@@ -0,0 +1,108 @@
+use chrono::Local;
+use log::{warn, LevelFilter, Log, Metadata, Record};
+use std::{collections::VecDeque, str::FromStr, sync::Mutex};
+
+/// How many lines the log viewer can go back through
+const MAX_LINES: usize = 500;
+
+/// Recent log lines, oldest first
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Adds `line`, dropping the oldest to stay within `max`
+fn push_line(lines: &mut VecDeque<String>, line: String, max: usize) {
+    lines.push_back(line);
+    while lines.len() > max {
+        lines.pop_front();
+    }
+}
+
+/// Logs as `env_logger` always has, to stderr, and keeps the recent lines for the log viewer
+struct BufferLogger {
+    inner: env_logger::Logger,
+}
+
+impl Log for BufferLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if !self.inner.matches(record) {
+            return;
+        }
+        self.inner.log(record);
+        let line = format!(
+            "{} {} {}: {}",
+            Local::now().format("%H:%M:%S"),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        // Not `lock`, as that logs when it's poisoned, which would come straight back here
+        let mut recent = RECENT
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        push_line(&mut recent, line, MAX_LINES);
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+}
+
+/// `log_level` names, same as `RUST_LOG`'s
+fn parse_level(name: &str) -> Option<LevelFilter> {
+    LevelFilter::from_str(name.trim()).ok()
+}
+
+/// Starts logging at `level` (e.g. "info"), or whatever `RUST_LOG` says without one
+pub fn init(level: Option<&str>) {
+    let mut builder = env_logger::Builder::from_default_env();
+    let parsed = level.map(|name| (name, parse_level(name)));
+    if let Some((_, Some(filter))) = parsed {
+        builder.filter_level(filter);
+    }
+    let inner = builder.build();
+    let max_level = inner.filter();
+    match log::set_boxed_logger(Box::new(BufferLogger { inner })) {
+        Ok(()) => log::set_max_level(max_level),
+        Err(err) => eprintln!("Can't set up logging: {err}"),
+    }
+    if let Some((name, None)) = parsed {
+        warn!("Unknown log_level {name:?}, so going with RUST_LOG");
+    }
+}
+
+/// What's been logged lately, oldest first
+pub fn recent_lines() -> Vec<String> {
+    let recent = RECENT
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    recent.iter().cloned().collect()
+}
+
+#[cfg(test)]
+mod test {
+    use log::LevelFilter;
+    use pretty_assertions::assert_eq;
+    use std::collections::VecDeque;
+
+    use crate::log_buffer::{parse_level, push_line};
+
+    #[test]
+    fn oldest_lines_go() {
+        let mut lines = VecDeque::new();
+        for index in 0..5 {
+            push_line(&mut lines, index.to_string(), 3);
+        }
+        assert_eq!(vec!["2", "3", "4"], Vec::from(lines));
+    }
+
+    #[test]
+    fn levels() {
+        assert_eq!(Some(LevelFilter::Info), parse_level("info"));
+        assert_eq!(Some(LevelFilter::Debug), parse_level("DEBUG"));
+        assert_eq!(Some(LevelFilter::Off), parse_level("off"));
+        assert_eq!(None, parse_level("loud"));
+    }
+}
@@ -0,0 +1,183 @@
+use libremarkable::{
+    appctx,
+    cgmath::{Point2, Vector2},
+    framebuffer::{core::Framebuffer, FramebufferDraw},
+    input::MultitouchEvent,
+};
+
+use crate::{
+    drawing::{
+        draw_multiline_text, pressed, refresh, scaled, scaled_size, wrap_text, Button,
+        SCREEN_HEIGHT, SCREEN_WIDTH,
+    },
+    engine::Engine,
+    log_buffer::recent_lines,
+    reset::{draw_back, handle_back},
+    routine::Routine,
+};
+
+const LOG_BUTTON_SIZE: Vector2<u32> = Vector2 { x: 250, y: 95 };
+const BUTTON_GAP: i32 = 20;
+// Under the back button, so a big `ui_scale` still fits all three
+const BUTTON_Y: u32 = 140;
+const LOG_TEXT_SIZE: f32 = 30.0;
+// Characters are about 0.6 of the text size wide
+const LINE_CHARS: usize = ((SCREEN_WIDTH as f32 - 40.0) / (LOG_TEXT_SIZE * 0.6)) as usize;
+
+#[derive(Clone, Copy)]
+enum Action {
+    Older,
+    Newer,
+    Refresh,
+}
+
+fn buttons() -> Vec<Button<Action>> {
+    let size = scaled_size(LOG_BUTTON_SIZE);
+    [
+        ("Older", Action::Older),
+        ("Newer", Action::Newer),
+        ("Refresh", Action::Refresh),
+    ]
+    .into_iter()
+    .enumerate()
+    .map(|(index, (text, action))| {
+        Button::new(
+            text,
+            Point2 {
+                x: BUTTON_GAP + (size.x as i32 + BUTTON_GAP) * index as i32,
+                y: scaled(BUTTON_Y) as i32,
+            },
+            size,
+            action,
+        )
+    })
+    .collect()
+}
+
+/// Where the first line of the log goes, under the buttons
+fn text_top() -> f32 {
+    (scaled(BUTTON_Y) + scaled(LOG_BUTTON_SIZE.y)) as f32 + 2.0 * LOG_TEXT_SIZE
+}
+
+/// How many lines fit on screen
+fn rows() -> usize {
+    ((SCREEN_HEIGHT as f32 - text_top()) / LOG_TEXT_SIZE) as usize
+}
+
+/// `rows` lines ending `offset` lines back from the newest
+fn page(lines: &[String], rows: usize, offset: usize) -> &[String] {
+    let end = lines.len().saturating_sub(offset);
+    &lines[end.saturating_sub(rows)..end]
+}
+
+/// `offset` a page further back, stopping once the oldest line is at the top
+fn older(len: usize, rows: usize, offset: usize) -> usize {
+    (offset + rows).min(len.saturating_sub(rows))
+}
+
+/// The most recent log lines, for seeing what went wrong (e.g. a network error) without a shell
+pub struct LogViewer {
+    /// Wrapped to fit the screen, oldest first
+    lines: Vec<String>,
+    /// Lines back from the newest, which is 0 unless it's been scrolled back with "Older"
+    offset: usize,
+}
+
+impl LogViewer {
+    pub fn new() -> LogViewer {
+        LogViewer {
+            lines: vec![],
+            offset: 0,
+        }
+    }
+
+    fn load(&mut self) {
+        self.lines = recent_lines()
+            .iter()
+            .flat_map(|line| wrap_text(line, LINE_CHARS))
+            .collect();
+        self.offset = 0;
+    }
+
+    fn draw(&self, fb: &mut Framebuffer) {
+        fb.clear();
+        draw_back(fb);
+        for button in buttons() {
+            button.draw(fb);
+        }
+        let text = if self.lines.is_empty() {
+            "Nothing logged yet. Try a higher log_level in the settings".to_string()
+        } else {
+            page(&self.lines, rows(), self.offset).join("\n")
+        };
+        draw_multiline_text(
+            fb,
+            Point2 {
+                x: BUTTON_GAP as f32,
+                y: text_top(),
+            },
+            &text,
+            LOG_TEXT_SIZE,
+            LINE_CHARS,
+        );
+        refresh(fb);
+    }
+}
+
+impl Routine for LogViewer {
+    fn init(&mut self, fb: &mut Framebuffer, _ctrl: &mut Engine) {
+        self.load();
+        self.draw(fb);
+    }
+
+    fn on_multitouch_event(
+        &mut self,
+        ctx: &mut appctx::ApplicationContext<'_>,
+        event: MultitouchEvent,
+        _ctrl: &mut Engine,
+    ) {
+        let MultitouchEvent::Press { finger } = event else {
+            return;
+        };
+        if handle_back(ctx, finger.pos) {
+            return;
+        }
+        let buttons = buttons();
+        let Some(button) = pressed(&buttons, finger.pos) else {
+            return;
+        };
+        match button.action {
+            Action::Older => self.offset = older(self.lines.len(), rows(), self.offset),
+            Action::Newer => self.offset = self.offset.saturating_sub(rows()),
+            Action::Refresh => self.load(),
+        }
+        self.draw(ctx.get_framebuffer_ref());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use crate::log_viewer::{older, page};
+
+    #[test]
+    fn pages() {
+        let lines: Vec<String> = (0..10).map(|index| index.to_string()).collect();
+        assert_eq!(["7", "8", "9"], page(&lines, 3, 0));
+        assert_eq!(["4", "5", "6"], page(&lines, 3, 3));
+        // Partial page at the start
+        assert_eq!(["0", "1"], page(&lines, 3, 8));
+        assert!(page(&lines, 3, 20).is_empty());
+        assert_eq!(["0", "1"], page(&lines[..2], 3, 0));
+    }
+
+    #[test]
+    fn older_stops_at_the_start() {
+        assert_eq!(3, older(10, 3, 0));
+        assert_eq!(7, older(10, 3, 6));
+        assert_eq!(7, older(10, 3, 7));
+        // Less than a page, so nothing further back
+        assert_eq!(0, older(2, 3, 0));
+    }
+}
@@ -0,0 +1,199 @@
+use crate::{
+    chooser::{Mode, CURRENT_MODE},
+    dragon_go_server::{LoginInfo, LOGIN_FILE},
+    drawing::{centred_x, pressed, refresh, Button},
+    engine::Engine,
+    keyboard::Keyboard,
+    lock,
+    reset::{draw_back, handle_back},
+    routine::Routine,
+};
+use libremarkable::{
+    appctx,
+    cgmath::{Point2, Vector2},
+    framebuffer::{common::color, core::Framebuffer, FramebufferDraw},
+    input::MultitouchEvent,
+};
+use log::{info, warn};
+use std::fs;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Field {
+    Username,
+    Password,
+}
+
+const FIELD_SIZE: Vector2<u32> = Vector2 { x: 1200, y: 110 };
+const FIELD_X: i32 = centred_x(FIELD_SIZE.x);
+// Roughly what fits in a field at button text size
+const FIELD_CHARS: usize = 18;
+
+fn field_text(label: &str, value: &str, masked: bool, active: bool) -> String {
+    let shown: String = if masked {
+        "*".repeat(value.chars().count())
+    } else {
+        value.to_string()
+    };
+    let room = FIELD_CHARS - label.len();
+    let count = shown.chars().count();
+    // Keep the end visible, as that's where the typing is happening
+    let shown: String = shown.chars().skip(count.saturating_sub(room)).collect();
+    format!("{}{label}{shown}", if active { ">" } else { " " })
+}
+
+pub struct LoginEntry {
+    login_info: LoginInfo,
+    field: Field,
+    keyboard: Keyboard,
+    message: Option<String>,
+}
+
+impl LoginEntry {
+    pub fn new() -> LoginEntry {
+        let login_info = fs::read(LOGIN_FILE)
+            .ok()
+            .and_then(|raw| serde_json::from_slice::<LoginInfo>(&raw).ok())
+            .unwrap_or_default();
+        LoginEntry {
+            login_info,
+            field: Field::Username,
+            keyboard: Keyboard::new(),
+            message: None,
+        }
+    }
+
+    fn field_buttons(&self) -> Vec<Button<Field>> {
+        [
+            ("User:", Field::Username, &self.login_info.username, 200),
+            ("Pass:", Field::Password, &self.login_info.password, 400),
+        ]
+        .iter()
+        .map(|(label, field, value, y)| {
+            Button::new(
+                &field_text(
+                    label,
+                    value,
+                    *field == Field::Password,
+                    *field == self.field,
+                ),
+                Point2 { x: FIELD_X, y: *y },
+                FIELD_SIZE,
+                *field,
+            )
+        })
+        .collect()
+    }
+
+    fn draw(&self, fb: &mut Framebuffer) {
+        fb.clear();
+        draw_back(fb);
+        for button in self.field_buttons() {
+            button.draw(fb);
+        }
+        if let Some(ref message) = self.message {
+            fb.draw_text(
+                Point2 {
+                    x: FIELD_X as f32,
+                    y: 700.0,
+                },
+                message,
+                60.0,
+                color::BLACK,
+                false,
+            );
+        }
+        self.keyboard.draw(fb);
+        refresh(fb);
+    }
+
+    fn save(&self) -> Result<(), String> {
+        if self.login_info.username.is_empty() || self.login_info.password.is_empty() {
+            return Err("Need a username and password".to_string());
+        }
+        let dumped = serde_json::to_vec_pretty(&self.login_info).expect("can dump login info");
+        fs::write(LOGIN_FILE, dumped).map_err(|err| {
+            warn!("Can't write login data to {LOGIN_FILE}: {err}");
+            format!("Can't write {LOGIN_FILE}")
+        })
+    }
+}
+
+impl Routine for LoginEntry {
+    fn init(&mut self, fb: &mut Framebuffer, _ctrl: &mut Engine) {
+        self.draw(fb);
+    }
+
+    fn on_multitouch_event(
+        &mut self,
+        ctx: &mut appctx::ApplicationContext<'_>,
+        event: MultitouchEvent,
+        _ctrl: &mut Engine,
+    ) {
+        if let MultitouchEvent::Press { finger } = event {
+            let fb = ctx.get_framebuffer_ref();
+
+            if handle_back(ctx, finger.pos) {
+                return;
+            }
+
+            if let Some(button) = pressed(&self.field_buttons(), finger.pos) {
+                self.field = button.action;
+                self.draw(fb);
+                return;
+            }
+
+            let keys = self.keyboard.buttons();
+            let key = match pressed(&keys, finger.pos) {
+                Some(button) => button.action,
+                None => return,
+            };
+            let text = match self.field {
+                Field::Username => &mut self.login_info.username,
+                Field::Password => &mut self.login_info.password,
+            };
+            if !self.keyboard.press(key, text) {
+                self.message = None;
+                self.draw(fb);
+                return;
+            }
+            // Done on the username moves on to the password
+            if self.field == Field::Username {
+                self.field = Field::Password;
+                self.draw(fb);
+                return;
+            }
+            match self.save() {
+                Ok(()) => {
+                    info!("Saved login for {}", self.login_info.username);
+                    *lock(&CURRENT_MODE) = Mode::DragonGoServer;
+                    ctx.stop();
+                }
+                Err(message) => {
+                    self.message = Some(message);
+                    self.draw(fb);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use crate::login_entry::field_text;
+
+    #[test]
+    fn password_masked() {
+        assert_eq!(">Pass:******", field_text("Pass:", "hunter", true, true));
+        assert_eq!(" User:hunter", field_text("User:", "hunter", false, false));
+    }
+
+    #[test]
+    fn long_field_shows_end() {
+        assert_eq!(
+            " User:ghijklmnopqrs",
+            field_text("User:", "abcdefghijklmnopqrs", false, false)
+        );
+    }
+}
@@ -1,24 +1,53 @@
 use crate::{
-    board::Board,
-    chooser::CURRENT_MODE,
-    drawing::{refresh, refresh_with_options},
-    gtp::{clear_board, do_human_move, get_response, list_stones, set_board_size},
-    reset::{draw_reset, reset_button_top_left, RESET_BUTTON_SIZE},
+    atari_game::UNDO_BUTTON_SIZE,
+    board::{added_stones, Board, Stone, AVAILABLE_WIDTH},
+    chooser::Mode,
+    clock::Clock,
+    drawing::{
+        centred_x, draw_multiline_text, draw_status_note, flush_refresh, pressed, refresh, scaled,
+        scaled_size, submit_refresh, wait_refresh, Button, GameInfo, Held, HoldTracker, Hud,
+        StatusBar, SCREEN_HEIGHT, SCREEN_WIDTH,
+    },
+    engine::Engine,
+    game_parse::is_self_atari,
+    game_state::{
+        clear_state, draw_resume_choice, load_state, resume_choice, save_state, GameState,
+        LocalGame, ResumeChoice,
+    },
+    gtp::{
+        area_score, board_size_fallback_text, cleanup_dead_stones, count_captures, do_human_move,
+        group_status, influence_regions, legal_moves, parse_engine_move, play_pass, poll_response,
+        replay_moves, restart_engine, set_komi, set_level, set_supported_board_size,
+        set_time_settings, suggest_move, AreaScore, EngineMove, GroupStatus, GtpError, StoneCache,
+        StoneLists,
+    },
+    move_choice::{ChoicePress, DoubleTap, MoveChoice},
+    reset::{draw_back, handle_back},
+    review::review_just_played,
     routine::Routine,
+    settings::SETTINGS,
+    sgf::{game_sgf, save_sgf},
 };
-use gtp::{controller::Engine, Command};
+use gtp::Command;
 use libremarkable::{
     appctx,
     cgmath::{Point2, Vector2},
     framebuffer::{
-        common::{color, mxcfb_rect, waveform_mode},
+        common::{color, waveform_mode},
         core::Framebuffer,
         FramebufferDraw,
     },
     input::MultitouchEvent,
 };
-use log::info;
-use std::time::Instant;
+use log::{error, info, warn};
+use std::time::{Duration, Instant};
+
+// GnuGo gets slow later in the game on a Remarkable, so give it much longer than normal commands
+const MACHINE_MOVE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+// How often update_loop checks whether GnuGo has finished its move
+const MACHINE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+// Between the clock and the score button, and the clock and the edge of the screen
+const CLOCK_GAP: i32 = 20;
 
 #[derive(PartialEq, Debug, Clone, Copy)]
 enum Turn {
@@ -26,67 +55,430 @@ enum Turn {
     MachineTurn = 2,
 }
 
-fn do_machine_move(ctrl: &mut Engine) {
-    ctrl.send(Command::new_with_args("genmove", |e| e.s("black")));
-    info!("waiting for machine response");
-    let resp = get_response(ctrl);
-    info!("machine: {}", resp.text());
+#[derive(Clone, Copy)]
+enum SummaryAction {
+    SaveSgf,
+    NewGame,
+    /// New game with the human playing the other colour
+    Rematch,
+    Back,
+}
+
+const SUMMARY_BUTTON_SIZE: Vector2<u32> = Vector2 { x: 700, y: 95 };
+
+/// To the right of the score button, if there's room, otherwise the clock goes in the status
+fn clock_hud(score_button: &Button<()>) -> Option<Hud> {
+    let left = score_button.top_left.x + score_button.size.x as i32 + CLOCK_GAP;
+    let width = SCREEN_WIDTH as i32 - CLOCK_GAP - left;
+    Hud::new(
+        Point2 {
+            x: left,
+            y: score_button.top_left.y,
+        },
+        Vector2 {
+            x: width.max(0) as u32,
+            y: score_button.size.y,
+        },
+    )
+}
+
+/// Under the other summary buttons. Not one of them, as leaving for the reviewer needs the
+/// app context
+fn review_button() -> Button<()> {
+    let size = scaled_size(SUMMARY_BUTTON_SIZE);
+    Button::new(
+        "Review game",
+        Point2 {
+            x: centred_x(size.x),
+            y: 1400,
+        },
+        size,
+        (),
+    )
+}
+
+fn summary_buttons() -> Vec<Button<SummaryAction>> {
+    let size = scaled_size(SUMMARY_BUTTON_SIZE);
+    [
+        ("Save SGF", SummaryAction::SaveSgf, 800),
+        ("New game", SummaryAction::NewGame, 950),
+        ("Rematch (swap colours)", SummaryAction::Rematch, 1100),
+        ("Back to game", SummaryAction::Back, 1250),
+    ]
+    .iter()
+    .map(|(text, action, y)| {
+        let button = Button::new(
+            text,
+            Point2 {
+                x: centred_x(size.x),
+                y: *y,
+            },
+            size,
+            *action,
+        );
+        // Both of these throw the current game away
+        match action {
+            SummaryAction::NewGame | SummaryAction::Rematch => button.held(),
+            _ => button,
+        }
+    })
+    .collect()
+}
+
+/// Stones each side has taken, from GTP `captures`
+struct Captures {
+    black: usize,
+    white: usize,
 }
 
 pub struct MachineGame {
     board: Board,
+    /// GnuGo plays the other colour, and black goes first
+    human: Stone,
     current_turn: Turn,
+    score_button: Button<()>,
+    score: Option<AreaScore>,
+    hint_button: Button<()>,
+    /// GnuGo's suggestion for the human, in GTP coordinates
+    hint: Option<Point2<u8>>,
+    fb: Option<&'static mut Framebuffer>,
+    /// When we asked GnuGo for its move, if it's still thinking
+    machine_started: Option<Instant>,
+    /// Every move so far, 0-based from the top-left, with `None` for a pass
+    history: Vec<(Stone, Option<Point2<u8>>)>,
+    captures: Option<Captures>,
+    /// GnuGo played out extra moves to take dead stones when scoring, which aren't in `history`
+    cleaned_up: bool,
+    showing_summary: bool,
+    /// For the held buttons on the summary and resume screens
+    hold: HoldTracker,
+    /// A game saved before a crash, while asking whether to pick it back up
+    resume: Option<GameState>,
+    summary_message: Option<String>,
+    stone_cache: StoneCache,
+    /// Human moves GnuGo has answered, so not counting its opening move
+    turns: usize,
+    /// The human's clock, if the settings want one
+    clock: Option<Clock>,
+    /// The human lost on time
+    time_up: bool,
+    /// GnuGo gave up, so the human won
+    machine_resigned: bool,
+    /// Where the human's clock goes, if it's not in the status
+    clock_hud: Option<Hud>,
+    /// Last clock `update_loop` drew, so it only gets redrawn when it changes
+    shown_clock_text: String,
+    /// Stones on screen with nothing drawn over them, which `fast_stones` can add to
+    shown_stones: Option<StoneLists>,
+    /// A self-atari that's been warned about, and gets played if it's tapped again
+    self_atari: Option<Point2<u8>>,
+    double_tap: DoubleTap,
+    /// The move to commit, with `confirm_moves`
+    choice: MoveChoice,
+    influence_button: Button<()>,
+    /// GnuGo's `influence_regions` while they're shown over the board
+    influence: Option<Vec<i8>>,
+    /// For passing back after GnuGo passes, which ends the game
+    pass_button: Button<()>,
+    /// Where `pass_button` goes, for while the machine's thinking
+    cancel_button: Button<()>,
+    /// Whether `pass_button` is on screen, so `fast_stones` knows when to add or clear it
+    pass_shown: bool,
+    /// Taps on the board ask GnuGo whether that group lives, rather than playing there
+    inspecting: bool,
 }
 
 impl MachineGame {
     pub fn new() -> MachineGame {
-        MachineGame {
-            board: Board::new(9),
-            current_turn: Turn::MachineTurn,
-        }
+        MachineGame::with_size(SETTINGS.local_board_size())
     }
 
-    fn draw_turn(&self, fb: &mut Framebuffer, refresh: bool) {
-        let rect_width = 550;
-        info!("draw_turn {:?}", self.current_turn);
-        let text = if self.current_turn == Turn::HumanTurn {
-            "Human turn"
-        } else {
-            "Machine turn"
-        };
-        fb.fill_rect(
+    /// For a board other than the settings' size, when the engine won't play on that
+    fn with_size(board_size: u8) -> MachineGame {
+        let board = Board::new(board_size);
+        let score_button = Button::new(
+            "Score",
             Point2 {
-                x: self.board.spare_width as i32,
-                y: 0,
+                x: (board.spare_width + AVAILABLE_WIDTH / 2 - 10) as i32,
+                y: scaled(120) as i32,
             },
-            Vector2 {
-                x: rect_width,
-                y: 100,
+            scaled_size(UNDO_BUTTON_SIZE),
+            (),
+        );
+        let hint_button = Button::new(
+            "Hint",
+            Point2 {
+                x: board.spare_width as i32,
+                y: scaled(120) as i32,
             },
-            color::WHITE,
+            scaled_size(UNDO_BUTTON_SIZE),
+            (),
         );
-        fb.draw_text(
+        let choice = MoveChoice::below_board(&board, SCREEN_HEIGHT as i32 - 152);
+        let influence_size = scaled_size(UNDO_BUTTON_SIZE);
+        // Shares the row with the `confirm_moves` buttons, so it's hidden while they're up
+        let influence_button = Button::new(
+            "Influence",
             Point2 {
-                x: self.board.spare_width as f32,
-                y: 100.0,
+                x: centred_x(influence_size.x),
+                y: SCREEN_HEIGHT as i32 - 152,
             },
-            text,
-            100.0,
-            color::BLACK,
-            false,
+            influence_size,
+            (),
         );
-        if refresh {
-            refresh_with_options(
-                fb,
-                &mxcfb_rect {
-                    top: 0,
-                    left: self.board.spare_width as u32,
-                    width: rect_width,
-                    height: 100,
-                },
-                waveform_mode::WAVEFORM_MODE_AUTO,
-            );
+        // On the right of the row, where "Commit" goes for `confirm_moves`
+        let pass_button = Button::new(
+            "Pass",
+            Point2 {
+                x: (board.spare_width + board.grid_size()) as i32 - influence_size.x as i32,
+                y: SCREEN_HEIGHT as i32 - 152,
+            },
+            influence_size,
+            (),
+        );
+        let clock_hud = clock_hud(&score_button);
+        let cancel_button = Button::new("Cancel", pass_button.top_left, influence_size, ());
+        MachineGame {
+            board,
+            human: Stone::White,
+            current_turn: Turn::MachineTurn,
+            score_button,
+            score: None,
+            hint_button,
+            hint: None,
+            fb: None,
+            machine_started: None,
+            history: vec![],
+            captures: None,
+            cleaned_up: false,
+            showing_summary: false,
+            hold: HoldTracker::default(),
+            resume: None,
+            summary_message: None,
+            stone_cache: StoneCache::default(),
+            turns: 0,
+            clock: None,
+            time_up: false,
+            machine_resigned: false,
+            clock_hud,
+            shown_clock_text: String::new(),
+            shown_stones: None,
+            self_atari: None,
+            double_tap: DoubleTap::default(),
+            choice,
+            influence_button,
+            influence: None,
+            pass_button,
+            cancel_button,
+            pass_shown: false,
+            inspecting: false,
+        }
+    }
+
+    /// Left of the influence button, where "Undo" goes for `confirm_moves`, so it's hidden along
+    /// with the influence button
+    fn inspect_button(&self) -> Button<()> {
+        Button::new(
+            if self.inspecting { "Play" } else { "Inspect" },
+            Point2 {
+                x: self.board.spare_width as i32,
+                y: self.influence_button.top_left.y,
+            },
+            scaled_size(UNDO_BUTTON_SIZE),
+            (),
+        )
+    }
+
+    /// Asks GnuGo for a move, which `update_loop` then waits for so touches still work meanwhile.
+    /// The `machine_level` goes first every time, so it's the machine's whatever else has used
+    /// the engine
+    fn start_machine_move(
+        &mut self,
+        ctrl: &mut Engine,
+        fb: &mut Framebuffer,
+    ) -> Result<(), GtpError> {
+        if let Some(level) = SETTINGS.machine_level() {
+            set_level(ctrl, level)?;
+        }
+        let machine = self.human.other().gtp_name();
+        ctrl.send(Command::new_with_args("genmove", |e| e.s(machine)));
+        info!("waiting for machine response");
+        self.machine_started = Some(Instant::now());
+        self.set_turn(Turn::MachineTurn, fb);
+        self.cancel_button.refresh_shown(fb, true);
+        Ok(())
+    }
+
+    /// GTP has no way to stop a `genmove`, so this restarts GnuGo and plays the game back
+    /// without the human's last move, for them to have another go. The clock carries on from
+    /// where it was before that move
+    fn cancel_machine_move(
+        &mut self,
+        ctrl: &mut Engine,
+        fb: &mut Framebuffer,
+    ) -> Result<(), GtpError> {
+        info!("Cancelling the machine's move");
+        self.machine_started = None;
+        self.current_turn = Turn::HumanTurn;
+        self.draw_status(fb, "Cancelling...", true);
+        restart_engine(ctrl)?;
+        let mut history = self.history.clone();
+        if matches!(history.last(), Some((stone, _)) if *stone == self.human) {
+            history.pop();
+        }
+        let clock = self.clock.take();
+        self.start_game(ctrl, fb, history)?;
+        if let (Some(mut clock), Turn::HumanTurn) = (clock, self.current_turn) {
+            clock.start(Instant::now());
+            self.clock = Some(clock);
+        }
+        Ok(())
+    }
+
+    fn check_machine_move(
+        &mut self,
+        ctrl: &mut Engine,
+        fb: &mut Framebuffer,
+    ) -> Result<(), GtpError> {
+        if let Some(started) = self.machine_started {
+            if let Some(resp) = poll_response(ctrl, started, MACHINE_MOVE_TIMEOUT)? {
+                info!("machine: {} after {:.2?}", resp.text(), started.elapsed());
+                self.machine_started = None;
+                self.cancel_button.refresh_shown(fb, false);
+                // GTP points here are 1-based, see do_human_move
+                let point = match parse_engine_move(&resp.text())? {
+                    EngineMove::Play(p) => Some(Point2 {
+                        x: p.x - 1,
+                        y: p.y - 1,
+                    }),
+                    EngineMove::Pass => None,
+                    EngineMove::Resign => {
+                        self.machine_resign(fb);
+                        return Ok(());
+                    }
+                };
+                if matches!(self.history.last(), Some((stone, _)) if *stone == self.human) {
+                    self.turns += 1;
+                }
+                self.history.push((self.human.other(), point));
+                self.save_state();
+                self.stone_cache.board_changed();
+                self.current_turn = Turn::HumanTurn;
+                if let Some(ref mut clock) = self.clock {
+                    clock.start(Instant::now());
+                }
+                self.update_stones(ctrl, fb)?;
+            }
         }
+        Ok(())
+    }
+
+    fn draw_status(&self, fb: &mut Framebuffer, text: &str, refresh: bool) {
+        let status = StatusBar::new(self.board.spare_width as i32);
+        status.draw(fb, text, refresh);
+        let note = match self.self_atari {
+            Some(_) => "Tap again\nto confirm".to_string(),
+            None => format!("Turn {}\nYou're {:?}", self.turns + 1, self.human),
+        };
+        draw_status_note(fb, status.right(text), status.height(), &note, refresh);
+    }
+
+    fn game_info(&self) -> GameInfo {
+        let over = self.score.is_some() || self.time_up || self.machine_resigned;
+        let to_play = match self.current_turn {
+            Turn::HumanTurn => self.human,
+            Turn::MachineTurn => self.human.other(),
+        };
+        GameInfo {
+            size: self.board.board_size,
+            komi: SETTINGS.rules.komi(),
+            // Local games are always even
+            handicap: 0,
+            to_play: (!over).then_some(to_play),
+        }
+    }
+
+    fn draw_turn(&self, fb: &mut Framebuffer, refresh: bool) {
+        info!("draw_turn {:?}", self.current_turn);
+        self.game_info().draw(fb, &self.board, refresh);
+        if let Some(ref score) = self.score {
+            let text = match score.score {
+                Some(ref final_score) => final_score.to_string(),
+                None => "Score ?".to_string(),
+            };
+            self.draw_status(fb, &text, refresh);
+            return;
+        }
+        self.draw_status(fb, &self.turn_text(), refresh);
+    }
+
+    fn turn_text(&self) -> String {
+        if self.time_up {
+            return "Out of time".to_string();
+        }
+        match (self.current_turn, &self.clock) {
+            (Turn::HumanTurn, _) if self.inspecting => "Tap a group".to_string(),
+            (Turn::HumanTurn, _) if self.machine_passed() => "Machine passed".to_string(),
+            (Turn::HumanTurn, Some(clock)) if self.clock_hud.is_none() => {
+                clock.display(Instant::now())
+            }
+            (Turn::HumanTurn, _) => "Human turn".to_string(),
+            (Turn::MachineTurn, _) => "Machine turn".to_string(),
+        }
+    }
+
+    /// Keeps the human's clock on screen up to date, and ends the game when it runs out
+    fn update_clock(&mut self, fb: &mut Framebuffer) {
+        if self.current_turn != Turn::HumanTurn || self.time_up {
+            return;
+        }
+        let Some(ref clock) = self.clock else {
+            return;
+        };
+        if clock.is_out_of_time(Instant::now()) {
+            self.lose_on_time(fb);
+            return;
+        }
+        if self.showing_summary {
+            return;
+        }
+        match self.clock_hud {
+            Some(ref hud) => {
+                let text = clock.display(Instant::now());
+                if text != self.shown_clock_text {
+                    hud.update(fb, &text);
+                    self.shown_clock_text = text;
+                }
+            }
+            None => {
+                let text = self.turn_text();
+                if self.score.is_none() && text != self.shown_clock_text {
+                    self.draw_status(fb, &text, true);
+                    self.shown_clock_text = text;
+                }
+            }
+        }
+    }
+
+    /// The clock in its own area, for full redraws
+    fn draw_clock(&mut self, fb: &mut Framebuffer) {
+        if let (Some(ref hud), Some(ref clock)) = (&self.clock_hud, &self.clock) {
+            let text = clock.display(Instant::now());
+            hud.draw(fb, &text);
+            self.shown_clock_text = text;
+        }
+    }
+
+    fn lose_on_time(&mut self, fb: &mut Framebuffer) {
+        info!("Human out of time");
+        self.time_up = true;
+        clear_state();
+        if let Some(ref mut clock) = self.clock {
+            clock.stop(Instant::now());
+        }
+        self.showing_summary = true;
+        self.summary_message = None;
+        self.draw_summary(fb);
     }
 
     fn set_turn(&mut self, turn: Turn, fb: &mut Framebuffer) {
@@ -95,30 +487,649 @@ impl MachineGame {
         self.draw_turn(fb, true);
     }
 
-    fn reset_game(&self, ctrl: &mut Engine, fb: &mut Framebuffer) {
-        clear_board(ctrl);
-        do_machine_move(ctrl);
-        self.redraw_stones(ctrl, fb);
+    fn show_engine_error(&self, fb: &mut Framebuffer, err: GtpError) {
+        error!("Engine error: {err}");
+        self.draw_status(fb, err.status_text(), true);
     }
 
-    fn redraw_stones(&self, ctrl: &mut Engine, fb: &mut Framebuffer) {
+    fn reset_game(&mut self, ctrl: &mut Engine, fb: &mut Framebuffer) -> Result<(), GtpError> {
+        self.start_game(ctrl, fb, vec![])
+    }
+
+    fn save_state(&self) {
+        save_state(&GameState::new(
+            LocalGame::Machine,
+            self.board.board_size,
+            self.human,
+            &self.history,
+        ));
+    }
+
+    /// Sets GnuGo up with `history` already played, which is empty for a new game. Resumed
+    /// games get a fresh clock, as the old one wasn't saved
+    fn start_game(
+        &mut self,
+        ctrl: &mut Engine,
+        fb: &mut Framebuffer,
+        history: Vec<(Stone, Option<Point2<u8>>)>,
+    ) -> Result<(), GtpError> {
+        replay_moves(ctrl, self.board.board_size, &history)?;
+        set_komi(ctrl, SETTINGS.rules.komi())?;
+        let time_control = SETTINGS.machine_clock();
+        match time_control {
+            Some(control) => set_time_settings(
+                ctrl,
+                control.main_time.as_secs() as u32,
+                control.byo_yomi_time.as_secs() as u32,
+                control.byo_yomi_stones,
+            )?,
+            // Otherwise an earlier timed game would still be limiting GnuGo
+            None => set_time_settings(ctrl, 0, 1, 0)?,
+        }
+        self.clock = time_control.map(Clock::new);
+        self.time_up = false;
+        self.machine_resigned = false;
+        self.stone_cache.force_refresh(ctrl)?;
+        self.score = None;
+        self.cleaned_up = false;
+        self.hint = None;
+        self.influence = None;
+        self.inspecting = false;
+        self.self_atari = None;
+        self.double_tap.clear();
+        self.choice.chosen = None;
+        // Counted like check_machine_move does, as human moves GnuGo has answered
+        self.turns = history
+            .windows(2)
+            .filter(|pair| pair[0].0 == self.human && pair[1].0 != self.human)
+            .count();
+        self.history = history;
+        self.save_state();
+        self.showing_summary = false;
+        self.summary_message = None;
+        let next = self
+            .history
+            .last()
+            .map_or(Stone::Black, |(stone, _)| stone.other());
+        if next == self.human {
+            self.current_turn = Turn::HumanTurn;
+            if let Some(ref mut clock) = self.clock {
+                clock.start(Instant::now());
+            }
+            self.redraw_stones(ctrl, fb)?;
+        } else {
+            self.redraw_stones(ctrl, fb)?;
+            self.start_machine_move(ctrl, fb)?;
+        }
+        Ok(())
+    }
+
+    /// "B+T" or "W+T" for GnuGo winning on time, or "B+R" or "W+R" for the human winning by
+    /// resignation, if the game ended either way
+    fn forfeit_result(&self) -> Option<&'static str> {
+        if self.time_up {
+            return Some(match self.human.other() {
+                Stone::Black => "B+T",
+                Stone::White => "W+T",
+            });
+        }
+        if self.machine_resigned {
+            return Some(match self.human {
+                Stone::Black => "B+R",
+                Stone::White => "W+R",
+            });
+        }
+        None
+    }
+
+    fn machine_resign(&mut self, fb: &mut Framebuffer) {
+        info!("Machine resigned");
+        self.machine_resigned = true;
+        // The human gets to look at the summary, and back at the board
+        self.current_turn = Turn::HumanTurn;
+        clear_state();
+        self.showing_summary = true;
+        self.summary_message = None;
+        self.draw_summary(fb);
+    }
+
+    /// Whether GnuGo's last move was a pass, so the human can pass too
+    fn machine_passed(&self) -> bool {
+        matches!(self.history.last(), Some((stone, None)) if *stone == self.human.other())
+    }
+
+    /// Passing after GnuGo passed ends the game, so it goes straight to scoring
+    fn pass_too(&mut self, ctrl: &mut Engine, fb: &mut Framebuffer) -> Result<(), GtpError> {
+        if !play_pass(ctrl, self.human.gtp_name())?.played() {
+            info!("GnuGo wouldn't take a pass");
+            return Ok(());
+        }
+        if let Some(ref mut clock) = self.clock {
+            clock.stop(Instant::now());
+        }
+        self.stone_cache.board_changed();
+        self.history.push((self.human, None));
+        self.save_state();
+        self.show_score(ctrl, fb)
+    }
+
+    fn redraw_stones(&mut self, ctrl: &mut Engine, fb: &mut Framebuffer) -> Result<(), GtpError> {
         let start = Instant::now();
-        let white_stones = list_stones(ctrl, "white");
-        let black_stones = list_stones(ctrl, "black");
+        let (white_stones, black_stones) = self.stone_cache.stones(ctrl)?;
         self.board.draw_board(fb, &white_stones, &black_stones);
+        self.shown_stones = None;
+        match self.score {
+            Some(ref score) => self.draw_score_details(fb, score, &white_stones),
+            None => self.shown_stones = Some((white_stones, black_stones)),
+        }
+        if let Some(ref influence) = self.influence {
+            self.board.draw_influence(fb, influence);
+        }
+        let show_legal = SETTINGS.show_legal_moves
+            && self.current_turn == Turn::HumanTurn
+            && self.score.is_none();
+        if show_legal {
+            if let Some(legal) = legal_moves(ctrl, self.human.gtp_name())? {
+                self.board.draw_legal_markers(fb, &legal);
+            }
+        }
+        if self.choice.chosen.is_some() || self.influence.is_some() || show_legal {
+            // Drawn over the board, so fast_stones can't just add to it
+            self.shown_stones = None;
+        }
+        self.choice
+            .draw(fb, &self.board, self.human == Stone::White);
+        self.pass_shown = false;
+        if self.choice.chosen.is_none() {
+            self.influence_button.draw(fb);
+            self.inspect_button().draw(fb);
+            if self.current_turn == Turn::HumanTurn && self.machine_passed() {
+                self.pass_button.draw(fb);
+                self.pass_shown = true;
+            }
+        }
         self.draw_turn(fb, false);
-        draw_reset(&self.board, fb);
+        self.draw_clock(fb);
+        self.score_button.draw(fb);
+        self.hint_button.draw(fb);
+        draw_back(fb);
         refresh(fb);
         let elapsed = start.elapsed();
         info!("redraw elapsed: {:.2?}", elapsed);
+        Ok(())
+    }
+
+    /// After a move, either just draws the new stones if `fast_stones` is on and nothing got
+    /// taken, or redraws everything
+    fn update_stones(&mut self, ctrl: &mut Engine, fb: &mut Framebuffer) -> Result<(), GtpError> {
+        if SETTINGS.flash_captures {
+            let captured = self.stone_cache.removed(ctrl)?;
+            self.board.flash_stones(fb, &captured);
+        }
+        // Legal moves change with every move, so they need the whole board redrawing
+        if !SETTINGS.fast_stones || SETTINGS.show_legal_moves {
+            return self.redraw_stones(ctrl, fb);
+        }
+        let Some(shown) = self.shown_stones.take() else {
+            return self.redraw_stones(ctrl, fb);
+        };
+        let start = Instant::now();
+        let stones = self.stone_cache.stones(ctrl)?;
+        let Some(added) = added_stones(&shown, &stones) else {
+            return self.redraw_stones(ctrl, fb);
+        };
+        let marker = self
+            .board
+            .draw_pieces(fb, &added)
+            .map(|rect| submit_refresh(fb, &rect, waveform_mode::WAVEFORM_MODE_DU));
+        self.draw_turn(fb, true);
+        let pass = self.current_turn == Turn::HumanTurn && self.machine_passed();
+        if pass != self.pass_shown {
+            self.pass_button.refresh_shown(fb, pass);
+            self.pass_shown = pass;
+        }
+        if let Some(marker) = marker {
+            wait_refresh(fb, marker);
+        }
+        self.shown_stones = Some(stones);
+        info!("fast stones elapsed: {:.2?}", start.elapsed());
+        Ok(())
+    }
+
+    fn draw_score_details(
+        &self,
+        fb: &mut Framebuffer,
+        score: &AreaScore,
+        white_stones: &[Point2<u8>],
+    ) {
+        match (&score.score, &score.dead_stones) {
+            (Some(_), Some(dead_stones)) => {
+                for stone in dead_stones {
+                    self.board.draw_dead_marker(
+                        fb,
+                        stone.x - 1,
+                        stone.y - 1,
+                        white_stones.contains(stone),
+                    );
+                }
+            }
+            _ => {
+                // GnuGo couldn't agree with itself, so show what it said and let the human decide
+                let text = format!("{} dead: {}", score.raw_score, score.raw_dead_stones);
+                draw_multiline_text(
+                    fb,
+                    Point2 {
+                        x: self.board.spare_width as f32,
+                        // Under the board, as the top is full of buttons
+                        y: (SCREEN_HEIGHT - 222) as f32,
+                    },
+                    &text.replace('\n', " "),
+                    40.0,
+                    25,
+                );
+            }
+        }
+    }
+
+    fn show_score(&mut self, ctrl: &mut Engine, fb: &mut Framebuffer) -> Result<(), GtpError> {
+        self.draw_status(fb, "Cleaning up...", true);
+        // Both sides get played out until the dead stones are taken, so they're not counted
+        let max_moves = self.board.board_size as usize * self.board.board_size as usize;
+        let cleanup = cleanup_dead_stones(ctrl, self.human, max_moves, |played| {
+            self.draw_status(fb, &format!("Cleaning up ({played})..."), true);
+        })?;
+        if cleanup > 0 {
+            self.cleaned_up = true;
+            self.stone_cache.board_changed();
+        }
+        self.draw_status(fb, "Scoring...", true);
+        self.score = Some(area_score(ctrl)?);
+        self.captures = Some(Captures {
+            black: count_captures(ctrl, "black")?,
+            white: count_captures(ctrl, "white")?,
+        });
+        self.showing_summary = true;
+        self.summary_message = None;
+        self.draw_summary(fb);
+        Ok(())
+    }
+
+    fn summary_lines(&self) -> Vec<String> {
+        let mut lines = vec![match (self.forfeit_result(), &self.score) {
+            (Some(result), _) => format!("Result: {result}"),
+            (
+                None,
+                Some(AreaScore {
+                    score: Some(ref final_score),
+                    ..
+                }),
+            ) => format!("Result: {final_score} ({} rules)", SETTINGS.rules),
+            (None, Some(ref score)) => format!("Result: {}", score.raw_score.trim()),
+            (None, None) => "Result: ?".to_string(),
+        }];
+        lines.push(format!(
+            "Moves: {} ({} turns)",
+            self.history.len(),
+            self.turns
+        ));
+        if let Some(ref captures) = self.captures {
+            lines.push(format!("Black took {}", captures.black));
+            lines.push(format!("White took {}", captures.white));
+        }
+        lines
+    }
+
+    fn draw_summary(&mut self, fb: &mut Framebuffer) {
+        self.shown_stones = None;
+        fb.clear();
+        for (index, line) in self.summary_lines().iter().enumerate() {
+            fb.draw_text(
+                Point2 {
+                    x: self.board.spare_width as f32,
+                    y: 200.0 + 130.0 * index as f32,
+                },
+                line,
+                100.0,
+                color::BLACK,
+                false,
+            );
+        }
+        for button in summary_buttons() {
+            button.draw(fb);
+        }
+        review_button().draw(fb);
+        if let Some(ref message) = self.summary_message {
+            draw_multiline_text(
+                fb,
+                Point2 {
+                    x: self.board.spare_width as f32,
+                    y: 1600.0,
+                },
+                message,
+                40.0,
+                50,
+            );
+        }
+        draw_back(fb);
+        refresh(fb);
+    }
+
+    /// Every move so far from `history`, and the result if there is one
+    fn game_sgf(&self) -> String {
+        let result = self
+            .forfeit_result()
+            .map(str::to_string)
+            .or(match self.score {
+                Some(AreaScore {
+                    score: Some(ref result),
+                    ..
+                }) => Some(result.sgf_result()),
+                _ => None,
+            });
+        game_sgf(self.board.board_size, &self.history, result.as_deref())
+    }
+
+    fn save_game(&mut self) {
+        let sgf = self.game_sgf();
+        self.summary_message = Some(match save_sgf("machine", &sgf) {
+            Ok(path) => format!("Saved to {}", path.display()),
+            Err(err) => {
+                warn!("Can't save game: {err}");
+                format!("Can't save: {err}")
+            }
+        });
+    }
+
+    fn handle_summary_press(
+        &mut self,
+        ctrl: &mut Engine,
+        fb: &mut Framebuffer,
+        pos: Point2<u16>,
+    ) -> Result<(), GtpError> {
+        match pressed(&summary_buttons(), pos) {
+            Some(button) => self.summary_action(ctrl, fb, button.action),
+            None => Ok(()),
+        }
+    }
+
+    fn summary_action(
+        &mut self,
+        ctrl: &mut Engine,
+        fb: &mut Framebuffer,
+        action: SummaryAction,
+    ) -> Result<(), GtpError> {
+        match action {
+            SummaryAction::SaveSgf => {
+                self.save_game();
+                self.draw_summary(fb);
+            }
+            SummaryAction::NewGame => self.reset_game(ctrl, fb)?,
+            SummaryAction::Rematch => {
+                self.human = self.human.other();
+                info!("Rematch, with the human as {:?}", self.human);
+                self.reset_game(ctrl, fb)?;
+            }
+            SummaryAction::Back => {
+                self.showing_summary = false;
+                if self.cleaned_up {
+                    // Back to how the game was, so it can carry on
+                    self.draw_status(fb, "Restoring...", true);
+                    replay_moves(ctrl, self.board.board_size, &self.history)?;
+                    self.cleaned_up = false;
+                    self.stone_cache.board_changed();
+                }
+                self.redraw_stones(ctrl, fb)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn toggle_hint(&mut self, ctrl: &mut Engine, fb: &mut Framebuffer) -> Result<(), GtpError> {
+        if self.hint.take().is_some() {
+            return self.redraw_stones(ctrl, fb);
+        }
+        self.draw_status(fb, "Thinking...", true);
+        match suggest_move(ctrl, self.human.gtp_name())? {
+            Some(point) => {
+                let rect = self.board.draw_hint_marker(fb, point.x - 1, point.y - 1);
+                self.shown_stones = None;
+                // The marker and the status can both update at once
+                let marker = submit_refresh(fb, &rect, waveform_mode::WAVEFORM_MODE_DU);
+                self.hint = Some(point);
+                self.draw_turn(fb, true);
+                wait_refresh(fb, marker);
+            }
+            None => self.draw_status(fb, "Hint: pass", true),
+        }
+        Ok(())
+    }
+
+    fn toggle_influence(
+        &mut self,
+        ctrl: &mut Engine,
+        fb: &mut Framebuffer,
+    ) -> Result<(), GtpError> {
+        if self.influence.take().is_none() {
+            self.draw_status(fb, "Thinking...", true);
+            self.influence = Some(influence_regions(ctrl, self.human, self.board.board_size)?);
+        }
+        self.redraw_stones(ctrl, fb)
+    }
+
+    fn toggle_inspect(&mut self, ctrl: &mut Engine, fb: &mut Framebuffer) -> Result<(), GtpError> {
+        self.inspecting = !self.inspecting;
+        self.double_tap.clear();
+        self.redraw_stones(ctrl, fb)
+    }
+
+    /// Shows what GnuGo thinks of the group on `point`, which is 0-based
+    fn inspect(
+        &mut self,
+        ctrl: &mut Engine,
+        fb: &mut Framebuffer,
+        point: Point2<u8>,
+    ) -> Result<(), GtpError> {
+        self.draw_status(fb, "Thinking...", true);
+        let status = group_status(
+            ctrl,
+            Point2 {
+                x: point.x + 1,
+                y: point.y + 1,
+            },
+        )?;
+        info!("Group at {point:?} is {status:?}");
+        let text = match status {
+            Some(GroupStatus::Alive) => "Alive",
+            Some(GroupStatus::Dead) => "Dead",
+            Some(GroupStatus::Seki) => "Seki",
+            None => "No stone there",
+        };
+        self.draw_status(fb, text, true);
+        Ok(())
+    }
+
+    /// With `confirm_moves`, tapping the board just shows the move, and "Commit" plays it
+    fn handle_choice_press(
+        &mut self,
+        ctrl: &mut Engine,
+        fb: &mut Framebuffer,
+        pos: Point2<u16>,
+    ) -> Result<(), GtpError> {
+        let (white_stones, black_stones) = self.stone_cache.stones(ctrl)?;
+        let empty = self.board.pixel_to_point(pos).filter(|point| {
+            let gtp_point = Point2 {
+                x: point.x + 1,
+                y: point.y + 1,
+            };
+            !white_stones.contains(&gtp_point) && !black_stones.contains(&gtp_point)
+        });
+        match self.choice.handle_press(pos, empty) {
+            ChoicePress::Nothing => Ok(()),
+            ChoicePress::Picked => {
+                // Undo and Commit go over the inspect and pass buttons, so only influence needs
+                // clearing
+                self.influence_button.refresh_shown(fb, false);
+                self.choice
+                    .draw_picked(fb, &self.board, self.human == Stone::White);
+                // Drawn over the board, so fast_stones can't just add to it
+                self.shown_stones = None;
+                self.pass_shown = false;
+                Ok(())
+            }
+            ChoicePress::Changed => self.redraw_stones(ctrl, fb),
+            ChoicePress::Commit(point) => {
+                // Clears the buttons, even if GnuGo turns the move down
+                self.redraw_stones(ctrl, fb)?;
+                self.play_move(ctrl, fb, point)
+            }
+        }
+    }
+
+    fn choose_resume(&mut self, ctrl: &mut Engine, fb: &mut Framebuffer, choice: ResumeChoice) {
+        let Some(state) = self.resume.take() else {
+            return;
+        };
+        let started = match choice {
+            ResumeChoice::Resume => {
+                info!("Resuming {} moves", state.history().len());
+                self.human = state.player;
+                self.start_game(ctrl, fb, state.history())
+            }
+            ResumeChoice::NewGame => self.reset_game(ctrl, fb),
+        };
+        if let Err(err) = started {
+            // Most likely GnuGo wouldn't take one of the saved moves, so don't offer it again
+            clear_state();
+            self.current_turn = Turn::HumanTurn;
+            self.show_engine_error(fb, err);
+        }
+    }
+
+    fn play_move(
+        &mut self,
+        ctrl: &mut Engine,
+        fb: &mut Framebuffer,
+        point: Point2<u8>,
+    ) -> Result<(), GtpError> {
+        if self.forfeit_result().is_some() {
+            return Ok(());
+        }
+        let now = Instant::now();
+        if self
+            .clock
+            .as_ref()
+            .is_some_and(|clock| clock.is_out_of_time(now))
+        {
+            // Ran out just before update_loop noticed
+            self.lose_on_time(fb);
+            return Ok(());
+        }
+        if SETTINGS.warn_self_atari {
+            let warned = self.self_atari.take();
+            if warned != Some(point) {
+                if warned.is_some() {
+                    // Took the warning, so put the turn back in case this move doesn't work out
+                    self.draw_turn(fb, true);
+                }
+                let stones = self.stone_cache.stones(ctrl)?;
+                if is_self_atari(&stones, self.board.board_size, point, self.human) {
+                    info!("Self-atari at {point:?}");
+                    self.self_atari = Some(point);
+                    self.draw_status(fb, "Self-atari!", true);
+                    return Ok(());
+                }
+            }
+        }
+        if !do_human_move(ctrl, point, self.human.gtp_name())?.played() {
+            info!("Bad human move");
+            return Ok(());
+        }
+        if let Some(ref mut clock) = self.clock {
+            clock.stop(now);
+        }
+        self.stone_cache.board_changed();
+        self.history.push((self.human, Some(point)));
+        self.save_state();
+        self.score = None;
+        self.hint = None;
+        self.influence = None;
+        self.current_turn = Turn::MachineTurn;
+        self.update_stones(ctrl, fb)?;
+        self.start_machine_move(ctrl, fb)
     }
 }
 
 impl Routine for MachineGame {
-    fn init(&mut self, fb: &mut Framebuffer, ctrl: &mut Engine) {
-        set_board_size(ctrl, self.board.board_size);
-        self.reset_game(ctrl, fb);
-        self.set_turn(Turn::HumanTurn, fb);
+    fn init(&mut self, fb: &'static mut Framebuffer, ctrl: &mut Engine) {
+        self.resume = load_state(LocalGame::Machine, self.board.board_size);
+        if self.resume.is_some() {
+            draw_resume_choice(fb);
+            self.fb = Some(fb);
+            return;
+        }
+        let asked = self.board.board_size;
+        let started = set_supported_board_size(ctrl, asked).and_then(|size| {
+            if size != asked {
+                *self = MachineGame::with_size(size);
+            }
+            self.reset_game(ctrl, fb)
+        });
+        match started {
+            Ok(()) if self.board.board_size != asked => {
+                let text = board_size_fallback_text(asked, self.board.board_size);
+                self.draw_status(fb, &text, true);
+            }
+            Ok(()) => {}
+            Err(err) => {
+                self.current_turn = Turn::HumanTurn;
+                self.show_engine_error(fb, err);
+            }
+        }
+        self.fb = Some(fb);
+    }
+
+    fn update_loop(&mut self, ctrl: &mut Engine) -> Option<Duration> {
+        if self.clock.is_some() && self.current_turn == Turn::HumanTurn {
+            if let Some(fb) = self.fb.take() {
+                self.update_clock(fb);
+                flush_refresh(fb);
+                let _empty = self.fb.insert(fb);
+            }
+        }
+        if self.machine_started.is_some() {
+            if let Some(fb) = self.fb.take() {
+                if let Err(err) = self.check_machine_move(ctrl, fb) {
+                    self.machine_started = None;
+                    self.cancel_button.refresh_shown(fb, false);
+                    self.current_turn = Turn::HumanTurn;
+                    self.show_engine_error(fb, err);
+                }
+                flush_refresh(fb);
+                let _empty = self.fb.insert(fb);
+            } else {
+                error!("No framebuffer!");
+            }
+            return Some(MACHINE_POLL_INTERVAL);
+        }
+        // Wakes up as the clock ticks over, rather than polling for nothing. Touches wake it
+        // anyway, so it notices a move straight away
+        let clock_tick = match (self.current_turn, &self.clock) {
+            (Turn::HumanTurn, Some(clock)) if !self.time_up => {
+                clock.until_next_tick(Instant::now())
+            }
+            _ => None,
+        };
+        Some(clock_tick.unwrap_or(MACHINE_POLL_INTERVAL))
+    }
+
+    fn on_leave(&mut self, ctrl: &mut Engine) {
+        if self.machine_started.take().is_some() {
+            // Otherwise the next mode would get the answer to our genmove
+            warn!("Restarting GnuGo to abandon its move");
+            if let Err(err) = restart_engine(ctrl) {
+                error!("Can't restart GnuGo: {err}");
+            }
+        }
     }
 
     fn on_multitouch_event(
@@ -127,43 +1138,225 @@ impl Routine for MachineGame {
         event: MultitouchEvent,
         ctrl: &mut Engine,
     ) {
-        match event {
-            MultitouchEvent::Press { finger } => {
-                if self.current_turn != Turn::HumanTurn {
-                    info!("Ignoring touch, as machine turn");
+        if self.resume.is_some() {
+            if let Some(choice) = resume_choice(&mut self.hold, ctx, &event) {
+                self.choose_resume(ctrl, ctx.get_framebuffer_ref(), choice);
+            }
+            return;
+        }
+
+        if self.showing_summary {
+            let fb = ctx.get_framebuffer_ref();
+            match self.hold.handle(fb, &summary_buttons(), &event) {
+                Held::Fired(action) => {
+                    if let Err(err) = self.summary_action(ctrl, fb, action) {
+                        self.show_engine_error(fb, err);
+                    }
                     return;
                 }
-                let fb = ctx.get_framebuffer_ref();
+                Held::Holding => return,
+                Held::Ignored => {}
+            }
+        }
+
+        if let MultitouchEvent::Press { finger } = event {
+            let fb = ctx.get_framebuffer_ref();
+
+            if handle_back(ctx, finger.pos) {
+                return;
+            }
+
+            if self.machine_started.is_some() && self.cancel_button.contains(finger.pos) {
+                if let Err(err) = self.cancel_machine_move(ctrl, fb) {
+                    self.show_engine_error(fb, err);
+                }
+                return;
+            }
 
-                let rbtl = reset_button_top_left(&self.board);
-                if (finger.pos.x as i32) >= rbtl.x
-                    && (finger.pos.x as i32) < (rbtl.x + RESET_BUTTON_SIZE.x as i32)
-                    && (finger.pos.y as i32) >= rbtl.y
-                    && (finger.pos.y as i32) < (rbtl.y + RESET_BUTTON_SIZE.y as i32)
-                {
-                    *CURRENT_MODE.lock().unwrap() = crate::chooser::Mode::Chooser;
-                    ctx.stop();
-                    return;
+            if self.current_turn != Turn::HumanTurn {
+                info!("Ignoring touch, as machine turn");
+                return;
+            }
+
+            if self.showing_summary && review_button().contains(finger.pos) {
+                // Done with, so coming back here starts a new game rather than offering this one
+                clear_state();
+                review_just_played(ctx, self.game_sgf(), Mode::AgainstMachine);
+                return;
+            }
+
+            if self.showing_summary {
+                if let Err(err) = self.handle_summary_press(ctrl, fb, finger.pos) {
+                    self.show_engine_error(fb, err);
                 }
+                return;
+            }
 
-                let point = self.board.nearest_spot(finger.pos.x, finger.pos.y);
-                let pos = finger.pos;
-                if point.x >= self.board.board_size || point.y >= self.board.board_size {
-                    info!("Bad point {point:?}");
-                    return;
+            if self.score_button.contains(finger.pos) {
+                if let Err(err) = self.show_score(ctrl, fb) {
+                    self.show_engine_error(fb, err);
+                }
+                return;
+            }
+
+            if self.hint_button.contains(finger.pos) {
+                if let Err(err) = self.toggle_hint(ctrl, fb) {
+                    self.show_engine_error(fb, err);
+                }
+                return;
+            }
+
+            if self.choice.chosen.is_none() && self.influence_button.contains(finger.pos) {
+                if let Err(err) = self.toggle_influence(ctrl, fb) {
+                    self.show_engine_error(fb, err);
                 }
-                info!("Drawing: {point:?} for {pos:?}");
-                if !do_human_move(ctrl, point, "white") {
-                    info!("Bad human move");
+                return;
+            }
+
+            if self.choice.chosen.is_none() && self.inspect_button().contains(finger.pos) {
+                if let Err(err) = self.toggle_inspect(ctrl, fb) {
+                    self.show_engine_error(fb, err);
+                }
+                return;
+            }
+
+            if self.choice.chosen.is_none()
+                && self.machine_passed()
+                && self.pass_button.contains(finger.pos)
+            {
+                if let Err(err) = self.pass_too(ctrl, fb) {
+                    self.show_engine_error(fb, err);
+                }
+                return;
+            }
+
+            if self.inspecting {
+                let Some(point) = self.board.pixel_to_point(finger.pos) else {
+                    info!("Bad point {:?}", finger.pos);
                     return;
+                };
+                if let Err(err) = self.inspect(ctrl, fb, point) {
+                    self.show_engine_error(fb, err);
                 }
-                self.set_turn(Turn::MachineTurn, fb);
-                self.redraw_stones(ctrl, fb);
-                do_machine_move(ctrl);
-                self.redraw_stones(ctrl, fb);
-                self.set_turn(Turn::HumanTurn, fb);
+                return;
             }
-            _ => {}
+
+            if SETTINGS.confirm_moves {
+                if let Err(err) = self.handle_choice_press(ctrl, fb, finger.pos) {
+                    self.current_turn = Turn::HumanTurn;
+                    self.show_engine_error(fb, err);
+                }
+                return;
+            }
+
+            let pos = finger.pos;
+            let Some(point) = self.board.pixel_to_point(pos) else {
+                info!("Bad point {pos:?}");
+                self.double_tap.clear();
+                return;
+            };
+            if !self
+                .double_tap
+                .confirm(point, Instant::now(), SETTINGS.double_tap_window())
+            {
+                self.draw_status(fb, "Tap again to play", true);
+                return;
+            }
+            info!("Drawing: {point:?} for {pos:?}");
+            if let Err(err) = self.play_move(ctrl, fb, point) {
+                // Let the human try again, rather than leaving the game stuck on the machine
+                self.current_turn = Turn::HumanTurn;
+                self.show_engine_error(fb, err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use libremarkable::framebuffer::common::mxcfb_rect;
+    use pretty_assertions::assert_eq;
+
+    use crate::{
+        drawing::{scaled, Button},
+        machine_game::{review_button, summary_buttons, MachineGame},
+        reset::back_button,
+    };
+
+    fn overlaps(a: &mxcfb_rect, b: &mxcfb_rect) -> bool {
+        a.left < b.left + b.width
+            && b.left < a.left + a.width
+            && a.top < b.top + b.height
+            && b.top < a.top + a.height
+    }
+
+    fn button_rect<A>(button: &Button<A>) -> mxcfb_rect {
+        mxcfb_rect {
+            top: button.top_left.y as u32,
+            left: button.top_left.x as u32,
+            width: button.size.x,
+            height: button.size.y,
+        }
+    }
+
+    #[test]
+    fn clock_has_its_own_space() {
+        let game = MachineGame::new();
+        let clock = game.clock_hud.as_ref().unwrap().region();
+        let board = &game.board;
+        // Including the top row of stones, which stick out above the grid
+        let board_top = (board.spare_height - board.circle_radius) as u32;
+        assert!(clock.top + clock.height <= board_top);
+        // Status and its note, all the way across
+        assert!(clock.top >= scaled(100));
+        for button in [&game.score_button, &game.hint_button] {
+            assert!(!overlaps(&clock, &button_rect(button)));
+        }
+        assert!(!overlaps(&clock, &button_rect(&back_button())));
+    }
+
+    #[test]
+    fn influence_below_board() {
+        let game = MachineGame::new();
+        let board = &game.board;
+        // Including the bottom row of stones, which stick out below the grid
+        let board_bottom = board.spare_height + board.grid_size() + board.circle_radius;
+        assert!(game.influence_button.top_left.y as u16 > board_bottom);
+    }
+
+    #[test]
+    fn inspect_clear_of_influence() {
+        let game = MachineGame::new();
+        let inspect = game.inspect_button();
+        assert!(!overlaps(
+            &button_rect(&inspect),
+            &button_rect(&game.influence_button)
+        ));
+        assert_eq!(game.influence_button.top_left.y, inspect.top_left.y);
+    }
+
+    #[test]
+    fn pass_clear_of_other_buttons() {
+        let game = MachineGame::new();
+        let pass = button_rect(&game.pass_button);
+        assert!(!overlaps(&pass, &button_rect(&game.influence_button)));
+        assert!(!overlaps(&pass, &button_rect(&game.inspect_button())));
+    }
+
+    #[test]
+    fn cancel_where_pass_goes() {
+        let game = MachineGame::new();
+        assert_eq!(
+            button_rect(&game.pass_button),
+            button_rect(&game.cancel_button)
+        );
+    }
+
+    #[test]
+    fn review_clear_of_summary_buttons() {
+        let review = button_rect(&review_button());
+        for button in summary_buttons() {
+            assert!(!overlaps(&review, &button_rect(&button)));
         }
     }
 }
@@ -1,54 +1,145 @@
 use std::{
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc, Mutex,
+        Arc, Mutex, MutexGuard,
     },
-    thread::{park_timeout, spawn, JoinHandle},
+    thread::{park_timeout, sleep, spawn, JoinHandle},
+    time::{Duration, Instant},
 };
 
-use ::gtp::controller::Engine;
-use libremarkable::{appctx, input::InputEvent};
-use log::info;
+use libremarkable::{
+    appctx,
+    cgmath::Point2,
+    framebuffer::{core::Framebuffer, FramebufferDraw},
+    input::{GPIOEvent, InputEvent, PhysicalButton},
+};
+use log::{error, info, warn};
 
 use crate::{
     chooser::{Mode, CURRENT_MODE},
+    drawing::{draw_multiline_text, flush_refresh, refresh, text_size},
+    engine::{Engine, RemoteEngine},
+    gtp::wait_until_ready,
+    reset::{go_back, FingerTracker, Touch},
     routine::Routine,
+    settings::SETTINGS,
 };
 
+mod analysis;
 mod atari_game;
 mod board;
+mod calibration;
 mod chooser;
+mod clock;
+mod demo;
+mod diagnostics;
 mod dragon_go_server;
 mod drawing;
+mod engine;
 mod game_parse;
+mod game_state;
+mod go_grid;
 mod gtp;
+mod keyboard;
+mod log_buffer;
+mod log_viewer;
+mod login_entry;
 mod machine_game;
+mod move_choice;
+mod problem;
 mod reset;
+mod review;
 mod routine;
+mod settings;
+mod sgf;
 
-fn main() {
-    env_logger::init();
-    let mut app: appctx::ApplicationContext<'_> = appctx::ApplicationContext::default();
+// Long enough to read why it's not starting before going back to the launcher
+const STARTUP_ERROR_DISPLAY: Duration = Duration::from_secs(30);
+
+/// Whole screen message for before there's a mode to draw anything
+fn draw_startup_message(fb: &mut Framebuffer, text: &str) {
+    fb.clear();
+    draw_multiline_text(fb, Point2 { x: 100.0, y: 900.0 }, text, text_size(), 30);
+    refresh(fb);
+    flush_refresh(fb);
+}
 
+/// Locks `mutex` even if a thread panicked while holding it, so one failure doesn't take every
+/// later touch down with it. Whatever's inside is used as it was left
+pub fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| {
+        warn!("Lock was poisoned, carrying on anyway");
+        poisoned.into_inner()
+    })
+}
+
+fn new_engine() -> Engine {
+    if let Some(ref address) = SETTINGS.engine_address {
+        info!("Using GTP engine at {address}");
+        return Engine::Remote(RemoteEngine::new(address));
+    }
     info!("Starting GnuGo");
     let gnugo_path = std::env::var("GNUGO_BINARY").unwrap_or("/home/root/gnugo".into());
-    let mut ctrl = Engine::new(&gnugo_path, &["--mode", "gtp", "--level", "8"]);
-    ctrl.start().expect("Failure to launch gnugo");
+    let gnugo_args = SETTINGS.gnugo_args();
+    info!("GnuGo arguments: {gnugo_args:?}");
+    let gnugo_args: Vec<&str> = gnugo_args.iter().map(String::as_str).collect();
+    Engine::Local(Box::new(::gtp::controller::Engine::new(
+        &gnugo_path,
+        &gnugo_args,
+    )))
+}
+
+fn start_engine(engine: &mut Engine) -> Result<(), String> {
+    let name = match SETTINGS.engine_address {
+        Some(ref address) => format!("the engine at {address}"),
+        None => "Gnu Go".to_string(),
+    };
+    engine
+        .start()
+        .map_err(|err| format!("Can't start {name}: {err:?}"))?;
+    wait_until_ready(engine).map_err(|err| format!("{name} isn't responding: {err}"))
+}
+
+fn main() {
+    log_buffer::init(SETTINGS.log_level.as_deref());
+    let mut app: appctx::ApplicationContext<'_> = appctx::ApplicationContext::default();
+
+    let mut engine = new_engine();
+    draw_startup_message(app.get_framebuffer_ref(), "Starting engine...");
+    if let Err(err) = start_engine(&mut engine) {
+        error!("{err}");
+        draw_startup_message(app.get_framebuffer_ref(), &err);
+        sleep(STARTUP_ERROR_DISPLAY);
+        std::process::exit(1);
+    }
+    // Shared with the update thread, so the machine game can wait on GnuGo without blocking touches
+    let ctrl = Arc::new(Mutex::new(engine));
     info!("Init complete. Beginning event dispatch...");
 
+    *lock(&CURRENT_MODE) = SETTINGS.start_mode();
+
     let mut previous_mode: Option<Mode> = None;
     let thread_running = Arc::new(AtomicBool::new(false));
 
     let mut current_thread: Option<JoinHandle<_>> = None;
+    let mut fingers = FingerTracker::default();
 
     loop {
         info!("Starting mode loop");
-        let current_mode = *CURRENT_MODE.lock().expect("Working lock");
+        let current_mode = *lock(&CURRENT_MODE);
         let current_routine: Box<dyn Routine> = match current_mode {
-            Mode::Chooser => Box::new(chooser::Chooser {}),
+            Mode::Chooser => Box::new(chooser::Chooser::new()),
             Mode::AgainstMachine => Box::new(machine_game::MachineGame::new()),
             Mode::Atari => Box::new(atari_game::AtariGame::new()),
             Mode::DragonGoServer => Box::new(dragon_go_server::DragonGoServer::new()),
+            Mode::DragonGoLogin => Box::new(login_entry::LoginEntry::new()),
+            Mode::Analysis => Box::new(analysis::Analysis::new()),
+            Mode::Diagnostics => Box::new(diagnostics::Diagnostics::new()),
+            Mode::Review => Box::new(review::Review::new()),
+            Mode::Problems => Box::new(problem::Problems::new()),
+            Mode::Calibration => Box::new(calibration::Calibration::new()),
+            Mode::Demo => Box::new(demo::Demo::new()),
+            Mode::Log => Box::new(log_viewer::LogViewer::new()),
             Mode::Exit => {
                 break;
             }
@@ -58,23 +149,17 @@ fn main() {
         if previous_mode.is_none() || current_mode != previous_mode.unwrap_or(Mode::Chooser) {
             info!("New mode: {current_mode:?}");
             let fb = app.get_framebuffer_ref();
-            arc_routine
-                .lock()
-                .expect("Can get routine")
-                .init(fb, &mut ctrl);
-            if let Some(old_thread) = current_thread {
-                thread_running.store(false, Ordering::Relaxed);
-                old_thread.thread().unpark();
-                old_thread.join().unwrap();
-            }
+            lock(&arc_routine).init(fb, &mut lock(&ctrl));
+            flush_refresh(app.get_framebuffer_ref());
             thread_running.store(true, Ordering::Relaxed);
             let local_thread_running = thread_running.clone();
+            let local_ctrl = ctrl.clone();
             current_thread = Some(spawn(move || loop {
                 if !local_thread_running.load(Ordering::Relaxed) {
                     info!("End of thread");
                     break;
                 }
-                let how_long = local_routine.lock().expect("can unlock").update_loop();
+                let how_long = lock(&local_routine).update_loop(&mut lock(&local_ctrl));
                 match how_long {
                     Some(to_wait) => {
                         park_timeout(to_wait);
@@ -87,16 +172,74 @@ fn main() {
         }
         previous_mode = Some(current_mode);
         info!("start event loop");
-        app.start_event_loop(false, true, false, |ctx, evt| match evt {
-            InputEvent::MultitouchEvent { event } => {
-                arc_routine
-                    .lock()
-                    .expect("Get routine")
-                    .on_multitouch_event(ctx, event, &mut ctrl);
+        app.start_event_loop(false, true, true, |ctx, evt| match evt {
+            InputEvent::MultitouchEvent { event } => match fingers.track(&event, Instant::now()) {
+                Touch::Escape => go_back(ctx),
+                Touch::Ignore => {}
+                Touch::Pass => {
+                    lock(&arc_routine).on_multitouch_event(ctx, event, &mut lock(&ctrl));
+                    flush_refresh(ctx.get_framebuffer_ref());
+                    // Otherwise it might sleep for a while before noticing what the touch did
+                    if let Some(ref update_thread) = current_thread {
+                        update_thread.thread().unpark();
+                    }
+                }
+            },
+            InputEvent::GPIO {
+                event: GPIOEvent::Press { button },
+            } => {
+                info!("Button: {button:?}");
+                if button == PhysicalButton::MIDDLE {
+                    go_back(ctx);
+                    return;
+                }
+                lock(&arc_routine).on_button_event(ctx, button, &mut lock(&ctrl));
+                flush_refresh(ctx.get_framebuffer_ref());
+                if let Some(ref update_thread) = current_thread {
+                    update_thread.thread().unpark();
+                }
             }
             ev => {
                 info!("event: {ev:?}");
             }
         });
+        if *lock(&CURRENT_MODE) != current_mode {
+            // Stop the old update thread first, so it can't touch the engine or screen once the
+            // routine has left, or after the next one has started
+            if let Some(old_thread) = current_thread.take() {
+                thread_running.store(false, Ordering::Relaxed);
+                old_thread.thread().unpark();
+                if let Err(err) = old_thread.join() {
+                    error!("Update thread panicked: {err:?}");
+                }
+            }
+            lock(&arc_routine).on_leave(&mut lock(&ctrl));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::{
+        sync::{Arc, Mutex},
+        thread::spawn,
+    };
+
+    use crate::lock;
+
+    #[test]
+    fn poisoned_lock() {
+        let mutex = Arc::new(Mutex::new(1));
+        let local = mutex.clone();
+        let result = spawn(move || {
+            let mut guard = local.lock().unwrap();
+            *guard = 2;
+            panic!("Poisoning the lock");
+        })
+        .join();
+        assert!(result.is_err());
+        assert!(mutex.is_poisoned());
+        assert_eq!(2, *lock(&mutex));
     }
 }
@@ -0,0 +1,279 @@
+use libremarkable::{
+    cgmath::{Point2, Vector2},
+    framebuffer::core::Framebuffer,
+};
+use log::info;
+use std::time::{Duration, Instant};
+
+use crate::{
+    board::Board,
+    drawing::{pressed_index, scaled_size, Button},
+    reset::back_button_top_left,
+};
+
+pub const UNDO_BUTTON_SIZE: Vector2<u32> = Vector2 { x: 250, y: 95 };
+pub const COMMIT_BUTTON_SIZE: Vector2<u32> = Vector2 { x: 350, y: 95 };
+const BUTTON_GAP: i32 = 120;
+
+pub enum ChoicePress {
+    Nothing,
+    /// Picked a point with nothing chosen before, so just the stone and buttons need drawing,
+    /// e.g. with `draw_picked`
+    Picked,
+    /// Moved the choice or took it back, so it needs drawing again
+    Changed,
+    Commit(Point2<u8>),
+}
+
+/// A move shown on the board but not played yet, with "Undo" and "Commit" buttons for it
+pub struct MoveChoice {
+    /// 0-based
+    pub chosen: Option<Point2<u8>>,
+    undo_button: Button<()>,
+    commit_button: Button<()>,
+}
+
+impl MoveChoice {
+    /// Buttons in the top row, going leftwards from the back button. "Commit" drops to
+    /// `second_row` under the back button if there's no room for it
+    pub fn top_right(second_row: i32) -> MoveChoice {
+        let undo_size = scaled_size(UNDO_BUTTON_SIZE);
+        let commit_size = scaled_size(COMMIT_BUTTON_SIZE);
+        let undo_x = back_button_top_left().x - BUTTON_GAP - undo_size.x as i32;
+        let mut commit_top_left = Point2 {
+            x: undo_x - BUTTON_GAP - commit_size.x as i32,
+            y: 20,
+        };
+        if commit_top_left.x < 0 {
+            commit_top_left = Point2 {
+                x: back_button_top_left().x,
+                y: second_row,
+            };
+        }
+        MoveChoice {
+            chosen: None,
+            undo_button: Button::new("Undo", Point2 { x: undo_x, y: 20 }, undo_size, ()),
+            commit_button: Button::new("Commit", commit_top_left, commit_size, ()),
+        }
+    }
+
+    /// "Undo" on the left edge of the board at `y`, and "Commit" on the right
+    pub fn below_board(board: &Board, y: i32) -> MoveChoice {
+        let commit_size = scaled_size(COMMIT_BUTTON_SIZE);
+        let right = (board.spare_width + board.grid_size()) as i32;
+        MoveChoice {
+            chosen: None,
+            undo_button: Button::new(
+                "Undo",
+                Point2 {
+                    x: board.spare_width as i32,
+                    y,
+                },
+                scaled_size(UNDO_BUTTON_SIZE),
+                (),
+            ),
+            commit_button: Button::new(
+                "Commit",
+                Point2 {
+                    x: right - commit_size.x as i32,
+                    y,
+                },
+                commit_size,
+                (),
+            ),
+        }
+    }
+
+    /// The chosen stone and the buttons, if there's anything chosen
+    pub fn draw(&self, fb: &mut Framebuffer, board: &Board, white: bool) {
+        if let Some(ref chosen) = self.chosen {
+            self.undo_button.draw(fb);
+            self.commit_button.draw(fb);
+            board.draw_piece(fb, chosen.x, chosen.y, white);
+        }
+    }
+
+    /// Just the chosen stone and the buttons, each refreshed on its own, for after `Picked`
+    /// when nothing else needs drawing again
+    pub fn draw_picked(&self, fb: &mut Framebuffer, board: &Board, white: bool) {
+        if let Some(ref chosen) = self.chosen {
+            board.refresh_and_draw_one_piece(fb, chosen.x, chosen.y, white);
+            self.undo_button.refresh_shown(fb, true);
+            self.commit_button.refresh_shown(fb, true);
+        }
+    }
+
+    /// `point` is the empty point that was pressed, if it was one
+    pub fn handle_press(&mut self, pos: Point2<u16>, point: Option<Point2<u8>>) -> ChoicePress {
+        if let Some(chosen) = self.chosen {
+            // Side by side in the top row, so they go by whichever's nearer
+            match pressed_index([&self.undo_button, &self.commit_button], pos) {
+                Some(0) => {
+                    self.chosen = None;
+                    return ChoicePress::Changed;
+                }
+                Some(_) => {
+                    self.chosen = None;
+                    return ChoicePress::Commit(chosen);
+                }
+                None => {}
+            }
+        }
+        match point {
+            Some(point) if self.chosen != Some(point) => {
+                info!("Choosing {point:?} for {pos:?}");
+                match self.chosen.replace(point) {
+                    Some(_) => ChoicePress::Changed,
+                    None => ChoicePress::Picked,
+                }
+            }
+            _ => ChoicePress::Nothing,
+        }
+    }
+}
+
+/// For `double_tap_ms`, where a move only gets played once the same point's been tapped twice in
+/// a row, close enough together
+#[derive(Default)]
+pub struct DoubleTap {
+    /// The first tap, waiting for a second
+    pending: Option<(Point2<u8>, Instant)>,
+}
+
+impl DoubleTap {
+    /// Whether a tap on `point` at `now` should play it. If not, it's the first tap of the next
+    /// try. No `window` means every tap plays
+    pub fn confirm(&mut self, point: Point2<u8>, now: Instant, window: Option<Duration>) -> bool {
+        let Some(window) = window else {
+            return true;
+        };
+        match self.pending.take() {
+            Some((pending, at)) if pending == point && now.duration_since(at) <= window => true,
+            _ => {
+                info!("Waiting for a second tap on {point:?}");
+                self.pending = Some((point, now));
+                false
+            }
+        }
+    }
+
+    /// Forgets the first tap, e.g. when something else gets pressed
+    pub fn clear(&mut self) {
+        self.pending = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use libremarkable::cgmath::Point2;
+    use pretty_assertions::assert_eq;
+    use std::time::{Duration, Instant};
+
+    use crate::{
+        board::Board,
+        move_choice::{ChoicePress, DoubleTap, MoveChoice},
+    };
+
+    #[test]
+    fn choose_then_commit() {
+        let board = Board::new(9);
+        let mut choice = MoveChoice::below_board(&board, 1700);
+        let point = Point2 { x: 2, y: 3 };
+        let on_board = Point2 { x: 700, y: 900 };
+        assert!(matches!(
+            choice.handle_press(on_board, Some(point)),
+            ChoicePress::Picked
+        ));
+        assert_eq!(Some(point), choice.chosen);
+        // Same point again does nothing, as it's already shown
+        assert!(matches!(
+            choice.handle_press(on_board, Some(point)),
+            ChoicePress::Nothing
+        ));
+        // Somewhere else moves it, which needs the old stone clearing
+        let moved = Point2 { x: 4, y: 3 };
+        assert!(matches!(
+            choice.handle_press(on_board, Some(moved)),
+            ChoicePress::Changed
+        ));
+        assert!(matches!(
+            choice.handle_press(on_board, Some(point)),
+            ChoicePress::Changed
+        ));
+        let commit = choice.commit_button.top_left;
+        let commit = Point2 {
+            x: commit.x as u16 + 10,
+            y: commit.y as u16 + 10,
+        };
+        assert!(matches!(
+            choice.handle_press(commit, None),
+            ChoicePress::Commit(played) if played == point
+        ));
+        assert_eq!(None, choice.chosen);
+        // Nothing chosen, so the buttons aren't there
+        assert!(matches!(
+            choice.handle_press(commit, None),
+            ChoicePress::Nothing
+        ));
+    }
+
+    #[test]
+    fn undo() {
+        let board = Board::new(9);
+        let mut choice = MoveChoice::below_board(&board, 1700);
+        choice.chosen = Some(Point2 { x: 0, y: 0 });
+        let undo = choice.undo_button.top_left;
+        let undo = Point2 {
+            x: undo.x as u16 + 10,
+            y: undo.y as u16 + 10,
+        };
+        assert!(matches!(
+            choice.handle_press(undo, None),
+            ChoicePress::Changed
+        ));
+        assert_eq!(None, choice.chosen);
+    }
+
+    #[test]
+    fn double_tap() {
+        let window = Some(Duration::from_millis(500));
+        let point = Point2 { x: 3, y: 4 };
+        let start = Instant::now();
+        let mut tap = DoubleTap::default();
+        assert!(!tap.confirm(point, start, window));
+        assert!(tap.confirm(point, start + Duration::from_millis(300), window));
+        // Played, so it's back to needing two
+        assert!(!tap.confirm(point, start + Duration::from_millis(400), window));
+    }
+
+    #[test]
+    fn double_tap_too_slow() {
+        let window = Some(Duration::from_millis(500));
+        let point = Point2 { x: 3, y: 4 };
+        let start = Instant::now();
+        let mut tap = DoubleTap::default();
+        assert!(!tap.confirm(point, start, window));
+        let late = start + Duration::from_millis(600);
+        assert!(!tap.confirm(point, late, window));
+        // The late one counts as a first tap
+        assert!(tap.confirm(point, late + Duration::from_millis(100), window));
+    }
+
+    #[test]
+    fn double_tap_elsewhere_resets() {
+        let window = Some(Duration::from_millis(500));
+        let start = Instant::now();
+        let mut tap = DoubleTap::default();
+        assert!(!tap.confirm(Point2 { x: 3, y: 4 }, start, window));
+        assert!(!tap.confirm(Point2 { x: 4, y: 4 }, start, window));
+        assert!(!tap.confirm(Point2 { x: 3, y: 4 }, start, window));
+        tap.clear();
+        assert!(!tap.confirm(Point2 { x: 3, y: 4 }, start, window));
+    }
+
+    #[test]
+    fn no_double_tap() {
+        let mut tap = DoubleTap::default();
+        assert!(tap.confirm(Point2 { x: 0, y: 0 }, Instant::now(), None));
+    }
+}
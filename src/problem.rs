@@ -0,0 +1,497 @@
+use crate::{
+    board::{Board, Stone},
+    drawing::{draw_multiline_text, pressed, refresh, scaled, scaled_size, Button, SCREEN_HEIGHT},
+    engine::Engine,
+    game_parse::{get_game_data_up_to, GameData},
+    gtp::{do_human_move, replay_position, GtpError, StoneCache},
+    reset::{draw_back, handle_back},
+    review::{file_name, read_sgf, FileList, FilePress},
+    routine::Routine,
+    sgf::{problem_files, PROBLEM_DIR},
+};
+use libremarkable::{
+    appctx,
+    cgmath::{Point2, Vector2},
+    framebuffer::core::Framebuffer,
+    input::MultitouchEvent,
+};
+use log::{info, warn};
+use sgf_parse::{
+    go::{parse, Move, Prop},
+    SgfNode,
+};
+use std::path::Path;
+
+const BUTTON_SIZE: Vector2<u32> = Vector2 { x: 350, y: 95 };
+
+/// A move in the solution tree, and the moves that can follow it
+#[derive(Debug, PartialEq)]
+struct SolutionMove {
+    stone: Stone,
+    /// 0-based, with `None` for a pass
+    point: Option<Point2<u8>>,
+    comment: Option<String>,
+    replies: Vec<SolutionMove>,
+}
+
+/// Moves in `node`, or in its children if it hasn't got one (e.g. a setup node)
+fn solution_moves(node: &SgfNode<Prop>) -> Vec<SolutionMove> {
+    let mut stone_move = None;
+    let mut comment = None;
+    for prop in node.properties() {
+        match prop {
+            Prop::B(played) => stone_move = Some((Stone::Black, *played)),
+            Prop::W(played) => stone_move = Some((Stone::White, *played)),
+            Prop::C(text) => comment = Some(text.text.clone()),
+            _ => {}
+        }
+    }
+    let replies = node.children().flat_map(solution_moves).collect();
+    match stone_move {
+        Some((stone, played)) => vec![SolutionMove {
+            stone,
+            point: match played {
+                Move::Move(point) => Some(Point2 {
+                    x: point.x,
+                    y: point.y,
+                }),
+                Move::Pass => None,
+            },
+            comment,
+            replies,
+        }],
+        None => replies,
+    }
+}
+
+/// What a comment says about its line: the usual "RIGHT" or "Correct" for a solution, or
+/// "WRONG"/"Incorrect" for a failure
+fn marked_right(comment: &str) -> Option<bool> {
+    let comment = comment.to_uppercase();
+    if comment.contains("WRONG") || comment.contains("INCORRECT") {
+        Some(false)
+    } else if comment.contains("RIGHT") || comment.contains("CORRECT") {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+fn has_marks(moves: &[SolutionMove]) -> bool {
+    moves
+        .iter()
+        .any(|m| m.comment.as_deref().and_then(marked_right).is_some() || has_marks(&m.replies))
+}
+
+#[derive(Debug, PartialEq)]
+struct Problem {
+    setup: GameData,
+    /// Whoever plays the first move of the solution
+    player: Stone,
+    moves: Vec<SolutionMove>,
+    /// Comments say which lines are right. Without them, only the first variation is
+    marked: bool,
+}
+
+impl Problem {
+    fn parse(raw_sgf: &str) -> Result<Problem, String> {
+        let setup = get_game_data_up_to(raw_sgf, Some(0)).map_err(|err| err.to_string())?;
+        let nodes = parse(raw_sgf).map_err(|err| err.to_string())?;
+        let moves: Vec<SolutionMove> = nodes.iter().flat_map(solution_moves).collect();
+        let Some(first) = moves.first() else {
+            return Err("No solution in it".to_string());
+        };
+        // Only PL in the setup counts, and setup already has whatever it said
+        let says_to_play = nodes.iter().any(|node| node.get_property("PL").is_some());
+        if says_to_play && setup.to_play != first.stone {
+            return Err(format!(
+                "It says {:?} to play, but the solution starts with {:?}",
+                setup.to_play, first.stone
+            ));
+        }
+        Ok(Problem {
+            player: first.stone,
+            marked: has_marks(&moves),
+            setup,
+            moves,
+        })
+    }
+
+    /// The moves that can follow the ones picked by `path`
+    fn options(&self, path: &[usize]) -> &[SolutionMove] {
+        let mut options = &self.moves[..];
+        for index in path {
+            options = &options[*index].replies;
+        }
+        options
+    }
+
+    /// Whether the line in `path` is a solution, once there's nothing left to play
+    fn is_right(&self, path: &[usize]) -> bool {
+        let Some((last, before)) = path.split_last() else {
+            return false;
+        };
+        let end = &self.options(before)[*last];
+        if self.marked {
+            end.comment.as_deref().and_then(marked_right) == Some(true)
+        } else {
+            path.iter().all(|index| *index == 0)
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Status {
+    Playing,
+    Right,
+    Wrong,
+}
+
+#[derive(Clone, Copy)]
+enum Action {
+    Retry,
+    Files,
+}
+
+struct ProblemGame {
+    name: String,
+    problem: Problem,
+    board: Board,
+    /// Indexes into the solution tree of the moves so far, both sides
+    path: Vec<usize>,
+    status: Status,
+    message: Option<String>,
+    last_move: Option<(Point2<u8>, Stone)>,
+    stone_cache: StoneCache,
+}
+
+impl ProblemGame {
+    /// Errors are ready to show on screen
+    fn load(path: &Path) -> Result<ProblemGame, String> {
+        let name = file_name(path);
+        let raw_sgf = read_sgf(path)?;
+        let problem = Problem::parse(&raw_sgf).map_err(|err| {
+            warn!("Bad problem in {name}: {err}");
+            format!("{name} isn't a problem SGF file")
+        })?;
+        Ok(ProblemGame {
+            board: Board::new(problem.setup.size),
+            name,
+            problem,
+            path: vec![],
+            status: Status::Playing,
+            message: None,
+            last_move: None,
+            stone_cache: StoneCache::default(),
+        })
+    }
+
+    /// Puts GnuGo back to the starting position
+    fn reset(&mut self, ctrl: &mut Engine) -> Result<(), GtpError> {
+        self.path.clear();
+        self.status = Status::Playing;
+        self.message = None;
+        self.last_move = None;
+        let setup = &self.problem.setup;
+        replay_position(
+            ctrl,
+            setup.size,
+            &setup.black_stones,
+            &setup.white_stones,
+            |_, _| {},
+        )?;
+        self.stone_cache.board_changed();
+        Ok(())
+    }
+
+    /// Plays `point` for the player, and the problem's answer if there is one
+    fn play(&mut self, ctrl: &mut Engine, point: Point2<u8>) -> Result<(), GtpError> {
+        let player = self.problem.player;
+        if !do_human_move(ctrl, point, player.gtp_name())?.played() {
+            self.message = Some("Can't play there".to_string());
+            return Ok(());
+        }
+        self.stone_cache.board_changed();
+        self.last_move = Some((point, player));
+        self.message = None;
+        let options = self.problem.options(&self.path);
+        let Some(index) = options.iter().position(|m| m.point == Some(point)) else {
+            info!("{point:?} isn't in the solution");
+            self.status = Status::Wrong;
+            return Ok(());
+        };
+        self.path.push(index);
+        let Some(reply) = self.problem.options(&self.path).first() else {
+            self.finish();
+            return Ok(());
+        };
+        if let Some(reply_point) = reply.point {
+            // GnuGo checks the problem's answer is a legal move too
+            if !do_human_move(ctrl, reply_point, reply.stone.gtp_name())?.played() {
+                warn!("Illegal reply {reply_point:?} in {}", self.name);
+                self.message = Some("The problem's answer is an illegal move".to_string());
+                self.status = Status::Wrong;
+                return Ok(());
+            }
+            self.stone_cache.board_changed();
+            self.last_move = Some((reply_point, reply.stone));
+        }
+        self.message = reply.comment.clone();
+        self.path.push(0);
+        if self.problem.options(&self.path).is_empty() {
+            self.finish();
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) {
+        self.status = if self.problem.is_right(&self.path) {
+            Status::Right
+        } else {
+            Status::Wrong
+        };
+    }
+
+    fn buttons(&self) -> Vec<Button<Action>> {
+        let left = self.board.spare_width as i32;
+        let size = scaled_size(BUTTON_SIZE);
+        vec![
+            Button::new("Retry", Point2 { x: left, y: 20 }, size, Action::Retry),
+            Button::new(
+                "Files",
+                Point2 {
+                    x: left,
+                    y: 20 + scaled(120) as i32,
+                },
+                size,
+                Action::Files,
+            ),
+        ]
+    }
+
+    fn status_text(&self) -> String {
+        let mut text = format!(
+            "{}: {}",
+            self.name,
+            match self.status {
+                Status::Playing => format!("{:?} to play", self.problem.player),
+                Status::Right => "Correct!".to_string(),
+                Status::Wrong => "Wrong, try again".to_string(),
+            }
+        );
+        if let Some(ref message) = self.message {
+            text += &format!("\n{message}");
+        }
+        text
+    }
+
+    fn draw(&mut self, fb: &mut Framebuffer, ctrl: &mut Engine) -> Result<(), GtpError> {
+        let (white_stones, black_stones) = self.stone_cache.stones(ctrl)?;
+        self.board.draw_board(fb, &white_stones, &black_stones);
+        if let Some((point, stone)) = self.last_move {
+            self.board
+                .draw_last_move_marker(fb, point.x, point.y, stone == Stone::White);
+        }
+        for button in self.buttons() {
+            button.draw(fb);
+        }
+        draw_back(fb);
+        draw_multiline_text(
+            fb,
+            Point2 {
+                x: self.board.spare_width as f32,
+                y: (SCREEN_HEIGHT - 152) as f32,
+            },
+            &self.status_text(),
+            40.0,
+            50,
+        );
+        refresh(fb);
+        Ok(())
+    }
+}
+
+/// Tsumego: plays through the solutions in problem SGF files
+pub struct Problems {
+    files: FileList,
+    game: Option<ProblemGame>,
+    message: Option<String>,
+}
+
+impl Problems {
+    pub fn new() -> Problems {
+        Problems {
+            files: FileList::load(problem_files),
+            game: None,
+            message: None,
+        }
+    }
+
+    fn redraw(&mut self, fb: &mut Framebuffer, ctrl: &mut Engine) {
+        let Some(ref mut game) = self.game else {
+            self.files.draw(
+                fb,
+                "Problems",
+                &format!("No problems yet. Put SGF files in {PROBLEM_DIR}"),
+                self.message.as_deref(),
+            );
+            return;
+        };
+        if let Err(err) = game.draw(fb, ctrl) {
+            self.show_engine_error(fb, ctrl, err);
+        }
+    }
+
+    fn show_engine_error(&mut self, fb: &mut Framebuffer, ctrl: &mut Engine, err: GtpError) {
+        warn!("Problem engine error: {err}");
+        self.game = None;
+        self.message = Some(err.status_text().to_string());
+        self.redraw(fb, ctrl);
+    }
+
+    fn handle_files_press(
+        &mut self,
+        ctrl: &mut Engine,
+        pos: Point2<u16>,
+    ) -> Result<bool, GtpError> {
+        let path = match self.files.handle_press(pos) {
+            FilePress::Nothing => return Ok(false),
+            FilePress::Changed => return Ok(true),
+            FilePress::Chosen(path) => path,
+        };
+        info!("Problem {}", path.display());
+        match ProblemGame::load(&path) {
+            Ok(mut game) => {
+                game.reset(ctrl)?;
+                self.game = Some(game);
+                self.message = None;
+            }
+            Err(message) => self.message = Some(message),
+        }
+        Ok(true)
+    }
+
+    fn handle_game_press(&mut self, ctrl: &mut Engine, pos: Point2<u16>) -> Result<bool, GtpError> {
+        let Some(ref mut game) = self.game else {
+            return Ok(false);
+        };
+        if let Some(button) = pressed(&game.buttons(), pos) {
+            match button.action {
+                Action::Retry => game.reset(ctrl)?,
+                Action::Files => {
+                    self.game = None;
+                    self.files = FileList::load(problem_files);
+                }
+            }
+            return Ok(true);
+        }
+        if game.status != Status::Playing {
+            return Ok(false);
+        }
+        let Some(point) = game.board.pixel_to_point(pos) else {
+            return Ok(false);
+        };
+        game.play(ctrl, point)?;
+        Ok(true)
+    }
+}
+
+impl Routine for Problems {
+    fn init(&mut self, fb: &mut Framebuffer, ctrl: &mut Engine) {
+        self.redraw(fb, ctrl);
+    }
+
+    fn on_multitouch_event(
+        &mut self,
+        ctx: &mut appctx::ApplicationContext<'_>,
+        event: MultitouchEvent,
+        ctrl: &mut Engine,
+    ) {
+        if let MultitouchEvent::Press { finger } = event {
+            if handle_back(ctx, finger.pos) {
+                return;
+            }
+            let fb = ctx.get_framebuffer_ref();
+            let changed = if self.game.is_some() {
+                self.handle_game_press(ctrl, finger.pos)
+            } else {
+                self.handle_files_press(ctrl, finger.pos)
+            };
+            match changed {
+                Ok(true) => self.redraw(fb, ctrl),
+                Ok(false) => {}
+                Err(err) => self.show_engine_error(fb, ctrl, err),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use libremarkable::cgmath::Point2;
+    use pretty_assertions::assert_eq;
+
+    use crate::{
+        board::Stone,
+        problem::{marked_right, Problem},
+    };
+
+    #[test]
+    fn marks() {
+        assert_eq!(Some(true), marked_right("RIGHT"));
+        assert_eq!(Some(true), marked_right("Correct, black lives"));
+        assert_eq!(Some(false), marked_right("Incorrect"));
+        assert_eq!(Some(false), marked_right("wrong: white gets a ko"));
+        assert_eq!(None, marked_right("Black to live"));
+    }
+
+    #[test]
+    fn unmarked_problem() {
+        // Main line ba, ab; a variation playing ab first
+        let problem =
+            Problem::parse("(;SZ[5]AW[ca][bb]AB[cb][db](;B[ba](;W[ab])(;W[aa]))(;B[ab];W[ba]))")
+                .unwrap_or_else(|err| panic!("{err}"));
+        assert_eq!(Stone::Black, problem.player);
+        assert!(!problem.marked);
+        assert_eq!(2, problem.setup.white_stones.len());
+        assert_eq!(
+            vec![Some(Point2 { x: 1, y: 0 }), Some(Point2 { x: 0, y: 1 })],
+            problem
+                .options(&[])
+                .iter()
+                .map(|m| m.point)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(2, problem.options(&[0]).len());
+        assert!(problem.is_right(&[0, 0]));
+        assert!(!problem.is_right(&[0, 1]));
+        assert!(!problem.is_right(&[1, 0]));
+    }
+
+    #[test]
+    fn marked_problem() {
+        let problem = Problem::parse(
+            "(;SZ[9]AB[cc](;W[dd];B[de];W[ed]C[Wrong])(;W[ee];B[dd];W[dc]C[RIGHT]))",
+        )
+        .unwrap_or_else(|err| panic!("{err}"));
+        assert_eq!(Stone::White, problem.player);
+        assert!(problem.marked);
+        assert!(!problem.is_right(&[0, 0, 0]));
+        assert!(problem.is_right(&[1, 0, 0]));
+    }
+
+    #[test]
+    fn not_a_problem() {
+        assert!(Problem::parse("(;SZ[9]AB[cc])").is_err());
+        assert!(Problem::parse("not an sgf").is_err());
+    }
+
+    #[test]
+    fn player_from_pl() {
+        let problem = Problem::parse("(;SZ[9]AB[cc]PL[W];W[dd]C[RIGHT])")
+            .unwrap_or_else(|err| panic!("{err}"));
+        assert_eq!(Stone::White, problem.player);
+        assert_eq!(Stone::White, problem.setup.to_play);
+        assert!(Problem::parse("(;SZ[9]AB[cc]PL[B];W[dd]C[RIGHT])").is_err());
+    }
+}
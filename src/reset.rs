@@ -1,27 +1,184 @@
 use libremarkable::{
+    appctx,
     cgmath::{Point2, Vector2},
     framebuffer::core::Framebuffer,
+    input::MultitouchEvent,
+};
+use log::info;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
 };
 
 use crate::{
-    board::{Board, AVAILABLE_WIDTH},
-    drawing::draw_button,
+    chooser::{Mode, CURRENT_MODE},
+    drawing::{scaled_size, Button, SCREEN_WIDTH},
+    lock,
 };
 
-pub const RESET_BUTTON_SIZE: Vector2<u32> = Vector2 { x: 400, y: 95 };
+pub const BACK_BUTTON_SIZE: Vector2<u32> = Vector2 { x: 350, y: 95 };
+const BACK_BUTTON_MARGIN: i32 = 20;
+// Gap to leave between the back button and anything next to it
+const BACK_BUTTON_GAP: i32 = 40;
+// Long enough that a double tap on "Back" doesn't also press whatever's under it on the chooser
+const BACK_DEBOUNCE: Duration = Duration::from_millis(500);
+
+// Fingers down at once that always mean "back to the chooser", whatever the mode draws
+const ESCAPE_FINGERS: usize = 3;
+// Nobody holds a finger down this long, so it's a release we never saw
+const STUCK_FINGER: Duration = Duration::from_secs(10);
+
+static LAST_BACK: Mutex<Option<Instant>> = Mutex::new(None);
 
-pub fn reset_button_top_left(board: &Board) -> Point2<i32> {
+/// Always the top-right corner of the screen, so it's in the same place in every mode
+pub fn back_button_top_left() -> Point2<i32> {
     Point2 {
-        x: (board.spare_width + AVAILABLE_WIDTH / 2 + 200) as i32,
-        y: 20,
+        x: SCREEN_WIDTH as i32 - BACK_BUTTON_MARGIN - scaled_size(BACK_BUTTON_SIZE).x as i32,
+        y: BACK_BUTTON_MARGIN,
+    }
+}
+
+/// Width from `left` that's clear of the back button, for things on the same row
+pub fn space_before_back(left: i32) -> u32 {
+    (back_button_top_left().x - BACK_BUTTON_GAP - left).max(0) as u32
+}
+
+pub fn back_button() -> Button<()> {
+    Button::new(
+        "< Back",
+        back_button_top_left(),
+        scaled_size(BACK_BUTTON_SIZE),
+        (),
+    )
+}
+
+pub fn draw_back(fb: &mut Framebuffer) {
+    back_button().draw(fb);
+}
+
+/// If `pos` is on the back button, goes back to the chooser and returns true
+pub fn handle_back(ctx: &mut appctx::ApplicationContext<'_>, pos: Point2<u16>) -> bool {
+    if !back_button().contains(pos) {
+        return false;
+    }
+    go_back(ctx);
+    true
+}
+
+pub fn go_back(ctx: &mut appctx::ApplicationContext<'_>) {
+    info!("Back to chooser");
+    *lock(&LAST_BACK) = Some(Instant::now());
+    *lock(&CURRENT_MODE) = Mode::Chooser;
+    ctx.stop();
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Touch {
+    /// For the current mode to deal with
+    Pass,
+    /// Another finger while one's already down, which shouldn't place a stone or press a button
+    Ignore,
+    /// The escape gesture
+    Escape,
+}
+
+/// Fingers on the screen, by tracking id, for spotting the escape gesture
+#[derive(Default)]
+pub struct FingerTracker {
+    down: HashMap<i32, Instant>,
+}
+
+impl FingerTracker {
+    pub fn track(&mut self, event: &MultitouchEvent, now: Instant) -> Touch {
+        match event {
+            MultitouchEvent::Press { finger } => {
+                self.down
+                    .retain(|_, pressed| now.saturating_duration_since(*pressed) < STUCK_FINGER);
+                self.down.insert(finger.tracking_id, now);
+                match self.down.len() {
+                    1 => Touch::Pass,
+                    count if count >= ESCAPE_FINGERS => {
+                        self.down.clear();
+                        Touch::Escape
+                    }
+                    _ => Touch::Ignore,
+                }
+            }
+            MultitouchEvent::Release { finger } => {
+                self.down.remove(&finger.tracking_id);
+                Touch::Pass
+            }
+            _ => Touch::Pass,
+        }
     }
 }
 
-pub fn draw_reset(board: &Board, fb: &mut Framebuffer) {
-    draw_button(
-        fb,
-        "Exit game",
-        reset_button_top_left(board),
-        RESET_BUTTON_SIZE,
-    );
+/// Whether we've only just gone back, so touches should be ignored in case they were meant for
+/// the back button
+pub fn in_back_debounce() -> bool {
+    lock(&LAST_BACK).is_some_and(|t| t.elapsed() < BACK_DEBOUNCE)
+}
+
+#[cfg(test)]
+mod test {
+    use libremarkable::input::{Finger, MultitouchEvent};
+    use pretty_assertions::assert_eq;
+    use std::time::{Duration, Instant};
+
+    use crate::reset::{FingerTracker, Touch};
+
+    fn finger(tracking_id: i32) -> Finger {
+        let mut finger = Finger::default();
+        finger.tracking_id = tracking_id;
+        finger
+    }
+
+    fn press(tracking_id: i32) -> MultitouchEvent {
+        MultitouchEvent::Press {
+            finger: finger(tracking_id),
+        }
+    }
+
+    fn release(tracking_id: i32) -> MultitouchEvent {
+        MultitouchEvent::Release {
+            finger: finger(tracking_id),
+        }
+    }
+
+    #[test]
+    fn single_taps_pass() {
+        let mut tracker = FingerTracker::default();
+        let now = Instant::now();
+        for id in 1..5 {
+            assert_eq!(Touch::Pass, tracker.track(&press(id), now));
+            assert_eq!(Touch::Pass, tracker.track(&release(id), now));
+        }
+    }
+
+    #[test]
+    fn three_fingers_escape() {
+        let mut tracker = FingerTracker::default();
+        let now = Instant::now();
+        assert_eq!(Touch::Pass, tracker.track(&press(1), now));
+        assert_eq!(Touch::Ignore, tracker.track(&press(2), now));
+        assert_eq!(Touch::Escape, tracker.track(&press(3), now));
+        // Lifting them all afterwards is back to normal
+        for id in 1..4 {
+            tracker.track(&release(id), now);
+        }
+        assert_eq!(Touch::Pass, tracker.track(&press(4), now));
+    }
+
+    #[test]
+    fn missed_release() {
+        let mut tracker = FingerTracker::default();
+        let now = Instant::now();
+        tracker.track(&press(1), now);
+        tracker.track(&press(2), now);
+        assert_eq!(
+            Touch::Pass,
+            tracker.track(&press(3), now + Duration::from_secs(60))
+        );
+    }
 }
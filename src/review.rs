@@ -0,0 +1,553 @@
+use crate::{
+    board::{Board, Stone},
+    chooser::{Mode, CURRENT_MODE},
+    drawing::{
+        draw_multiline_text, hit, pressed, refresh, scaled, scaled_size, text_size, Button,
+        SCREEN_HEIGHT, SCREEN_WIDTH,
+    },
+    engine::Engine,
+    game_parse::{get_game_data, get_game_data_up_to, get_moves, GameData, PlayedMove},
+    lock,
+    reset::{draw_back, handle_back, space_before_back},
+    routine::Routine,
+    sgf::saved_games,
+};
+use libremarkable::{
+    appctx,
+    cgmath::{Point2, Vector2},
+    framebuffer::{common::color, core::Framebuffer, FramebufferDraw},
+    input::MultitouchEvent,
+};
+use log::{info, warn};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+const BUTTON_SIZE: Vector2<u32> = Vector2 { x: 350, y: 95 };
+const LEFT: i32 = 100;
+const FIRST_ROW_Y: i32 = 250;
+const ROW_HEIGHT: i32 = 110;
+const ROW_TEXT_SIZE: f32 = 50.0;
+const FILES_PER_PAGE: usize = 12;
+// Far bigger than any real game, but stops something odd in the directory tying the app up
+const MAX_SGF_BYTES: u64 = 1024 * 1024;
+
+#[derive(Clone, Copy)]
+enum Action {
+    Previous,
+    Next,
+    Files,
+    /// Back to the mode the game was just played in, for another
+    NewGame(Mode),
+}
+
+/// A game that's just ended in a local mode, with the mode it was played in
+struct JustPlayed {
+    raw_sgf: String,
+    from: Mode,
+}
+
+/// For `Review` to open as soon as it starts, rather than the file list
+static JUST_PLAYED: Mutex<Option<JustPlayed>> = Mutex::new(None);
+
+/// Switches to reviewing `raw_sgf`, a game that's just ended in the `from` mode, with a "New
+/// game" button that goes back there
+pub fn review_just_played(ctx: &mut appctx::ApplicationContext<'_>, raw_sgf: String, from: Mode) {
+    info!("Reviewing the game just played in {from:?}");
+    *lock(&JUST_PLAYED) = Some(JustPlayed { raw_sgf, from });
+    *lock(&CURRENT_MODE) = Mode::Review;
+    ctx.stop();
+}
+
+/// Which move put each stone on the board there, counting from 1. A point that's been played
+/// more than once (after a capture) gets the latest, and setup stones don't get one
+fn move_numbers(moves: &[PlayedMove], position: &GameData) -> Vec<(Point2<u8>, usize, Stone)> {
+    let mut latest = HashMap::new();
+    for (index, (stone, point)) in moves.iter().enumerate() {
+        if let Some(point) = point {
+            latest.insert(*point, (index + 1, *stone));
+        }
+    }
+    let mut numbers: Vec<_> = latest
+        .into_iter()
+        .filter(|(point, (_, stone))| match stone {
+            Stone::Black => position.black_stones.contains(point),
+            Stone::White => position.white_stones.contains(point),
+        })
+        .map(|(point, (number, stone))| (point, number, stone))
+        .collect();
+    numbers.sort_by_key(|(_, number, _)| *number);
+    numbers
+}
+
+pub fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Reads an SGF file, with errors ready to show on screen
+pub fn read_sgf(path: &Path) -> Result<String, String> {
+    let name = file_name(path);
+    let size = fs::metadata(path)
+        .map_err(|err| format!("Can't read {name}: {err}"))?
+        .len();
+    if size > MAX_SGF_BYTES {
+        warn!("{name} is {size} bytes");
+        return Err(format!("{name} is too big to show"));
+    }
+    fs::read_to_string(path).map_err(|err| format!("Can't read {name}: {err}"))
+}
+
+fn more_button() -> Button<()> {
+    Button::new(
+        "More",
+        Point2 {
+            x: LEFT,
+            y: FIRST_ROW_Y + ROW_HEIGHT * FILES_PER_PAGE as i32 + 40,
+        },
+        scaled_size(BUTTON_SIZE),
+        (),
+    )
+}
+
+/// Tappable area for the `index`th file on the page
+fn row_top_left(index: usize) -> Point2<i32> {
+    Point2 {
+        x: LEFT,
+        y: FIRST_ROW_Y + ROW_HEIGHT * index as i32,
+    }
+}
+
+fn row_size() -> Vector2<u32> {
+    Vector2 {
+        x: SCREEN_WIDTH as u32 - 2 * LEFT as u32,
+        y: ROW_HEIGHT as u32,
+    }
+}
+
+pub enum FilePress {
+    Nothing,
+    /// Onto another page
+    Changed,
+    Chosen(PathBuf),
+}
+
+/// Pages of SGF files to pick from
+pub struct FileList {
+    files: Vec<PathBuf>,
+    page: usize,
+}
+
+impl FileList {
+    /// Lists what `find` gives, or nothing if that fails (e.g. there's no directory yet)
+    pub fn load(find: impl FnOnce() -> io::Result<Vec<PathBuf>>) -> FileList {
+        let files = match find() {
+            Ok(files) => files,
+            Err(err) => {
+                info!("No SGF files: {err}");
+                vec![]
+            }
+        };
+        FileList { files, page: 0 }
+    }
+
+    fn page_count(&self) -> usize {
+        self.files.len().div_ceil(FILES_PER_PAGE)
+    }
+
+    fn page_files(&self) -> impl Iterator<Item = &PathBuf> {
+        self.files
+            .iter()
+            .skip(self.page * FILES_PER_PAGE)
+            .take(FILES_PER_PAGE)
+    }
+
+    /// Fills the screen with the list, and `message` (e.g. why a file didn't load) at the bottom
+    pub fn draw(&self, fb: &mut Framebuffer, title: &str, empty: &str, message: Option<&str>) {
+        fb.clear();
+        draw_back(fb);
+        fb.draw_text(
+            Point2 {
+                x: LEFT as f32,
+                y: 150.0,
+            },
+            title,
+            text_size(),
+            color::BLACK,
+            false,
+        );
+        if self.files.is_empty() {
+            draw_multiline_text(
+                fb,
+                Point2 {
+                    x: LEFT as f32,
+                    y: (FIRST_ROW_Y + ROW_HEIGHT / 2) as f32,
+                },
+                empty,
+                ROW_TEXT_SIZE,
+                40,
+            );
+        }
+        for (index, path) in self.page_files().enumerate() {
+            let top_left = row_top_left(index);
+            fb.draw_text(
+                Point2 {
+                    x: top_left.x as f32,
+                    y: (top_left.y + ROW_HEIGHT / 2) as f32 + ROW_TEXT_SIZE / 2.0,
+                },
+                &file_name(path),
+                ROW_TEXT_SIZE,
+                color::BLACK,
+                false,
+            );
+        }
+        if self.page_count() > 1 {
+            more_button().draw(fb);
+        }
+        if let Some(message) = message {
+            draw_multiline_text(
+                fb,
+                Point2 {
+                    x: LEFT as f32,
+                    y: (SCREEN_HEIGHT - 152) as f32,
+                },
+                message,
+                40.0,
+                50,
+            );
+        }
+        refresh(fb);
+    }
+
+    pub fn handle_press(&mut self, pos: Point2<u16>) -> FilePress {
+        if self.page_count() > 1 && more_button().contains(pos) {
+            self.page = (self.page + 1) % self.page_count();
+            return FilePress::Changed;
+        }
+        self.page_files()
+            .enumerate()
+            .find(|(index, _)| hit(pos, row_top_left(*index), row_size()))
+            .map_or(FilePress::Nothing, |(_, path)| {
+                FilePress::Chosen(path.clone())
+            })
+    }
+}
+
+/// A saved game, stepped through a move at a time
+struct ReviewGame {
+    name: String,
+    raw_sgf: String,
+    board: Board,
+    total_moves: usize,
+    shown_moves: usize,
+    position: GameData,
+    /// Move numbers on the stones, which gets cluttered so is toggled by tapping the board
+    numbers: bool,
+    /// Where "New game" goes, for a game that's just been played rather than one from a file
+    new_game: Option<Mode>,
+}
+
+impl ReviewGame {
+    /// Errors are ready to show on screen
+    fn load(path: &Path) -> Result<ReviewGame, String> {
+        let name = file_name(path);
+        let raw_sgf = read_sgf(path)?;
+        ReviewGame::from_sgf(name, raw_sgf, None)
+    }
+
+    fn from_sgf(
+        name: String,
+        raw_sgf: String,
+        new_game: Option<Mode>,
+    ) -> Result<ReviewGame, String> {
+        let position = get_game_data(&raw_sgf).map_err(|err| {
+            warn!("Bad SGF in {name}: {err}");
+            format!("{name} isn't a valid SGF file")
+        })?;
+        let mut game = ReviewGame {
+            name,
+            raw_sgf,
+            board: Board::new(position.size),
+            total_moves: position.move_count,
+            shown_moves: position.move_count,
+            position,
+            numbers: false,
+            new_game,
+        };
+        game.show(0);
+        Ok(game)
+    }
+
+    /// Moves to the position after `moves`, within the game
+    fn show(&mut self, moves: usize) {
+        let moves = moves.min(self.total_moves);
+        // Already parsed it all once, so this shouldn't fail
+        if let Ok(position) = get_game_data_up_to(&self.raw_sgf, Some(moves)) {
+            self.shown_moves = moves;
+            self.position = position;
+        }
+    }
+
+    fn buttons(&self) -> Vec<Button<Action>> {
+        let left = self.board.spare_width as i32;
+        let size = scaled_size(BUTTON_SIZE);
+        let second_row = 20 + scaled(120) as i32;
+        let next_x = left + scaled(400) as i32;
+        // Drop down a row if big buttons would run into the back button
+        let next_y = if size.x > space_before_back(next_x) {
+            second_row
+        } else {
+            20
+        };
+        vec![
+            Button::new("< Prev", Point2 { x: left, y: 20 }, size, Action::Previous),
+            Button::new(
+                "Next >",
+                Point2 {
+                    x: next_x,
+                    y: next_y,
+                },
+                size,
+                Action::Next,
+            ),
+            match self.new_game {
+                Some(mode) => Button::new(
+                    "New game",
+                    Point2 {
+                        x: left,
+                        y: second_row,
+                    },
+                    size,
+                    Action::NewGame(mode),
+                ),
+                None => Button::new(
+                    "Files",
+                    Point2 {
+                        x: left,
+                        y: second_row,
+                    },
+                    size,
+                    Action::Files,
+                ),
+            },
+        ]
+    }
+
+    fn draw(&self, fb: &mut Framebuffer) {
+        let position = &self.position;
+        self.board
+            .draw_board(fb, &position.white_stones, &position.black_stones);
+        if self.numbers {
+            // Already parsed it all once, so this shouldn't fail
+            let moves = get_moves(&self.raw_sgf, Some(self.shown_moves)).unwrap_or_default();
+            for (point, number, stone) in move_numbers(&moves, position) {
+                self.board.draw_move_number(
+                    fb,
+                    point.x - 1,
+                    point.y - 1,
+                    number,
+                    stone == Stone::White,
+                );
+            }
+        } else if self.shown_moves > 0 {
+            let mover = position.to_play.other();
+            let last_move = match mover {
+                Stone::Black => position.last_black_move,
+                Stone::White => position.last_white_move,
+            };
+            if let Some(last_move) = last_move {
+                self.board.draw_last_move_marker(
+                    fb,
+                    last_move.x - 1,
+                    last_move.y - 1,
+                    mover == Stone::White,
+                );
+            }
+        }
+        for button in self.buttons() {
+            button.draw(fb);
+        }
+        draw_back(fb);
+        let mut status = format!(
+            "{}: move {} of {}",
+            self.name, self.shown_moves, self.total_moves
+        );
+        if let (true, Some(result)) = (self.shown_moves == self.total_moves, &position.result) {
+            status += &format!(", {result}");
+        }
+        // Setup stones don't say whose turn it is, so PL (or black) decides who starts
+        if self.shown_moves == 0 && self.total_moves > 0 {
+            status += &format!(", {:?} to play", position.to_play);
+        }
+        draw_multiline_text(
+            fb,
+            Point2 {
+                x: self.board.spare_width as f32,
+                y: (SCREEN_HEIGHT - 152) as f32,
+            },
+            &status,
+            40.0,
+            50,
+        );
+        refresh(fb);
+    }
+}
+
+/// Read-only viewer for games saved into the SGF directory
+pub struct Review {
+    files: FileList,
+    game: Option<ReviewGame>,
+    message: Option<String>,
+}
+
+impl Review {
+    pub fn new() -> Review {
+        let game = lock(&JUST_PLAYED).take().and_then(|played| {
+            ReviewGame::from_sgf("Last game".to_string(), played.raw_sgf, Some(played.from))
+                .map_err(|err| warn!("Can't review the game just played: {err}"))
+                .ok()
+        });
+        Review {
+            files: FileList::load(saved_games),
+            game,
+            message: None,
+        }
+    }
+
+    fn redraw(&self, fb: &mut Framebuffer) {
+        match self.game {
+            Some(ref game) => game.draw(fb),
+            None => self.files.draw(
+                fb,
+                "Saved games",
+                "Nothing saved yet. Use \"Save\" in a machine game or when setting up a board",
+                self.message.as_deref(),
+            ),
+        }
+    }
+
+    /// Returns whether anything changed
+    fn handle_files_press(&mut self, pos: Point2<u16>) -> bool {
+        let path = match self.files.handle_press(pos) {
+            FilePress::Nothing => return false,
+            FilePress::Changed => return true,
+            FilePress::Chosen(path) => path,
+        };
+        info!("Reviewing {}", path.display());
+        match ReviewGame::load(&path) {
+            Ok(game) => {
+                self.game = Some(game);
+                self.message = None;
+            }
+            Err(message) => self.message = Some(message),
+        }
+        true
+    }
+
+    /// Returns whether anything changed
+    fn handle_game_press(
+        &mut self,
+        ctx: &mut appctx::ApplicationContext<'_>,
+        pos: Point2<u16>,
+    ) -> bool {
+        let Some(ref mut game) = self.game else {
+            return false;
+        };
+        let buttons = game.buttons();
+        let Some(button) = pressed(&buttons, pos) else {
+            if game.board.pixel_to_point(pos).is_some() {
+                game.numbers = !game.numbers;
+                info!("Move numbers {}", if game.numbers { "on" } else { "off" });
+                return true;
+            }
+            return false;
+        };
+        match button.action {
+            Action::Previous => game.show(game.shown_moves.saturating_sub(1)),
+            Action::Next => game.show(game.shown_moves + 1),
+            Action::Files => {
+                self.game = None;
+                self.files = FileList::load(saved_games);
+            }
+            Action::NewGame(mode) => {
+                *lock(&CURRENT_MODE) = mode;
+                ctx.stop();
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl Routine for Review {
+    fn init(&mut self, fb: &mut Framebuffer, _ctrl: &mut Engine) {
+        self.redraw(fb);
+    }
+
+    fn on_multitouch_event(
+        &mut self,
+        ctx: &mut appctx::ApplicationContext<'_>,
+        event: MultitouchEvent,
+        _ctrl: &mut Engine,
+    ) {
+        if let MultitouchEvent::Press { finger } = event {
+            if handle_back(ctx, finger.pos) {
+                return;
+            }
+            let changed = if self.game.is_some() {
+                self.handle_game_press(ctx, finger.pos)
+            } else {
+                self.handle_files_press(finger.pos)
+            };
+            if changed {
+                self.redraw(ctx.get_framebuffer_ref());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use libremarkable::cgmath::Point2;
+    use pretty_assertions::assert_eq;
+
+    use crate::{
+        board::Stone,
+        game_parse::{get_game_data, get_moves},
+        review::move_numbers,
+    };
+
+    #[test]
+    fn numbers_after_recapture() {
+        // White's corner stone gets taken by move 4, then black plays there
+        let sgf = "(;SZ[5];W[aa];B[ba];W[ee];B[ab];W[dd];B[aa])";
+        let position = get_game_data(sgf).unwrap();
+        let moves = get_moves(sgf, None).unwrap();
+        let point = |x, y| Point2 { x, y };
+        assert_eq!(
+            vec![
+                (point(2, 1), 2, Stone::Black),
+                (point(5, 5), 3, Stone::White),
+                (point(1, 2), 4, Stone::Black),
+                (point(4, 4), 5, Stone::White),
+                (point(1, 1), 6, Stone::Black),
+            ],
+            move_numbers(&moves, &position)
+        );
+    }
+
+    #[test]
+    fn no_numbers_for_setup_stones() {
+        let sgf = "(;SZ[5]AB[cc];W[aa])";
+        let position = get_game_data(sgf).unwrap();
+        let moves = get_moves(sgf, None).unwrap();
+        assert_eq!(
+            vec![(Point2 { x: 1, y: 1 }, 1, Stone::White)],
+            move_numbers(&moves, &position)
+        );
+    }
+}
@@ -1,7 +1,11 @@
+use crate::engine::Engine;
 use std::time::Duration;
 
-use gtp::controller::Engine;
-use libremarkable::{appctx, framebuffer::core::Framebuffer, input::MultitouchEvent};
+use libremarkable::{
+    appctx,
+    framebuffer::core::Framebuffer,
+    input::{MultitouchEvent, PhysicalButton},
+};
 
 pub trait Routine: Send {
     fn init(&mut self, fb: &'static mut Framebuffer, ctrl: &mut Engine);
@@ -12,7 +16,21 @@ pub trait Routine: Send {
         ctrl: &mut Engine,
     );
 
-    fn update_loop(&mut self) -> Option<Duration> {
+    /// A hardware button was pressed. The middle one always goes back to the chooser, so this
+    /// only gets the others
+    fn on_button_event(
+        &mut self,
+        _ctx: &mut appctx::ApplicationContext<'_>,
+        _button: PhysicalButton,
+        _ctrl: &mut Engine,
+    ) {
+    }
+
+    /// Called from a background thread; the return value is how long to wait before calling again
+    fn update_loop(&mut self, _ctrl: &mut Engine) -> Option<Duration> {
         None
     }
+
+    /// Called when switching to another mode, before that mode's `init`
+    fn on_leave(&mut self, _ctrl: &mut Engine) {}
 }
@@ -0,0 +1,778 @@
+use lazy_static::lazy_static;
+use libremarkable::{cgmath::Vector2, framebuffer::common::color};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{fmt, fs, sync::Mutex, time::Duration};
+
+use crate::{
+    board::{BoardStyle, DEFAULT_GRID_WIDTH},
+    chooser::Mode,
+    clock::TimeControl,
+    lock,
+};
+
+const SETTINGS_FILE: &str = "/opt/fuseki-settings";
+pub const DEFAULT_STONE_BORDER: u16 = 5;
+const DEFAULT_IDLE_MINUTES: u64 = 15;
+const DEFAULT_REFRESH_GRACE_SECONDS: u64 = 10;
+// Much longer and it's no different from never reloading while the screen's in use
+const MAX_REFRESH_GRACE_SECONDS: u64 = 300;
+const DEFAULT_LOCAL_BOARD_SIZE: u8 = 9;
+// 5x5 and 7x7 are handy for teaching, and GnuGo won't go above 19x19
+const LOCAL_BOARD_SIZES: std::ops::RangeInclusive<u8> = 5..=19;
+const DEFAULT_UI_SCALE: f32 = 1.0;
+// Much bigger and the buttons above the board run into it
+const UI_SCALES: std::ops::RangeInclusive<f32> = 0.75..=1.5;
+// Past this the lines swallow the star points and small boards look like a maze
+const GRID_WIDTHS: std::ops::RangeInclusive<u32> = 1..=30;
+const DEFAULT_GNUGO_ARGS: [&str; 2] = ["--level", "8"];
+// More than half a 19x19 square, which would be a tap on the wrong point rather than a skew
+pub const MAX_TOUCH_OFFSET: i32 = 40;
+const DEFAULT_HOLD_MS: u64 = 800;
+const MAX_HOLD_MS: u64 = 5000;
+const MAX_DOUBLE_TAP_MS: u64 = 5000;
+// Past this the padding reaches well into the board from the buttons over and under it
+const MAX_TOUCH_PADDING: u32 = 40;
+/// GnuGo's `level`s, from weakest to strongest
+const MACHINE_LEVELS: std::ops::RangeInclusive<u8> = 0..=10;
+
+lazy_static! {
+    pub static ref SETTINGS: Settings = Settings::load();
+}
+
+// Calibrating changes it without a restart, so it can't just come from SETTINGS
+static TOUCH_OFFSET: Mutex<Option<Vector2<i32>>> = Mutex::new(None);
+
+/// Pixels to add to touches on the board, from the last calibration
+pub fn touch_offset() -> Vector2<i32> {
+    *lock(&TOUCH_OFFSET).get_or_insert_with(|| SETTINGS.touch_offset())
+}
+
+/// Uses `offset` from now on, and writes it to the settings file for next time. Errors are ready
+/// to show on screen
+pub fn save_touch_offset(offset: Vector2<i32>) -> Result<(), String> {
+    *lock(&TOUCH_OFFSET) = Some(offset);
+    let updated = with_touch_offset(fs::read(SETTINGS_FILE).ok().as_deref(), offset)?;
+    fs::write(SETTINGS_FILE, updated).map_err(|err| {
+        warn!("Can't write {SETTINGS_FILE}: {err}");
+        format!("Can't save to {SETTINGS_FILE}")
+    })
+}
+
+/// The settings file with a new touch offset, keeping everything else in it
+fn with_touch_offset(raw: Option<&[u8]>, offset: Vector2<i32>) -> Result<Vec<u8>, String> {
+    let mut settings: Map<String, Value> = match raw {
+        Some(raw) => serde_json::from_slice(raw).map_err(|err| {
+            warn!("Not overwriting bad settings in {SETTINGS_FILE}: {err}");
+            format!("{SETTINGS_FILE} is wrongly formatted, so not saved")
+        })?,
+        None => Map::new(),
+    };
+    settings.insert("touch_offset_x".to_string(), offset.x.into());
+    settings.insert("touch_offset_y".to_string(), offset.y.into());
+    Ok(serde_json::to_vec_pretty(&settings).expect("can dump settings"))
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq)]
+pub enum StoneStyle {
+    /// White stones are a black ring with a white middle
+    #[default]
+    Ring,
+    /// White stones are filled grey inside the outline, which is less ambiguous on some screens
+    Grey,
+}
+
+/// How the board's turned on screen. The moves sent to engines and servers stay the same
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq)]
+pub enum Orientation {
+    /// A1 in the bottom left, as usual
+    #[default]
+    Normal,
+    /// Left and right swapped, so A1 is in the bottom right
+    FlipHorizontal,
+    /// Top and bottom swapped, so A1 is in the top left
+    FlipVertical,
+    /// Turned a quarter clockwise, so A1 is in the top left
+    Rotate90,
+    /// Upside down, so A1 is in the top right
+    Rotate180,
+    /// Turned a quarter anticlockwise, so A1 is in the bottom right
+    Rotate270,
+}
+
+/// Scoring rules, which GnuGo needs telling at launch
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq)]
+pub enum Rules {
+    /// Territory scoring, which is also what GnuGo does without being told
+    #[default]
+    Japanese,
+    /// Area scoring
+    Chinese,
+}
+
+impl Rules {
+    const FLAGS: [&'static str; 2] = ["--japanese-rules", "--chinese-rules"];
+
+    /// GnuGo command line flag that picks these rules
+    pub fn flag(&self) -> &'static str {
+        match self {
+            Rules::Japanese => "--japanese-rules",
+            Rules::Chinese => "--chinese-rules",
+        }
+    }
+
+    /// The usual komi for an even game, as area scoring counts a point more for the last stone
+    pub fn komi(&self) -> f64 {
+        match self {
+            Rules::Japanese => 6.5,
+            Rules::Chinese => 7.5,
+        }
+    }
+}
+
+impl fmt::Display for Rules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+fn default_stone_border() -> u16 {
+    DEFAULT_STONE_BORDER
+}
+
+fn default_idle_minutes() -> u64 {
+    DEFAULT_IDLE_MINUTES
+}
+
+fn default_refresh_grace_seconds() -> u64 {
+    DEFAULT_REFRESH_GRACE_SECONDS
+}
+
+fn default_local_board_size() -> u8 {
+    DEFAULT_LOCAL_BOARD_SIZE
+}
+
+fn default_ui_scale() -> f32 {
+    DEFAULT_UI_SCALE
+}
+
+fn default_hold_ms() -> u64 {
+    DEFAULT_HOLD_MS
+}
+
+fn default_grey() -> u8 {
+    // Black, as `color::GRAY` counts up from white
+    255
+}
+
+fn default_grid_width() -> u32 {
+    DEFAULT_GRID_WIDTH
+}
+
+fn default_gnugo_args() -> Vec<String> {
+    DEFAULT_GNUGO_ARGS
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Settings {
+    /// Mode name (e.g. "DragonGoServer") to boot straight into, rather than the chooser
+    #[serde(default)]
+    pub start_mode: Option<String>,
+    #[serde(default)]
+    pub stone_style: StoneStyle,
+    #[serde(default)]
+    pub orientation: Orientation,
+    /// Width in pixels of the outline on white stones
+    #[serde(default = "default_stone_border")]
+    pub stone_border: u16,
+    /// How dark black stones (and the outline of white ones) are, from 0 (white) to 255 (black)
+    #[serde(default = "default_grey")]
+    pub stone_grey: u8,
+    /// How dark the grid lines are, from 0 (white) to 255 (black)
+    #[serde(default = "default_grey")]
+    pub grid_grey: u8,
+    /// Width in pixels of the grid lines
+    #[serde(default = "default_grid_width")]
+    pub grid_width: u32,
+    /// Minutes without a touch before Dragon Go Server blanks the screen. 0 means never
+    #[serde(default = "default_idle_minutes")]
+    pub idle_minutes: u64,
+    /// Seconds after the last touch before Dragon Go Server reloads the games, so it doesn't
+    /// change the board while it's being looked at
+    #[serde(default = "default_refresh_grace_seconds")]
+    pub refresh_grace_seconds: u64,
+    /// Board size for the machine and Atari games
+    #[serde(default = "default_local_board_size")]
+    pub local_board_size: u8,
+    /// Multiplier for text and button sizes, for bigger text or a different screen density
+    #[serde(default = "default_ui_scale")]
+    pub ui_scale: f32,
+    /// Clock for the human in machine games. No main time or byo-yomi means no clock
+    #[serde(default)]
+    pub machine_main_minutes: u64,
+    #[serde(default)]
+    pub machine_byo_yomi_seconds: u64,
+    /// Moves to make in each byo-yomi period
+    #[serde(default)]
+    pub machine_byo_yomi_stones: u32,
+    #[serde(default)]
+    pub rules: Rules,
+    /// GnuGo `level` for the machine's moves in machine games, sent before each one. `None`
+    /// leaves it at whatever `gnugo_args` started it with
+    #[serde(default)]
+    pub machine_level: Option<u8>,
+    /// Draw new stones on their own with a quick black and white refresh, rather than redrawing
+    /// the whole board after every move. Quicker on a slow device, at the cost of some ghosting
+    #[serde(default)]
+    pub fast_stones: bool,
+    /// In machine games, ask for a second tap before playing a move that puts its own group in
+    /// atari
+    #[serde(default)]
+    pub warn_self_atari: bool,
+    /// In machine games, the first tap just shows the move, and "Commit" plays it
+    #[serde(default)]
+    pub confirm_moves: bool,
+    /// In machine and Atari games, mark every point the side to move could legally play
+    #[serde(default)]
+    pub show_legal_moves: bool,
+    /// In machine and Atari games, captured stones get blacked out for a moment before they go
+    #[serde(default)]
+    pub flash_captures: bool,
+    /// A line over the board in machine and Dragon Go Server games with the size, komi, handicap
+    /// and whose turn it is
+    #[serde(default)]
+    pub show_game_info: bool,
+    /// In Dragon Go Server, a game with a new move from the opponent is shown without it first,
+    /// then it's drawn with a ring round it
+    #[serde(default)]
+    pub reveal_last_move: bool,
+    /// How long buttons that lose a game or quit (Exit, Resign, New game...) need holding down
+    /// before they work. 0 means a tap is enough
+    #[serde(default = "default_hold_ms")]
+    pub hold_ms: u64,
+    /// In machine and Atari games, a move only gets played when the same point's tapped again
+    /// within this many milliseconds. 0 means a single tap plays it
+    #[serde(default)]
+    pub double_tap_ms: u64,
+    /// Pixels round each button that still count as pressing it, without drawing it any bigger
+    #[serde(default)]
+    pub touch_padding: u32,
+    /// How much gets logged, e.g. "info" or "debug", for the log viewer as well as stderr.
+    /// Without it `RUST_LOG` decides, which is just errors if that's not set either
+    #[serde(default)]
+    pub log_level: Option<String>,
+    /// "host:port" of a GTP engine to use over the network instead of running GnuGo locally
+    #[serde(default)]
+    pub engine_address: Option<String>,
+    /// Extra command line arguments for GnuGo, e.g. "--level". Rules flags are dropped unless they
+    /// match `rules`, which is where to set them (e.g. to match a Dragon Go Server game)
+    #[serde(default = "default_gnugo_args")]
+    pub gnugo_args: Vec<String>,
+    /// Pixels added to touches on the board, so taps line up with the grid. Set by calibrating
+    #[serde(default)]
+    pub touch_offset_x: i32,
+    #[serde(default)]
+    pub touch_offset_y: i32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            start_mode: None,
+            stone_style: StoneStyle::default(),
+            orientation: Orientation::default(),
+            stone_border: DEFAULT_STONE_BORDER,
+            stone_grey: default_grey(),
+            grid_grey: default_grey(),
+            grid_width: DEFAULT_GRID_WIDTH,
+            idle_minutes: DEFAULT_IDLE_MINUTES,
+            refresh_grace_seconds: DEFAULT_REFRESH_GRACE_SECONDS,
+            local_board_size: DEFAULT_LOCAL_BOARD_SIZE,
+            ui_scale: DEFAULT_UI_SCALE,
+            machine_main_minutes: 0,
+            machine_byo_yomi_seconds: 0,
+            machine_byo_yomi_stones: 0,
+            rules: Rules::default(),
+            machine_level: None,
+            fast_stones: false,
+            warn_self_atari: false,
+            confirm_moves: false,
+            show_legal_moves: false,
+            flash_captures: false,
+            show_game_info: false,
+            reveal_last_move: false,
+            hold_ms: DEFAULT_HOLD_MS,
+            double_tap_ms: 0,
+            touch_padding: 0,
+            log_level: None,
+            engine_address: None,
+            gnugo_args: default_gnugo_args(),
+            touch_offset_x: 0,
+            touch_offset_y: 0,
+        }
+    }
+}
+
+impl Settings {
+    pub fn load() -> Settings {
+        match fs::read(SETTINGS_FILE) {
+            Ok(raw) => match serde_json::from_slice(&raw) {
+                Ok(settings) => settings,
+                Err(err) => {
+                    warn!("Error loading settings from {SETTINGS_FILE}: {err}");
+                    Settings::default()
+                }
+            },
+            Err(err) => {
+                info!("No settings loaded from {SETTINGS_FILE}: {err}");
+                Settings::default()
+            }
+        }
+    }
+
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        if self.idle_minutes == 0 {
+            None
+        } else {
+            Some(Duration::from_secs(self.idle_minutes * 60))
+        }
+    }
+
+    pub fn refresh_grace(&self) -> Duration {
+        if self.refresh_grace_seconds <= MAX_REFRESH_GRACE_SECONDS {
+            Duration::from_secs(self.refresh_grace_seconds)
+        } else {
+            warn!(
+                "Invalid refresh grace {}, using {MAX_REFRESH_GRACE_SECONDS}",
+                self.refresh_grace_seconds
+            );
+            Duration::from_secs(MAX_REFRESH_GRACE_SECONDS)
+        }
+    }
+
+    pub fn hold_time(&self) -> Duration {
+        if self.hold_ms <= MAX_HOLD_MS {
+            Duration::from_millis(self.hold_ms)
+        } else {
+            warn!(
+                "Invalid hold time {}, using {DEFAULT_HOLD_MS}",
+                self.hold_ms
+            );
+            Duration::from_millis(DEFAULT_HOLD_MS)
+        }
+    }
+
+    /// How long there is for the second tap, if moves need one
+    pub fn double_tap_window(&self) -> Option<Duration> {
+        match self.double_tap_ms {
+            0 => None,
+            ms if ms <= MAX_DOUBLE_TAP_MS => Some(Duration::from_millis(ms)),
+            ms => {
+                warn!("Invalid double tap time {ms}, using {MAX_DOUBLE_TAP_MS}");
+                Some(Duration::from_millis(MAX_DOUBLE_TAP_MS))
+            }
+        }
+    }
+
+    pub fn touch_padding(&self) -> u32 {
+        if self.touch_padding <= MAX_TOUCH_PADDING {
+            self.touch_padding
+        } else {
+            warn!(
+                "Invalid touch padding {}, using {MAX_TOUCH_PADDING}",
+                self.touch_padding
+            );
+            MAX_TOUCH_PADDING
+        }
+    }
+
+    pub fn local_board_size(&self) -> u8 {
+        if LOCAL_BOARD_SIZES.contains(&self.local_board_size) {
+            self.local_board_size
+        } else {
+            warn!(
+                "Invalid local board size {}, using {DEFAULT_LOCAL_BOARD_SIZE}",
+                self.local_board_size
+            );
+            DEFAULT_LOCAL_BOARD_SIZE
+        }
+    }
+
+    pub fn machine_level(&self) -> Option<u8> {
+        match self.machine_level {
+            Some(level) if !MACHINE_LEVELS.contains(&level) => {
+                warn!("Invalid machine level {level}, leaving the engine's level alone");
+                None
+            }
+            level => level,
+        }
+    }
+
+    pub fn ui_scale(&self) -> f32 {
+        if UI_SCALES.contains(&self.ui_scale) {
+            self.ui_scale
+        } else {
+            warn!(
+                "Invalid UI scale {}, using {DEFAULT_UI_SCALE}",
+                self.ui_scale
+            );
+            DEFAULT_UI_SCALE
+        }
+    }
+
+    pub fn board_style(&self) -> BoardStyle {
+        let grid_width = if GRID_WIDTHS.contains(&self.grid_width) {
+            self.grid_width
+        } else {
+            warn!(
+                "Invalid grid width {}, using {DEFAULT_GRID_WIDTH}",
+                self.grid_width
+            );
+            DEFAULT_GRID_WIDTH
+        };
+        BoardStyle {
+            stone_style: self.stone_style,
+            stone_border: self.stone_border,
+            grid_colour: color::GRAY(self.grid_grey),
+            grid_width,
+            stone_colour: color::GRAY(self.stone_grey),
+            orientation: self.orientation,
+        }
+    }
+
+    pub fn machine_clock(&self) -> Option<TimeControl> {
+        let byo_yomi = self.machine_byo_yomi_seconds > 0 && self.machine_byo_yomi_stones > 0;
+        if self.machine_main_minutes == 0 && !byo_yomi {
+            return None;
+        }
+        Some(TimeControl {
+            main_time: Duration::from_secs(self.machine_main_minutes * 60),
+            byo_yomi_time: Duration::from_secs(if byo_yomi {
+                self.machine_byo_yomi_seconds
+            } else {
+                0
+            }),
+            byo_yomi_stones: if byo_yomi {
+                self.machine_byo_yomi_stones
+            } else {
+                0
+            },
+        })
+    }
+
+    /// `gnugo_args`, always in GTP mode as that's all we can talk to, and using `rules`
+    pub fn gnugo_args(&self) -> Vec<String> {
+        let mut args = vec!["--mode".to_string(), "gtp".to_string()];
+        let mut configured = self.gnugo_args.iter();
+        while let Some(arg) = configured.next() {
+            if Rules::FLAGS.contains(&arg.as_str()) {
+                if arg != self.rules.flag() {
+                    warn!("Ignoring {arg}, as the rules are {}", self.rules);
+                }
+                continue;
+            }
+            let mode = if arg == "--mode" {
+                configured.next().map(String::as_str)
+            } else if let Some(mode) = arg.strip_prefix("--mode=") {
+                Some(mode)
+            } else {
+                args.push(arg.clone());
+                continue;
+            };
+            if mode != Some("gtp") {
+                warn!("Ignoring GnuGo mode {mode:?}, only gtp works");
+            }
+        }
+        args.push(self.rules.flag().to_string());
+        args
+    }
+
+    pub fn touch_offset(&self) -> Vector2<i32> {
+        let offset = Vector2 {
+            x: self.touch_offset_x,
+            y: self.touch_offset_y,
+        };
+        if offset.x.abs() > MAX_TOUCH_OFFSET || offset.y.abs() > MAX_TOUCH_OFFSET {
+            warn!("Invalid touch offset {offset:?}, using none");
+            return Vector2 { x: 0, y: 0 };
+        }
+        offset
+    }
+
+    pub fn start_mode(&self) -> Mode {
+        match self.start_mode {
+            None => Mode::Chooser,
+            // Starting in Exit would just quit straight away, so treat it like any other bad value
+            Some(ref name) => match Mode::from_name(name) {
+                Some(Mode::Exit) | None => {
+                    warn!("Invalid start mode '{name}', using the chooser");
+                    Mode::Chooser
+                }
+                Some(mode) => mode,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use libremarkable::{cgmath::Vector2, framebuffer::common::color};
+    use pretty_assertions::assert_eq;
+    use std::time::Duration;
+
+    use crate::{
+        board::BoardStyle,
+        chooser::Mode,
+        clock::TimeControl,
+        settings::{
+            with_touch_offset, Orientation, Rules, Settings, StoneStyle, DEFAULT_STONE_BORDER,
+        },
+    };
+
+    fn start_mode(raw: &str) -> Mode {
+        serde_json::from_str::<Settings>(raw).unwrap().start_mode()
+    }
+
+    #[test]
+    fn default_start_mode() {
+        assert_eq!(Mode::Chooser, start_mode("{}"));
+    }
+
+    #[test]
+    fn valid_start_mode() {
+        assert_eq!(
+            Mode::DragonGoServer,
+            start_mode(r#"{"start_mode": "DragonGoServer"}"#)
+        );
+    }
+
+    #[test]
+    fn stone_defaults() {
+        let settings = serde_json::from_str::<Settings>("{}").unwrap();
+        assert_eq!(StoneStyle::Ring, settings.stone_style);
+        assert_eq!(DEFAULT_STONE_BORDER, settings.stone_border);
+    }
+
+    #[test]
+    fn board_style() {
+        let style = serde_json::from_str::<Settings>("{}")
+            .unwrap()
+            .board_style();
+        // GRAY(255) is the same pixels as BLACK, so the board looks just as it always did
+        assert_eq!(BoardStyle::default().grid_width, style.grid_width);
+        assert_eq!(color::BLACK.as_native(), style.grid_colour.as_native());
+        assert_eq!(color::BLACK.as_native(), style.stone_colour.as_native());
+        let style = serde_json::from_str::<Settings>(r#"{"grid_grey": 128, "grid_width": 4}"#)
+            .unwrap()
+            .board_style();
+        assert_eq!(color::GRAY(128), style.grid_colour);
+        assert_eq!(4, style.grid_width);
+        let style = serde_json::from_str::<Settings>(r#"{"grid_width": 0}"#)
+            .unwrap()
+            .board_style();
+        assert_eq!(10, style.grid_width);
+        assert_eq!(Orientation::Normal, style.orientation);
+        let style = serde_json::from_str::<Settings>(r#"{"orientation": "Rotate90"}"#)
+            .unwrap()
+            .board_style();
+        assert_eq!(Orientation::Rotate90, style.orientation);
+    }
+
+    #[test]
+    fn double_tap_window() {
+        let settings = serde_json::from_str::<Settings>("{}").unwrap();
+        assert_eq!(None, settings.double_tap_window());
+        let settings = serde_json::from_str::<Settings>(r#"{"double_tap_ms": 400}"#).unwrap();
+        assert_eq!(
+            Some(Duration::from_millis(400)),
+            settings.double_tap_window()
+        );
+        let settings = serde_json::from_str::<Settings>(r#"{"double_tap_ms": 60000}"#).unwrap();
+        assert_eq!(
+            Some(Duration::from_millis(5000)),
+            settings.double_tap_window()
+        );
+    }
+
+    #[test]
+    fn hold_time() {
+        let settings = serde_json::from_str::<Settings>("{}").unwrap();
+        assert_eq!(Duration::from_millis(800), settings.hold_time());
+        let settings = serde_json::from_str::<Settings>(r#"{"hold_ms": 0}"#).unwrap();
+        assert_eq!(Duration::ZERO, settings.hold_time());
+        let settings = serde_json::from_str::<Settings>(r#"{"hold_ms": 60000}"#).unwrap();
+        assert_eq!(Duration::from_millis(800), settings.hold_time());
+    }
+
+    #[test]
+    fn touch_padding() {
+        let settings = serde_json::from_str::<Settings>("{}").unwrap();
+        assert_eq!(0, settings.touch_padding());
+        let settings = serde_json::from_str::<Settings>(r#"{"touch_padding": 25}"#).unwrap();
+        assert_eq!(25, settings.touch_padding());
+        let settings = serde_json::from_str::<Settings>(r#"{"touch_padding": 300}"#).unwrap();
+        assert_eq!(40, settings.touch_padding());
+    }
+
+    #[test]
+    fn touch_offset() {
+        let settings =
+            serde_json::from_str::<Settings>(r#"{"touch_offset_x": -5, "touch_offset_y": 12}"#)
+                .unwrap();
+        assert_eq!(Vector2 { x: -5, y: 12 }, settings.touch_offset());
+        let settings = serde_json::from_str::<Settings>(r#"{"touch_offset_x": 500}"#).unwrap();
+        assert_eq!(Vector2 { x: 0, y: 0 }, settings.touch_offset());
+    }
+
+    #[test]
+    fn saved_touch_offset() {
+        let offset = Vector2 { x: 3, y: -7 };
+        let saved = with_touch_offset(
+            Some(br#"{"fast_stones": true, "touch_offset_x": 1}"#),
+            offset,
+        )
+        .unwrap();
+        let settings = serde_json::from_slice::<Settings>(&saved).unwrap();
+        assert!(settings.fast_stones);
+        assert_eq!(offset, settings.touch_offset());
+        let saved = with_touch_offset(None, offset).unwrap();
+        assert_eq!(
+            offset,
+            serde_json::from_slice::<Settings>(&saved)
+                .unwrap()
+                .touch_offset()
+        );
+        assert!(with_touch_offset(Some(b"not json"), offset).is_err());
+    }
+
+    #[test]
+    fn idle_timeout() {
+        let settings = serde_json::from_str::<Settings>("{}").unwrap();
+        assert_eq!(Some(Duration::from_secs(15 * 60)), settings.idle_timeout());
+        let settings = serde_json::from_str::<Settings>(r#"{"idle_minutes": 0}"#).unwrap();
+        assert_eq!(None, settings.idle_timeout());
+    }
+
+    #[test]
+    fn refresh_grace() {
+        let settings = serde_json::from_str::<Settings>("{}").unwrap();
+        assert_eq!(Duration::from_secs(10), settings.refresh_grace());
+        let settings = serde_json::from_str::<Settings>(r#"{"refresh_grace_seconds": 0}"#).unwrap();
+        assert_eq!(Duration::ZERO, settings.refresh_grace());
+        let settings =
+            serde_json::from_str::<Settings>(r#"{"refresh_grace_seconds": 9000}"#).unwrap();
+        assert_eq!(Duration::from_secs(300), settings.refresh_grace());
+    }
+
+    #[test]
+    fn local_board_size() {
+        let settings = serde_json::from_str::<Settings>(r#"{"local_board_size": 5}"#).unwrap();
+        assert_eq!(5, settings.local_board_size());
+        let settings = serde_json::from_str::<Settings>(r#"{"local_board_size": 1}"#).unwrap();
+        assert_eq!(9, settings.local_board_size());
+        let settings = serde_json::from_str::<Settings>(r#"{"local_board_size": 25}"#).unwrap();
+        assert_eq!(9, settings.local_board_size());
+    }
+
+    #[test]
+    fn machine_level() {
+        let settings = serde_json::from_str::<Settings>("{}").unwrap();
+        assert_eq!(None, settings.machine_level());
+        let settings = serde_json::from_str::<Settings>(r#"{"machine_level": 3}"#).unwrap();
+        assert_eq!(Some(3), settings.machine_level());
+        let settings = serde_json::from_str::<Settings>(r#"{"machine_level": 11}"#).unwrap();
+        assert_eq!(None, settings.machine_level());
+    }
+
+    #[test]
+    fn ui_scale() {
+        let settings = serde_json::from_str::<Settings>("{}").unwrap();
+        assert_eq!(1.0, settings.ui_scale());
+        let settings = serde_json::from_str::<Settings>(r#"{"ui_scale": 1.25}"#).unwrap();
+        assert_eq!(1.25, settings.ui_scale());
+        let settings = serde_json::from_str::<Settings>(r#"{"ui_scale": 3}"#).unwrap();
+        assert_eq!(1.0, settings.ui_scale());
+    }
+
+    #[test]
+    fn machine_clock() {
+        let settings = serde_json::from_str::<Settings>("{}").unwrap();
+        assert_eq!(None, settings.machine_clock());
+        let settings = serde_json::from_str::<Settings>(
+            r#"{"machine_main_minutes": 10, "machine_byo_yomi_seconds": 30}"#,
+        )
+        .unwrap();
+        // Byo-yomi needs a number of stones as well
+        assert_eq!(
+            Some(TimeControl {
+                main_time: Duration::from_secs(600),
+                byo_yomi_time: Duration::ZERO,
+                byo_yomi_stones: 0
+            }),
+            settings.machine_clock()
+        );
+        let settings = serde_json::from_str::<Settings>(
+            r#"{"machine_byo_yomi_seconds": 300, "machine_byo_yomi_stones": 10}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            Some(TimeControl {
+                main_time: Duration::ZERO,
+                byo_yomi_time: Duration::from_secs(300),
+                byo_yomi_stones: 10
+            }),
+            settings.machine_clock()
+        );
+    }
+
+    fn gnugo_args(raw: &str) -> Vec<String> {
+        serde_json::from_str::<Settings>(raw).unwrap().gnugo_args()
+    }
+
+    #[test]
+    fn always_gtp_mode() {
+        assert_eq!(
+            vec!["--mode", "gtp", "--level", "8", "--japanese-rules"],
+            gnugo_args("{}")
+        );
+        assert_eq!(
+            vec!["--mode", "gtp", "--level", "10", "--japanese-rules"],
+            gnugo_args(r#"{"gnugo_args": ["--level", "10"]}"#)
+        );
+        assert_eq!(
+            vec!["--mode", "gtp", "--capture-all-dead", "--japanese-rules"],
+            gnugo_args(r#"{"gnugo_args": ["--mode", "ascii", "--capture-all-dead"]}"#)
+        );
+        assert_eq!(
+            vec!["--mode", "gtp", "--japanese-rules"],
+            gnugo_args(r#"{"gnugo_args": ["--mode=gtp"]}"#)
+        );
+    }
+
+    #[test]
+    fn rules() {
+        assert_eq!(
+            Rules::Japanese,
+            serde_json::from_str::<Settings>("{}").unwrap().rules
+        );
+        assert_eq!(
+            vec!["--mode", "gtp", "--chinese-rules"],
+            gnugo_args(r#"{"rules": "Chinese", "gnugo_args": []}"#)
+        );
+        // The rules setting wins over a clashing flag
+        assert_eq!(
+            vec!["--mode", "gtp", "--japanese-rules"],
+            gnugo_args(r#"{"gnugo_args": ["--chinese-rules"]}"#)
+        );
+    }
+
+    #[test]
+    fn invalid_start_mode() {
+        assert_eq!(Mode::Chooser, start_mode(r#"{"start_mode": "Chess"}"#));
+        assert_eq!(Mode::Chooser, start_mode(r#"{"start_mode": "Exit"}"#));
+    }
+}
@@ -0,0 +1,163 @@
+use chrono::Local;
+use libremarkable::cgmath::Point2;
+
+use crate::board::Stone;
+use log::info;
+use std::{fs, io, path::PathBuf};
+
+const SAVE_DIR: &str = "/opt/fuseki-games";
+pub const PROBLEM_DIR: &str = "/opt/fuseki-problems";
+
+/// SGF point, e.g. "cd" for (2, 3). 0-based from the top-left, like `Board::pixel_to_point`
+pub fn sgf_point(point: Point2<u8>) -> String {
+    format!("{}{}", (b'a' + point.x) as char, (b'a' + point.y) as char)
+}
+
+fn point_list(points: &[Point2<u8>]) -> String {
+    points
+        .iter()
+        .map(|p| format!("[{}]", sgf_point(*p)))
+        .collect()
+}
+
+/// A game record with just setup stones on, e.g. from the analysis board
+pub fn setup_sgf(size: u8, black_stones: &[Point2<u8>], white_stones: &[Point2<u8>]) -> String {
+    let mut sgf = format!("(;FF[4]GM[1]AP[Fuseki]SZ[{size}]");
+    if !black_stones.is_empty() {
+        sgf += &format!("AB{}", point_list(black_stones));
+    }
+    if !white_stones.is_empty() {
+        sgf += &format!("AW{}", point_list(white_stones));
+    }
+    sgf + ")\n"
+}
+
+/// A played game, with `moves` being 0-based from the top-left and `None` for a pass
+pub fn game_sgf(size: u8, moves: &[(Stone, Option<Point2<u8>>)], result: Option<&str>) -> String {
+    let mut sgf = format!("(;FF[4]GM[1]AP[Fuseki]SZ[{size}]");
+    if let Some(result) = result {
+        sgf += &format!("RE[{result}]");
+    }
+    for (colour, point) in moves {
+        sgf += &format!(
+            ";{}[{}]",
+            match colour {
+                Stone::Black => "B",
+                Stone::White => "W",
+            },
+            point.map(sgf_point).unwrap_or_default()
+        );
+    }
+    sgf + ")\n"
+}
+
+/// Writes `contents` to a new timestamped file in the save directory
+pub fn save_sgf(prefix: &str, contents: &str) -> io::Result<PathBuf> {
+    fs::create_dir_all(SAVE_DIR)?;
+    let path = PathBuf::from(SAVE_DIR).join(format!(
+        "{prefix}-{}.sgf",
+        Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    fs::write(&path, contents)?;
+    info!("Saved {}", path.display());
+    Ok(path)
+}
+
+/// Every SGF file in the save directory, newest first
+pub fn saved_games() -> io::Result<Vec<PathBuf>> {
+    sgf_files(SAVE_DIR)
+}
+
+/// Every SGF file in the problem directory, newest first
+pub fn problem_files() -> io::Result<Vec<PathBuf>> {
+    sgf_files(PROBLEM_DIR)
+}
+
+fn sgf_files(dir: &str) -> io::Result<Vec<PathBuf>> {
+    let mut games = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "sgf") {
+            let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+            games.push((modified, path));
+        }
+    }
+    games.sort_by(|a, b| b.cmp(a));
+    Ok(games.into_iter().map(|(_, path)| path).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use libremarkable::cgmath::Point2;
+    use pretty_assertions::assert_eq;
+
+    use crate::{
+        board::Stone,
+        game_parse::get_game_data,
+        sgf::{game_sgf, setup_sgf, sgf_point},
+    };
+
+    #[test]
+    fn game() {
+        assert_eq!(
+            "(;FF[4]GM[1]AP[Fuseki]SZ[9]RE[W+3.5];B[ee];W[cc];B[])\n",
+            game_sgf(
+                9,
+                &[
+                    (Stone::Black, Some(Point2 { x: 4, y: 4 })),
+                    (Stone::White, Some(Point2 { x: 2, y: 2 })),
+                    (Stone::Black, None)
+                ],
+                Some("W+3.5")
+            )
+        );
+    }
+
+    #[test]
+    fn game_loads_back() {
+        let game_data = get_game_data(&game_sgf(
+            9,
+            &[
+                (Stone::Black, Some(Point2 { x: 4, y: 4 })),
+                (Stone::White, Some(Point2 { x: 2, y: 2 })),
+            ],
+            None,
+        ))
+        .unwrap();
+        assert_eq!(vec![Point2 { x: 5, y: 5 }], game_data.black_stones);
+        assert_eq!(vec![Point2 { x: 3, y: 3 }], game_data.white_stones);
+    }
+
+    #[test]
+    fn points() {
+        assert_eq!("aa", sgf_point(Point2 { x: 0, y: 0 }));
+        assert_eq!("cd", sgf_point(Point2 { x: 2, y: 3 }));
+        assert_eq!("ss", sgf_point(Point2 { x: 18, y: 18 }));
+    }
+
+    #[test]
+    fn setup() {
+        assert_eq!(
+            "(;FF[4]GM[1]AP[Fuseki]SZ[9]AB[cc][gg]AW[ee])\n",
+            setup_sgf(
+                9,
+                &[Point2 { x: 2, y: 2 }, Point2 { x: 6, y: 6 }],
+                &[Point2 { x: 4, y: 4 }]
+            )
+        );
+        assert_eq!("(;FF[4]GM[1]AP[Fuseki]SZ[5])\n", setup_sgf(5, &[], &[]));
+    }
+
+    #[test]
+    fn setup_loads_back() {
+        let game_data = get_game_data(&setup_sgf(
+            9,
+            &[Point2 { x: 2, y: 3 }],
+            &[Point2 { x: 5, y: 0 }],
+        ))
+        .unwrap();
+        assert_eq!(9, game_data.size);
+        assert_eq!(vec![Point2 { x: 3, y: 4 }], game_data.black_stones);
+        assert_eq!(vec![Point2 { x: 6, y: 1 }], game_data.white_stones);
+    }
+}